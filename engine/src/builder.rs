@@ -0,0 +1,150 @@
+use std::{collections::HashMap, fmt::Debug, sync::Arc};
+
+use crate::{
+    bytecode::Function, compile_internal, host::OpaqueType, lint::LintRule, program::Program,
+    rt::RtRef,
+};
+
+/// Configures and compiles scripts.
+pub struct EngineBuilder {
+    funcs: Vec<Function>,
+    /// the functions scripts may not call, with the reason why
+    denied: HashMap<&'static str, String>,
+    rules: Vec<LintRule>,
+    config: VmConfig,
+    verbose: bool,
+}
+
+impl EngineBuilder {
+    pub fn new() -> Self {
+        Self {
+            funcs: vec![],
+            denied: HashMap::new(),
+            rules: vec![],
+            config: VmConfig::default(),
+            verbose: false,
+        }
+    }
+
+    /// registers a host function which can be called by scripts
+    pub fn function(mut self, func: Function) -> Self {
+        self.funcs.push(func);
+        self
+    }
+
+    pub fn functions(mut self, funcs: impl IntoIterator<Item = Function>) -> Self {
+        self.funcs.extend(funcs);
+        self
+    }
+
+    /// rejects scripts which call the function `name` when they are compiled, `reason` tells
+    /// their authors why
+    pub fn deny(mut self, name: &'static str, reason: impl Into<String>) -> Self {
+        self.denied.insert(name, reason.into());
+        self
+    }
+
+    /// checks scripts for `rule` in addition to the engine's own lints
+    pub fn lint(mut self, rule: LintRule) -> Self {
+        self.rules.push(rule);
+        self
+    }
+
+    /// the maximum number of instructions a single execution may take before it's aborted
+    pub fn instruction_limit(mut self, limit: u64) -> Self {
+        self.config.instruction_limit = Some(limit);
+        self
+    }
+
+    /// the seed of the rng which is used by executions, if no seed is set, every execution will
+    /// use a different one
+    pub fn seed(mut self, seed: u64) -> Self {
+        self.config.seed = Some(seed);
+        self
+    }
+
+    /// the sink which gets informed about everything executions do
+    pub fn trace(mut self, sink: impl TraceSink + 'static) -> Self {
+        self.config.trace = Some(Arc::new(sink));
+        self
+    }
+
+    /// the maximum number of bytes the values of a single execution may occupy, including
+    /// the contents of strings and lists. Executions exceeding it are aborted, the bytes are
+    /// counted as values come and go, so checking it is cheap.
+    pub fn memory_limit(mut self, bytes: usize) -> Self {
+        self.config.memory_limit = Some(bytes);
+        self
+    }
+
+    /// records every host function call of an execution together with its arguments and
+    /// result, the host takes the records from the vm using `Vm::take_audit_log`
+    pub fn audit(mut self, audit: bool) -> Self {
+        self.config.audit = audit;
+        self
+    }
+
+    /// registers the opaque type whose values are created using `tag`,
+    /// values of unregistered opaque types can't be passed to scripts
+    pub fn opaque_type(mut self, tag: u32, ty: OpaqueType) -> Self {
+        self.config.opaque_types.insert(tag, ty);
+        self
+    }
+
+    /// dumps the intermediate compilation stages
+    pub fn verbose(mut self, verbose: bool) -> Self {
+        self.verbose = verbose;
+        self
+    }
+
+    pub fn compile(self, src: &str) -> anyhow::Result<Program> {
+        compile_internal(
+            src,
+            self.funcs,
+            self.denied,
+            self.rules,
+            self.config,
+            self.verbose,
+        )
+    }
+}
+
+impl Default for EngineBuilder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[derive(Clone, Default)]
+pub(crate) struct VmConfig {
+    pub(crate) instruction_limit: Option<u64>,
+    pub(crate) seed: Option<u64>,
+    pub(crate) trace: Option<Arc<dyn TraceSink>>,
+    pub(crate) memory_limit: Option<usize>,
+    pub(crate) audit: bool,
+    pub(crate) opaque_types: HashMap<u32, OpaqueType>,
+}
+
+/// Receives events from executing scripts, this can be used for debugging and logging.
+pub trait TraceSink: Send + Sync {
+    fn trace(&self, event: TraceEvent<'_>);
+}
+
+impl<F: Fn(TraceEvent<'_>) + Send + Sync> TraceSink for F {
+    fn trace(&self, event: TraceEvent<'_>) {
+        self(event)
+    }
+}
+
+#[derive(Debug)]
+pub enum TraceEvent<'a> {
+    Instruction {
+        ip: usize,
+        op: &'a dyn Debug,
+    },
+    HostCall {
+        name: &'static str,
+        args: &'a [RtRef],
+        ret: Option<RtRef>,
+    },
+}
@@ -4,6 +4,8 @@ use thin_vec::{thin_vec, ThinVec};
 
 use crate::{
     ast::AstNode,
+    diagnostic_builder,
+    host::HostFn,
     parser::Stmt,
     rt::{Ordering, RtRef, RtType},
 };
@@ -12,12 +14,13 @@ use crate::{
 #[repr(u8)]
 pub enum ByteCode {
     Push {
-        val: RtRef,
+        /// the index of the pushed value in the program's constant pool
+        const_idx: UHalf,
     },
     Pop {
         /// the offset describes how many value should be skipped starting from the most recent element
         /// when looking for an element to pop from the stack
-        offset: u8, // offsets other than 0 and 1 are unsupported
+        offset: u8, // offsets other than 0 and 1 are unsupported as the vm pops by swapping
     },
     Mov {
         src_idx: UHalf,
@@ -69,11 +72,20 @@ pub enum ByteCode {
         arg2_idx: UHalf,
         expected: Ordering,
     },
+    /// returns from the current script function, if there is none, the execution ends
     Return {
-        has_val: bool,
+        val_idx: Option<UHalf>,
     },
     CallLocal {
-        relative_off: isize,
+        /// the index of the called function in the program's script functions
+        fn_idx: UHalf,
+        push_val: bool,
+        arg_indices: ThinVec<UHalf>,
+    },
+    /// calls a script function in place of the current one, reusing its frame
+    TailCallLocal {
+        fn_idx: UHalf,
+        arg_indices: ThinVec<UHalf>,
     },
 }
 
@@ -88,31 +100,79 @@ pub struct Function {
     pub params: &'static [RtType],
     pub var_len: bool,
     pub name: &'static str,
-    pub call: fn(Vec<RtRef>) -> Option<RtRef>,
+    /// what the function does in a single line, shown to script authors
+    pub desc: &'static str,
+    /// the groups the function belongs to, hosts can deny calling a whole group at once
+    pub groups: &'static [&'static str],
+    pub call: HostFn,
+}
+
+/// A function defined by the script itself.
+#[derive(Debug)]
+pub struct ScriptFn {
+    pub name: String,
+    /// the index of the function's first instruction
+    pub offset: usize,
+    pub params: usize,
+    /// the maximum number of values the function's frame holds
+    pub max_depth: usize,
+}
+
+/// The literals of a program. Bytecode refers to them by index, so it doesn't contain any
+/// pointers, the pool owns the literals' heap data and the vm pushes copies of them.
+#[derive(Debug, Default)]
+pub(crate) struct ConstPool {
+    vals: Vec<RtRef>,
+}
+
+impl ConstPool {
+    #[inline]
+    pub(crate) fn get(&self, idx: UHalf) -> RtRef {
+        self.vals[idx as usize]
+    }
+
+    /// takes over `val` and returns its index
+    fn add(&mut self, val: RtRef) -> UHalf {
+        self.vals.push(val);
+        (self.vals.len() - 1) as UHalf
+    }
+}
+
+impl Drop for ConstPool {
+    fn drop(&mut self) {
+        for val in self.vals.drain(..) {
+            // literals are never lists or opaque values
+            if val.ty() == RtType::String {
+                let _ = unsafe { Box::from_raw(val.dst().cast::<String>()) };
+            }
+        }
+    }
 }
 
 struct Scope {
     vars: Vec<String>,
 }
 
-struct ResolvableCall {
-    args: usize,
-    name: String,
-    location_idx: usize,
+struct LocalFn {
+    idx: usize,
+    params: usize,
+    returns_val: bool,
 }
 
 struct Translator<'a> {
     code: Vec<ByteCode>,
     internal_fns: HashMap<String, InternalFn>,
-    local_fns: &'a HashMap<String, bool>,
+    local_fns: &'a HashMap<String, LocalFn>,
     fns: &'a Vec<Function>,
+    consts: &'a mut ConstPool,
     stack_idx: usize,
+    /// the maximum number of values the unit's frame ever holds
+    max_depth: usize,
     vars: HashMap<String, Vec<usize>>,
-    call_resolution: Vec<ResolvableCall>,
 }
 
 impl<'a> Translator<'a> {
-    fn translate_internal(&mut self, stmts: &Vec<Stmt>) {
+    fn translate_internal(&mut self, stmts: &Vec<Stmt>) -> anyhow::Result<()> {
         let mut curr_scope = Scope { vars: vec![] };
         let initial_stack_idx = self.stack_idx;
         for stmt in stmts {
@@ -123,9 +183,14 @@ impl<'a> Translator<'a> {
                     reassign,
                 } => {
                     let mut pops = 0;
-                    let var_idx = self.translate_node(val, &mut pops);
+                    let var_idx = self.translate_node(val, &mut pops)?;
                     if *reassign {
-                        let indices = self.vars.get(name).unwrap();
+                        let Some(indices) = self.vars.get(name) else {
+                            return diagnostic_builder!(format!(
+                                "There is no variable named `{}` to assign to",
+                                name
+                            ));
+                        };
                         let idx = indices.last().unwrap();
                         self.code.push(ByteCode::Mov {
                             dst_idx: *idx as UHalf,
@@ -141,26 +206,35 @@ impl<'a> Translator<'a> {
                     }
                 }
                 Stmt::CallFunc { name, args } => {
-                    let fn_idx = self.resolve_fn_idx(name);
+                    if let Some(local_fn) = self.local_fns.get(name) {
+                        check_arg_cnt(name, local_fn.params, false, args.len())?;
+                        let mut pops = 0;
+                        let mut indices = thin_vec![];
+                        for arg in args {
+                            indices.push(self.translate_node(&arg, &mut pops)? as UHalf);
+                        }
+                        self.code.push(ByteCode::CallLocal {
+                            fn_idx: local_fn.idx as UHalf,
+                            push_val: false,
+                            arg_indices: indices,
+                        });
+                        for _ in 0..pops {
+                            self.code.push(ByteCode::Pop { offset: 0 });
+                        }
+                        self.stack_idx -= pops;
+                        continue;
+                    }
+                    let fn_idx = self.resolve_fn_idx(name)?;
 
                     // FIXME: check arg types
 
-                    // if the argument count doesn't match and if the function isn't of variable length
-                    // or if the required arguments of the variable length function aren't present,
-                    // fail calling and inform the user
-                    if (!self.fns[fn_idx].var_len || self.fns[fn_idx].params.len() > args.len())
-                        && self.fns[fn_idx].params.len() != args.len()
-                    {
-                        panic!(
-                            "Function arg count mismatch (\"{}\")",
-                            self.fns[fn_idx].name
-                        );
-                    }
+                    let func = &self.fns[fn_idx];
+                    check_arg_cnt(name, func.params.len(), func.var_len, args.len())?;
 
                     let mut pops = 0;
                     let mut indices = thin_vec![];
                     for arg in args {
-                        indices.push(self.translate_node(&arg, &mut pops) as UHalf);
+                        indices.push(self.translate_node(&arg, &mut pops)? as UHalf);
                     }
                     self.code.push(ByteCode::Call {
                         fn_idx: fn_idx as u8,
@@ -177,12 +251,11 @@ impl<'a> Translator<'a> {
                     // and only ever jump up if the statement is true
                     let loop_start_len = self.code.len();
                     let mut pops = 0;
-                    self.translate_internal(stmts);
+                    self.translate_internal(stmts)?;
                     let body_size = self.code.len() - loop_start_len;
-                    println!("body size: {}", body_size);
 
                     // this is the argument for the condition which decides whether to continue with the loop
-                    let arg_idx = self.translate_node(&condition, &mut pops);
+                    let arg_idx = self.translate_node(&condition, &mut pops)?;
 
                     // cleanup for when we are in the loop
                     for _ in 0..pops {
@@ -217,7 +290,7 @@ impl<'a> Translator<'a> {
                     let mut jump_indices = vec![];
                     for (cond, stmts) in seq.iter() {
                         let mut pops = 0;
-                        let cond_val_idx = self.translate_node(&cond, &mut pops);
+                        let cond_val_idx = self.translate_node(&cond, &mut pops)?;
                         let cond_idx = self.code.len();
 
                         let prev_code_size = self.code.len();
@@ -226,7 +299,7 @@ impl<'a> Translator<'a> {
                             self.code.push(ByteCode::Pop { offset: 1 });
                         }
                         self.stack_idx -= pops;
-                        self.translate_internal(stmts);
+                        self.translate_internal(stmts)?;
                         let code_size = self.code.len() - prev_code_size;
                         self.code.insert(
                             cond_idx,
@@ -244,7 +317,7 @@ impl<'a> Translator<'a> {
                         }
                     }
                     // insert the fallback (if present)
-                    self.translate_internal(fallback);
+                    self.translate_internal(fallback)?;
                     // insert the jumps to the end of the if-(else) construct to ensure only 1 branch is ever taken
                     for idx in jump_indices.iter().rev() {
                         let end = self.code.len();
@@ -255,24 +328,71 @@ impl<'a> Translator<'a> {
                     }
                 }
                 Stmt::DefineFn { name, args, stmts } => {
-                    let code = translate_unit(stmts, self.fns, self.local_fns);
-                    if !code.0.fns.is_empty() {
-                        panic!("Nested function definitions are disallowed");
+                    let Some(local_fn) = self.local_fns.get(name) else {
+                        return diagnostic_builder!(format!(
+                            "`{}` is defined inside of a block, functions may only be defined at \
+                             the top level",
+                            name
+                        ));
+                    };
+                    let mut stmts = stmts.clone();
+                    // a trailing call to a function which doesn't return anything can reuse
+                    // the frame just like `return f()`, as both return `null`
+                    if let Some(Stmt::CallFunc { name, args }) = stmts.last() {
+                        if self.local_fns.get(name).is_some_and(|func| !func.returns_val) {
+                            let call = AstNode::CallFunc {
+                                name: name.clone(),
+                                params: args.clone(),
+                            };
+                            *stmts.last_mut().unwrap() = Stmt::Return { val: Some(call) };
+                        }
+                    }
+                    let mut code =
+                        translate_unit(&stmts, args, self.fns, self.local_fns, self.consts)?;
+                    if !code.fns.is_empty() {
+                        return diagnostic_builder!(format!(
+                            "`{}` defines a function, functions may only be defined at the top \
+                             level",
+                            name
+                        ));
                     }
-                    let internal_fn_idx = self.internal_fns.len();
+                    // functions without an explicit return at their end return implicitly
+                    code.main.push(ByteCode::Return { val_idx: None });
                     self.internal_fns.insert(name.clone(), InternalFn {
-                        code: code.0.main,
+                        code: code.main,
                         params: args.clone(),
-                        call_resolution: code.1,
-                        func_idx: internal_fn_idx,
+                        func_idx: local_fn.idx,
+                        max_depth: code.max_depth,
                     });
                 },
-                Stmt::Return { val } => {
-                    if let Some(val) = val {
-                        let mut _pops = 0;
-                        self.translate_node(val, &mut _pops);
+                Stmt::Return {
+                    val: Some(AstNode::CallFunc { name, params }),
+                } if self.local_fns.contains_key(name) => {
+                    let local_fn = &self.local_fns[name];
+                    check_arg_cnt(name, local_fn.params, false, params.len())?;
+                    let mut _pops = 0;
+                    let mut indices = thin_vec![];
+                    for param in params {
+                        indices.push(self.translate_node(param, &mut _pops)? as UHalf);
                     }
-                    self.code.push(ByteCode::Return { has_val: val.is_some() });
+                    // the callee's return value is returned directly, so the frame
+                    // isn't needed anymore and the callee can take it over
+                    self.code.push(ByteCode::TailCallLocal {
+                        fn_idx: local_fn.idx as UHalf,
+                        arg_indices: indices,
+                    });
+                    break;
+                },
+                Stmt::Return { val } => {
+                    let val_idx = match val {
+                        Some(val) => {
+                            let mut _pops = 0;
+                            Some(self.translate_node(val, &mut _pops)? as UHalf)
+                        }
+                        None => None,
+                    };
+                    // the vm discards the whole frame on return, so there's no need to pop anything
+                    self.code.push(ByteCode::Return { val_idx });
                     // discard all remaining code as it won't ever be executed and thus can be considered dead code
                     break;
                 },
@@ -288,42 +408,48 @@ impl<'a> Translator<'a> {
             self.code.push(ByteCode::Pop { offset: 0 });
         }
         self.stack_idx = initial_stack_idx;
+        Ok(())
     }
 
     /// returns the corresponding stack index
-    fn translate_node(&mut self, node: &AstNode, pops: &mut usize) -> usize {
+    fn translate_node(&mut self, node: &AstNode, pops: &mut usize) -> anyhow::Result<usize> {
         match node {
             AstNode::CallFunc { name, params } => {
-                if self.local_fns.contains_key(name) {
+                if let Some(local_fn) = self.local_fns.get(name) {
+                    check_arg_cnt(name, local_fn.params, false, params.len())?;
                     let mut call_pops = 0;
                     let mut indices = thin_vec![];
                     for param in params {
-                        indices.push(self.translate_node(param, &mut call_pops) as UHalf);
+                        indices.push(self.translate_node(param, &mut call_pops)? as UHalf);
                     }
 
-                    // push a placeholder instruction to be replaced later on
+                    // functions which don't return anything push `null`
                     self.code.push(ByteCode::CallLocal {
-                        relative_off: 0,
-                    }); // FIXME: should we push val?
+                        fn_idx: local_fn.idx as UHalf,
+                        push_val: true,
+                        arg_indices: indices,
+                    });
 
-                    let offset = if *self.local_fns.get(name).unwrap() { 1 } else { 0 };
                     for _ in 0..call_pops {
-                        self.code.push(ByteCode::Pop { offset });
+                        self.code.push(ByteCode::Pop { offset: 1 });
                     }
 
                     *pops += 1;
                     self.stack_idx += 1;
+                    self.record_depth();
 
                     self.stack_idx -= call_pops;
 
-                    return self.stack_idx - 1;
+                    return Ok(self.stack_idx - 1);
                 }
-                let func_idx = self.resolve_fn_idx(name);
+                let func_idx = self.resolve_fn_idx(name)?;
+                let func = &self.fns[func_idx];
+                check_arg_cnt(name, func.params.len(), func.var_len, params.len())?;
 
                 let mut call_pops = 0;
                 let mut indices = thin_vec![];
                 for param in params {
-                    indices.push(self.translate_node(param, &mut call_pops) as UHalf);
+                    indices.push(self.translate_node(param, &mut call_pops)? as UHalf);
                 }
 
                 self.code.push(ByteCode::Call {
@@ -338,15 +464,16 @@ impl<'a> Translator<'a> {
 
                 *pops += 1;
                 self.stack_idx += 1;
+                self.record_depth();
 
                 self.stack_idx -= call_pops;
 
-                self.stack_idx - 1
+                Ok(self.stack_idx - 1)
             }
             AstNode::BinOp { lhs, rhs, op } => {
                 let mut local_pops = 0;
-                let idx1 = self.translate_node(lhs, &mut local_pops);
-                let idx2 = self.translate_node(rhs, &mut local_pops);
+                let idx1 = self.translate_node(lhs, &mut local_pops)?;
+                let idx2 = self.translate_node(rhs, &mut local_pops)?;
                 match op {
                     crate::ast::BinOpKind::Add => {
                         self.code.push(ByteCode::Add {
@@ -436,49 +563,55 @@ impl<'a> Translator<'a> {
                 for _ in 0..local_pops {
                     self.code.push(ByteCode::Pop { offset: 1 });
                 }
+                // the result is pushed before the operands get popped
+                self.stack_idx += 1;
+                self.record_depth();
                 self.stack_idx -= local_pops;
 
                 *pops += 1;
-                self.stack_idx += 1;
-                self.stack_idx - 1
+                Ok(self.stack_idx - 1)
             }
             AstNode::Val(val) => {
-                self.code.push(ByteCode::Push { val: *val });
+                let const_idx = self.consts.add(*val);
+                self.code.push(ByteCode::Push { const_idx });
                 *pops += 1;
                 self.stack_idx += 1;
-                self.stack_idx - 1
+                self.record_depth();
+                Ok(self.stack_idx - 1)
             }
             AstNode::Var { name } => {
                 if let Some(val) = self.vars.get(name) {
-                    return *val.last().unwrap();
+                    return Ok(*val.last().unwrap());
                 }
-                if let Some(val) = self.internal_fns.get(name) {
-                    self.code.push(ByteCode::Push { val: RtRef::function(val.func_idx) });
+                if let Some(val) = self.local_fns.get(name) {
+                    let const_idx = self.consts.add(RtRef::function(val.idx));
+                    self.code.push(ByteCode::Push { const_idx });
                     *pops += 1;
                     self.stack_idx += 1;
-                    return self.stack_idx - 1;
+                    self.record_depth();
+                    return Ok(self.stack_idx - 1);
                 }
 
-                panic!("No variable or function named \"{}\"", name);
+                diagnostic_builder!(format!("There is no variable or function named `{}`", name))
             },
-            AstNode::UnaryOp { val, op } => match *op {
+            AstNode::UnaryOp { op, .. } => match *op {
+                // FIXME: support negation
                 crate::ast::UnaryOpKind::Not => {
-                    todo!()
+                    diagnostic_builder!("The `!` operator isn't supported yet")
                 }
             },
         }
     }
 
-    // FIXME: handle missing functions
-    fn resolve_fn_idx(&self, fn_name: &String) -> usize {
-        let fn_idx = self
-            .fns
-            .iter()
-            .enumerate()
-            .find(|(_, val)| &val.name == fn_name)
-            .expect(format!("Function \"{fn_name}\" doesn't exist").as_str())
-            .0;
-        fn_idx
+    fn record_depth(&mut self) {
+        self.max_depth = self.max_depth.max(self.stack_idx);
+    }
+
+    fn resolve_fn_idx(&self, fn_name: &String) -> anyhow::Result<usize> {
+        match self.fns.iter().position(|val| &val.name == fn_name) {
+            Some(fn_idx) => Ok(fn_idx),
+            None => diagnostic_builder!(format!("There is no function named `{}`", fn_name)),
+        }
     }
 
     fn optimize(&mut self) {
@@ -513,9 +646,9 @@ impl<'a> Translator<'a> {
                             }
                         }
                         self.code.remove(i);
+                        // the constant stays in the pool, which frees it along with the program
                         self.code.remove(i - 1);
                         optimized = false;
-                        // FIXME: deallocate if necessary
 
                         // continue optimization cycle
                         break;
@@ -528,99 +661,167 @@ impl<'a> Translator<'a> {
 
 struct TranslationOutput {
     main: Vec<ByteCode>,
+    max_depth: usize,
     fns: HashMap<String, InternalFn>,
-    
 }
 
 struct InternalFn {
     func_idx: usize,
     code: Vec<ByteCode>,
     params: Vec<String>,
-    call_resolution: Vec<ResolvableCall>,
+    max_depth: usize,
 }
 
-fn translate_unit(stmts: &Vec<Stmt>, fns: &Vec<Function>, local_fns: &HashMap<String, bool>) -> (TranslationOutput, Vec<ResolvableCall>) {
+/// translates a unit of code, `params` are the parameters of the function the unit belongs to,
+/// which the caller places at the beginning of the unit's frame
+fn translate_unit(
+    stmts: &Vec<Stmt>,
+    params: &[String],
+    fns: &Vec<Function>,
+    local_fns: &HashMap<String, LocalFn>,
+    consts: &mut ConstPool,
+) -> anyhow::Result<TranslationOutput> {
     let mut translator = Translator {
         code: vec![],
         fns,
-        stack_idx: 0,
-        vars: HashMap::new(),
+        consts,
+        stack_idx: params.len(),
+        max_depth: params.len(),
+        vars: params
+            .iter()
+            .enumerate()
+            .map(|(idx, param)| (param.clone(), vec![idx]))
+            .collect(),
         internal_fns: HashMap::new(),
-        call_resolution: vec![],
         local_fns,
     };
-    translator.translate_internal(stmts);
+    translator.translate_internal(stmts)?;
     translator.optimize();
-    (TranslationOutput {
+    Ok(TranslationOutput {
         main: translator.code,
+        max_depth: translator.max_depth,
         fns: translator.internal_fns,
-    }, translator.call_resolution)
+    })
 }
 
-struct IntermediateFn {
-    args: usize,
-    offset: usize,
-    call_res: Vec<ResolvableCall>,
+/// fails if a function taking `params` arguments (or at least `params` if it's `var_len`)
+/// is called with `args` arguments
+fn check_arg_cnt(name: &str, params: usize, var_len: bool, args: usize) -> anyhow::Result<()> {
+    if args == params || (var_len && args > params) {
+        return Ok(());
+    }
+    diagnostic_builder!(format!(
+        "`{}` takes {}{} argument{}, but is called with {}",
+        name,
+        if var_len { "at least " } else { "" },
+        params,
+        if params == 1 { "" } else { "s" },
+        args
+    ))
+}
+
+/// Collects the names of all functions `stmts` call, in the order they appear.
+pub fn called_fns<'a>(stmts: &'a [Stmt], calls: &mut Vec<&'a str>) {
+    fn visit<'a>(node: &'a AstNode, calls: &mut Vec<&'a str>) {
+        match node {
+            AstNode::CallFunc { name, params } => {
+                calls.push(name);
+                params.iter().for_each(|param| visit(param, calls));
+            }
+            AstNode::UnaryOp { val, .. } => visit(val, calls),
+            AstNode::BinOp { lhs, rhs, .. } => {
+                visit(lhs, calls);
+                visit(rhs, calls);
+            }
+            AstNode::Val(_) | AstNode::Var { .. } => {}
+        }
+    }
+    for stmt in stmts {
+        match stmt {
+            Stmt::DefineVar { val, .. } => visit(val, calls),
+            Stmt::DefineFn { stmts, .. } => called_fns(stmts, calls),
+            Stmt::CallFunc { name, args } => {
+                calls.push(name);
+                args.iter().for_each(|arg| visit(arg, calls));
+            }
+            Stmt::Loop { stmts, condition } => {
+                visit(condition, calls);
+                called_fns(stmts, calls);
+            }
+            Stmt::Conditional { seq, fallback } => {
+                for (condition, stmts) in seq {
+                    visit(condition, calls);
+                    called_fns(stmts, calls);
+                }
+                called_fns(fallback, calls);
+            }
+            Stmt::Return { val } => {
+                if let Some(val) = val {
+                    visit(val, calls);
+                }
+            }
+        }
+    }
 }
 
-fn discover_fn_defs(stmts: &Vec<Stmt>) -> anyhow::Result<HashMap<String, bool>> {
+fn discover_fn_defs(stmts: &Vec<Stmt>) -> anyhow::Result<HashMap<String, LocalFn>> {
     let mut defs = HashMap::new();
     for stmt in stmts {
-        if let Stmt::DefineFn { name, stmts, .. } = stmt {
+        if let Stmt::DefineFn { name, args, stmts } = stmt {
             let mut has_val = None;
             for stmt in stmts {
-                let val = if let Stmt::Return { val: Some(_) } = stmt {
-                    true
-                } else {
-                    false
+                let Stmt::Return { val } = stmt else {
+                    continue;
                 };
+                let val = val.is_some();
                 if let Some(curr_val) = has_val {
                     if curr_val != val {
-                        panic!("A function may only always or never return a value");
+                        return diagnostic_builder!(format!(
+                            "`{}` returns a value in some places but not in others, a function \
+                             may only always or never return a value",
+                            name
+                        ));
                     }
                 } else {
                     has_val = Some(val);
                 }
             }
-            defs.insert(name.clone(), has_val.unwrap_or(false));
+            let idx = defs.len();
+            defs.insert(name.clone(), LocalFn {
+                idx,
+                params: args.len(),
+                returns_val: has_val.unwrap_or(false),
+            });
         }
     }
     Ok(defs)
 }
 
-pub fn translate(stmts: &Vec<Stmt>, fns: &Vec<Function>) -> Vec<ByteCode> {
-    let fn_defs = discover_fn_defs(stmts).unwrap();
-    let res = translate_unit(stmts, fns, &fn_defs);
-
-    // resolve addresses of local functions at call sites
-    let mut bc = res.0.main;
-    let mut fn_stack = vec![];
-    let mut fn_lookup = HashMap::new();
-    for fun in res.0.fns {
-        fn_stack.push(IntermediateFn {
-            args: fun.1.params.len(),
+/// translates the script into bytecode, the main code comes first and is followed by the code
+/// of all script functions. The maximum depth of the main code's stack is returned as well.
+/// The literals of the script are moved into `consts`.
+pub fn translate(
+    stmts: &Vec<Stmt>,
+    fns: &Vec<Function>,
+    consts: &mut ConstPool,
+) -> anyhow::Result<(Vec<ByteCode>, Vec<ScriptFn>, usize)> {
+    let fn_defs = discover_fn_defs(stmts)?;
+    let res = translate_unit(stmts, &[], fns, &fn_defs, consts)?;
+
+    let mut bc = res.main;
+    // the main code ends the execution once it's done instead of running into the functions
+    bc.push(ByteCode::Return { val_idx: None });
+    let mut local_fns = res.fns.into_iter().collect::<Vec<_>>();
+    local_fns.sort_by_key(|(_, fun)| fun.func_idx);
+    let mut script_fns = vec![];
+    for (name, fun) in local_fns {
+        script_fns.push(ScriptFn {
+            name,
             offset: bc.len(),
-            call_res: fun.1.call_resolution,
+            params: fun.params.len(),
+            max_depth: fun.max_depth,
         });
-        fn_lookup.insert(fun.0.clone(), fn_stack.len() - 1);
-        bc.extend(fun.1.code);
-    }
-    for i in (0..fn_stack.len()).rev() {
-        for res in &fn_stack[i].call_res {
-            let fun = &fn_stack[*fn_lookup.get(&res.name).expect("Function call to unknown function")];
-            if fun.args != res.args {
-                panic!("Argument count mismatch");
-            }
-            bc[fn_stack[i].offset + res.location_idx] = ByteCode::CallLocal { relative_off: (fun.offset - res.location_idx) as isize };
-        }
-    }
-    // FIXME: handle functions with and without return value properly!
-    for res in res.1 {
-        let fun = &fn_stack[*fn_lookup.get(&res.name).expect("Function call to unknown function")];
-        if fun.args != res.args {
-            panic!("Argument count mismatch");
-        }
-        bc[res.location_idx] = ByteCode::CallLocal { relative_off: (fun.offset - res.location_idx) as isize };
+        bc.extend(fun.code);
     }
-    bc
+    Ok((bc, script_fns, res.max_depth))
 }
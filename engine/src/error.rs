@@ -3,6 +3,7 @@ use std::{
     fmt::{Display, Formatter},
 };
 
+#[cfg(feature = "term")]
 use crossterm::style::Stylize;
 
 use crate::span::Span;
@@ -97,26 +98,24 @@ impl DiagnosticItem {
         match self {
             DiagnosticItem::Error(str, span) => {
                 if !span.is_none() {
-                    DiagnosticBuilder::build_span_string(span).red().to_string()
+                    red(DiagnosticBuilder::build_span_string(span))
                         + "\n"
                         + &" ".repeat(span.start)
-                        + &str.to_string().red().to_string()
+                        + &red(str.to_string())
                         + "\n"
                 } else {
-                    str.to_string().red().to_string() + "\n"
+                    red(str.to_string()) + "\n"
                 }
             }
             DiagnosticItem::Warn(str, span) => {
                 if !span.is_none() {
-                    DiagnosticBuilder::build_span_string(span)
-                        .yellow()
-                        .to_string()
+                    yellow(DiagnosticBuilder::build_span_string(span))
                         + "\n"
                         + &" ".repeat(span.start)
-                        + &str.to_string().yellow().to_string()
+                        + &yellow(str.to_string())
                         + "\n"
                 } else {
-                    str.to_string().yellow().to_string() + "\n"
+                    yellow(str.to_string()) + "\n"
                 }
             }
             DiagnosticItem::Suggestion(str, span) => {
@@ -135,6 +134,28 @@ impl DiagnosticItem {
     }
 }
 
+// without terminal support diagnostics are left uncolored
+
+#[cfg(feature = "term")]
+fn red(str: String) -> String {
+    str.red().to_string()
+}
+
+#[cfg(not(feature = "term"))]
+fn red(str: String) -> String {
+    str
+}
+
+#[cfg(feature = "term")]
+fn yellow(str: String) -> String {
+    str.yellow().to_string()
+}
+
+#[cfg(not(feature = "term"))]
+fn yellow(str: String) -> String {
+    str
+}
+
 #[macro_export]
 macro_rules! diagnostic_builder {
     ($error:literal) => {
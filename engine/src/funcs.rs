@@ -1,14 +1,109 @@
-use crate::rt::RtRef;
-
-pub fn println(args: Vec<RtRef>) -> Option<RtRef> {
-    let val = args[0].get_string().unwrap().clone();
-    let mut fmt = String::new();
-    let mut split = val.split("{}");
-    fmt.push_str(split.next().unwrap());
-    for (idx, val) in split.enumerate() {
-        fmt.push_str(&args[idx + 1].to_string());
-        fmt.push_str(val);
-    }
-    println!("{}", fmt);
-    None
+use crate::{
+    bytecode::Function,
+    host::{HostCtx, HostError},
+    host_fn,
+    rt::RtRef,
+};
+
+host_fn! {
+    /// formats the values into the `{}`s of the string and prints it
+    #[name = "println"]
+    #[group = "io"]
+    pub fn println(_ctx: &mut HostCtx, val: String, ..args: RtRef) -> Result<(), HostError> {
+        let mut fmt = String::new();
+        let mut split = val.split("{}");
+        fmt.push_str(split.next().unwrap());
+        for (idx, val) in split.enumerate() {
+            let arg = args
+                .get(idx)
+                .ok_or_else(|| HostError::new(format!("missing argument {} to format", idx + 1)))?;
+            fmt.push_str(&arg.to_string());
+            fmt.push_str(val);
+        }
+        println!("{}", fmt);
+        Ok(())
+    }
+}
+
+host_fn! {
+    /// the absolute value of the number
+    #[name = "abs"]
+    pub fn abs(_ctx: &mut HostCtx, val: f64) -> Result<f64, HostError> {
+        Ok(val.abs())
+    }
+}
+
+host_fn! {
+    /// the number rounded down
+    #[name = "floor"]
+    pub fn floor(_ctx: &mut HostCtx, val: f64) -> Result<f64, HostError> {
+        Ok(val.floor())
+    }
+}
+
+host_fn! {
+    /// the number rounded up
+    #[name = "ceil"]
+    pub fn ceil(_ctx: &mut HostCtx, val: f64) -> Result<f64, HostError> {
+        Ok(val.ceil())
+    }
+}
+
+host_fn! {
+    /// the number rounded to the nearest integer, halfway cases away from zero
+    #[name = "round"]
+    pub fn round(_ctx: &mut HostCtx, val: f64) -> Result<f64, HostError> {
+        Ok(val.round())
+    }
+}
+
+host_fn! {
+    /// the square root of the number
+    #[name = "sqrt"]
+    pub fn sqrt(_ctx: &mut HostCtx, val: f64) -> Result<f64, HostError> {
+        if val < 0.0 {
+            return Err(HostError::new(format!("can't take the square root of {}", val)));
+        }
+        Ok(val.sqrt())
+    }
+}
+
+host_fn! {
+    /// `base` raised to the power of `exp`
+    #[name = "pow"]
+    pub fn pow(_ctx: &mut HostCtx, base: f64, exp: f64) -> Result<f64, HostError> {
+        Ok(base.powf(exp))
+    }
+}
+
+host_fn! {
+    /// the smallest of the passed values
+    #[name = "min"]
+    pub fn min(_ctx: &mut HostCtx, val: f64, ..others: f64) -> Result<f64, HostError> {
+        Ok(others.into_iter().fold(val, f64::min))
+    }
+}
+
+host_fn! {
+    /// the largest of the passed values
+    #[name = "max"]
+    pub fn max(_ctx: &mut HostCtx, val: f64, ..others: f64) -> Result<f64, HostError> {
+        Ok(others.into_iter().fold(val, f64::max))
+    }
+}
+
+/// The functions every program can call, unless the host passes its own functions with
+/// the same names.
+pub fn builtins() -> Vec<Function> {
+    vec![
+        println(),
+        abs(),
+        floor(),
+        ceil(),
+        round(),
+        sqrt(),
+        pow(),
+        min(),
+        max(),
+    ]
 }
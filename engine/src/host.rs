@@ -0,0 +1,430 @@
+use std::{
+    any::Any,
+    error::Error,
+    fmt::{Display, Formatter},
+    future::Future,
+    pin::Pin,
+};
+
+use serde::Serialize;
+
+use crate::{
+    bytecode::Function,
+    rng::Rng,
+    rt::{CardInventoryRef, CardVal, Player, RtRef, RtType},
+};
+
+pub type HostFn =
+    Box<dyn Fn(&mut HostCtx, &[RtRef]) -> Result<Option<RtRef>, HostError> + Send + Sync>;
+
+pub type HostFuture = Pin<Box<dyn Future<Output = Result<Option<RtRef>, HostError>> + Send>>;
+
+impl Function {
+    /// Creates a host function whose result is computed by a future, e.g. because it has to wait
+    /// for a remote player. The future has to copy everything it needs out of the arguments.
+    /// Executions are suspended while the future is pending, so they have to be run using
+    /// `Vm::run_async` which awaits it. `groups` are the groups the function belongs to.
+    pub fn new_async(
+        name: &'static str,
+        desc: &'static str,
+        params: &'static [RtType],
+        var_len: bool,
+        groups: &'static [&'static str],
+        call: impl Fn(&mut HostCtx, &[RtRef]) -> HostFuture + Send + Sync + 'static,
+    ) -> Self {
+        Self {
+            params,
+            var_len,
+            name,
+            desc,
+            groups,
+            call: Box::new(move |ctx, args| {
+                let future = call(ctx, args);
+                Ok(ctx.suspend(PendingCall { name, future }))
+            }),
+        }
+    }
+}
+
+/// The signature of a host function as plain data, for tools which tell script authors
+/// what they can call (editors, documentation).
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct FunctionInfo {
+    pub name: &'static str,
+    pub desc: &'static str,
+    /// the types of the required parameters, `any` for parameters which accept every value
+    pub params: Vec<String>,
+    /// whether any number of arguments may follow the required ones
+    pub var_len: bool,
+}
+
+impl Function {
+    pub fn info(&self) -> FunctionInfo {
+        FunctionInfo {
+            name: self.name,
+            desc: self.desc,
+            params: self
+                .params
+                .iter()
+                .map(|ty| match ty {
+                    RtType::None => "any".to_string(),
+                    ty => ty.to_string(),
+                })
+                .collect(),
+            var_len: self.var_len,
+        }
+    }
+}
+
+/// the request async host functions suspend the execution with
+pub(crate) struct PendingCall {
+    pub(crate) name: &'static str,
+    pub(crate) future: HostFuture,
+}
+
+/// The context host functions are called with, it's owned by the vm and
+/// lives as long as the execution it belongs to.
+pub struct HostCtx {
+    pub(crate) rng: Rng,
+    data: Option<Box<dyn Any + Send>>,
+    pub(crate) suspension: Option<Box<dyn Any + Send>>,
+}
+
+impl HostCtx {
+    pub(crate) fn new(rng: Rng) -> Self {
+        Self {
+            rng,
+            data: None,
+            suspension: None,
+        }
+    }
+
+    /// All randomness in a game has to go through this rng in order
+    /// for games to be reproducible from their seed.
+    pub fn rng(&mut self) -> &mut Rng {
+        &mut self.rng
+    }
+
+    /// Attaches host specific data to the execution, replacing any data that was attached before.
+    pub fn set_data<T: Any + Send>(&mut self, data: T) {
+        self.data = Some(Box::new(data));
+    }
+
+    /// Returns the data attached by the host, if it's of type `T`.
+    pub fn data<T: Any>(&self) -> Option<&T> {
+        self.data.as_ref().and_then(|data| data.downcast_ref::<T>())
+    }
+
+    pub fn data_mut<T: Any>(&mut self) -> Option<&mut T> {
+        self.data.as_mut().and_then(|data| data.downcast_mut::<T>())
+    }
+
+    /// Suspends the execution once the calling host function returns and hands `request` to
+    /// the host, instead of blocking until e.g. a player made a choice. The answer the host
+    /// resumes the execution with becomes the result of the call.
+    /// The returned value is meant to be returned by the host function: `Ok(ctx.suspend(request))`
+    pub fn suspend<T: Any + Send>(&mut self, request: T) -> Option<RtRef> {
+        self.suspension = Some(Box::new(request));
+        None
+    }
+}
+
+/// The callbacks the vm uses to manage values of an opaque type, which are values owned by the
+/// host that scripts can only pass around (e.g. a network session).
+#[derive(Clone)]
+pub struct OpaqueType {
+    pub name: &'static str,
+    /// called whenever the script copies a value, e.g. when assigning it to another variable
+    pub clone: fn(&(dyn Any + Send)) -> Box<dyn Any + Send>,
+    /// called once the value isn't referenced by the script anymore
+    pub cleanup: fn(Box<dyn Any + Send>),
+}
+
+impl OpaqueType {
+    /// An opaque type whose values are cloned and dropped like any other rust value.
+    pub fn new<T: Any + Send + Clone>(name: &'static str) -> Self {
+        Self {
+            name,
+            clone: |val| Box::new(val.downcast_ref::<T>().unwrap().clone()),
+            cleanup: drop,
+        }
+    }
+}
+
+/// An error raised by a host function, the vm aborts the execution of the script
+/// with it and attaches the name of the function that raised it.
+#[derive(Debug)]
+pub struct HostError {
+    msg: String,
+}
+
+impl HostError {
+    pub fn new(msg: impl Into<String>) -> Self {
+        Self { msg: msg.into() }
+    }
+
+    /// the argument at `idx` is either missing or not of the type `expected`
+    pub fn invalid_arg(idx: usize, expected: RtType) -> Self {
+        Self::new(format!(
+            "expected argument {} to be of type {:?}",
+            idx, expected
+        ))
+    }
+}
+
+impl Display for HostError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.msg)
+    }
+}
+
+impl Error for HostError {}
+
+/// Values which can be passed from scripts to host functions.
+pub trait FromRtRef: Sized {
+    /// the type scripts have to pass
+    const TY: RtType;
+
+    fn from_ref(val: RtRef) -> Option<Self>;
+}
+
+/// Values which can be returned from host functions to scripts.
+pub trait IntoRtRef {
+    fn into_ref(self) -> RtRef;
+}
+
+/// any value is accepted, so it's up to the host function to inspect it
+impl FromRtRef for RtRef {
+    const TY: RtType = RtType::None;
+
+    fn from_ref(val: RtRef) -> Option<Self> {
+        Some(val)
+    }
+}
+
+impl FromRtRef for f64 {
+    const TY: RtType = RtType::Decimal;
+
+    fn from_ref(val: RtRef) -> Option<Self> {
+        val.get_decimal()
+    }
+}
+
+impl FromRtRef for usize {
+    const TY: RtType = RtType::Decimal;
+
+    fn from_ref(val: RtRef) -> Option<Self> {
+        val.get_num().map(|val| val as usize)
+    }
+}
+
+impl FromRtRef for bool {
+    const TY: RtType = RtType::Bool;
+
+    fn from_ref(val: RtRef) -> Option<Self> {
+        val.get_bool()
+    }
+}
+
+impl FromRtRef for String {
+    const TY: RtType = RtType::String;
+
+    fn from_ref(val: RtRef) -> Option<Self> {
+        val.get_string().cloned()
+    }
+}
+
+impl FromRtRef for Player {
+    const TY: RtType = RtType::Player;
+
+    fn from_ref(val: RtRef) -> Option<Self> {
+        val.get_player()
+    }
+}
+
+impl FromRtRef for CardInventoryRef {
+    const TY: RtType = RtType::Inventory;
+
+    fn from_ref(val: RtRef) -> Option<Self> {
+        val.get_inventory()
+    }
+}
+
+impl FromRtRef for CardVal {
+    const TY: RtType = RtType::Card;
+
+    fn from_ref(val: RtRef) -> Option<Self> {
+        val.get_card()
+    }
+}
+
+impl<T: FromRtRef> FromRtRef for Vec<T> {
+    const TY: RtType = RtType::List;
+
+    fn from_ref(val: RtRef) -> Option<Self> {
+        val.get_list()?
+            .iter()
+            .map(|val| T::from_ref(*val))
+            .collect()
+    }
+}
+
+impl IntoRtRef for () {
+    fn into_ref(self) -> RtRef {
+        RtRef::NULL
+    }
+}
+
+impl IntoRtRef for RtRef {
+    fn into_ref(self) -> RtRef {
+        self
+    }
+}
+
+impl IntoRtRef for f64 {
+    fn into_ref(self) -> RtRef {
+        RtRef::decimal(self)
+    }
+}
+
+impl IntoRtRef for usize {
+    fn into_ref(self) -> RtRef {
+        RtRef::decimal(self as f64)
+    }
+}
+
+impl IntoRtRef for bool {
+    fn into_ref(self) -> RtRef {
+        RtRef::bool(self)
+    }
+}
+
+impl IntoRtRef for String {
+    fn into_ref(self) -> RtRef {
+        RtRef::string(Box::new(self))
+    }
+}
+
+impl IntoRtRef for Player {
+    fn into_ref(self) -> RtRef {
+        RtRef::player(self)
+    }
+}
+
+impl IntoRtRef for CardInventoryRef {
+    fn into_ref(self) -> RtRef {
+        RtRef::inventory(self)
+    }
+}
+
+impl IntoRtRef for CardVal {
+    fn into_ref(self) -> RtRef {
+        RtRef::card(self)
+    }
+}
+
+impl<T: IntoRtRef> IntoRtRef for Option<T> {
+    fn into_ref(self) -> RtRef {
+        self.map_or(RtRef::NULL, T::into_ref)
+    }
+}
+
+impl<T: IntoRtRef> IntoRtRef for Vec<T> {
+    fn into_ref(self) -> RtRef {
+        RtRef::list(Box::new(
+            self.into_iter().map(T::into_ref).collect::<Vec<_>>(),
+        ))
+    }
+}
+
+/// Extracts the argument at `idx` for a host function generated by `host_fn!`.
+#[doc(hidden)]
+pub fn host_arg<T: FromRtRef>(args: &[RtRef], idx: usize) -> Result<T, HostError> {
+    args.get(idx)
+        .and_then(|val| T::from_ref(*val))
+        .ok_or_else(|| HostError::invalid_arg(idx, T::TY))
+}
+
+/// Converts the return value of a host function generated by `host_fn!`.
+#[doc(hidden)]
+pub fn host_ret<T: IntoRtRef>(val: T) -> Option<RtRef> {
+    let val = val.into_ref();
+    (val.ty() != RtType::None).then_some(val)
+}
+
+/// Generates a function returning the `Function` descriptor for an ordinary rust function,
+/// taking care of extracting and validating its arguments.
+/// The first parameter is the `HostCtx`, the remaining ones have to implement `FromRtRef`
+/// and the function has to return a `Result` whose value implements `IntoRtRef`.
+/// A trailing `..name: T` parameter collects all remaining arguments into a `Vec<T>`.
+/// The doc comment becomes the function's description and every `#[group = "..."]` following
+/// the name adds the function to that group.
+///
+/// ```ignore
+/// host_fn! {
+///     /// puts the cards into the inventory
+///     #[name = "giveCards"]
+///     #[group = "inventory"]
+///     pub fn give_cards(
+///         ctx: &mut HostCtx,
+///         inv: CardInventoryRef,
+///         cards: Vec<CardVal>,
+///     ) -> Result<(), HostError> {
+///         ...
+///     }
+/// }
+/// ```
+#[macro_export]
+macro_rules! host_fn {
+    (@var_len) => {
+        false
+    };
+    (@var_len $rest:ident) => {
+        true
+    };
+    (
+        $(#[doc = $doc:literal])*
+        #[name = $script_name:literal]
+        $(#[group = $group:literal])*
+        $vis:vis fn $name:ident(
+            $ctx:ident: $ctx_ty:ty
+            $(, $arg:ident: $ty:ty)*
+            $(, ..$rest:ident: $rest_ty:ty)?
+            $(,)?
+        ) -> $ret:ty $body:block
+    ) => {
+        $(#[doc = $doc])*
+        $vis fn $name() -> $crate::Function {
+            fn $name($ctx: $ctx_ty $(, $arg: $ty)* $(, $rest: Vec<$rest_ty>)?) -> $ret $body
+
+            const PARAMS: &[$crate::RtType] = &[$(<$ty as $crate::FromRtRef>::TY),*];
+            $crate::Function {
+                params: PARAMS,
+                var_len: $crate::host_fn!(@var_len $($rest)?),
+                name: $script_name,
+                desc: concat!($($doc),*).trim_ascii(),
+                groups: &[$($group),*],
+                call: Box::new(|ctx, args| {
+                    if args.len() < PARAMS.len() {
+                        return Err($crate::HostError::new(format!(
+                            "expected {} arguments, but got {}",
+                            PARAMS.len(),
+                            args.len()
+                        )));
+                    }
+                    let mut _idx = 0;
+                    $(
+                        let $arg = $crate::host_arg::<$ty>(args, _idx)?;
+                        _idx += 1;
+                    )*
+                    $(
+                        let $rest = (_idx..args.len())
+                            .map(|idx| $crate::host_arg::<$rest_ty>(args, idx))
+                            .collect::<Result<Vec<_>, _>>()?;
+                    )?
+                    $name(ctx $(, $arg)* $(, $rest)?).map($crate::host_ret)
+                }),
+            }
+        }
+    };
+}
@@ -1,42 +1,135 @@
-use std::fs;
+use std::{collections::HashMap, sync::Arc};
 
+use crate::{builder::VmConfig, bytecode::ConstPool};
 pub use crate::{
+    builder::{EngineBuilder, TraceEvent, TraceSink},
     bytecode::Function,
-    rt::{CardInventory, CardInventoryRef, CardVal, Player, RtRef, RtType},
+    funcs::builtins,
+    host::{FromRtRef, FunctionInfo, HostCtx, HostError, HostFn, HostFuture, IntoRtRef, OpaqueType},
+    lint::{Lint, LintRule},
+    program::Program,
+    rng::Rng,
+    rt::{CardInventory, CardInventoryRef, CardVal, Player, RtRef, RtType, RtValue},
+    vm::{Frame, HostCall, Status, Vm, VmSnapshot},
 };
+#[doc(hidden)]
+pub use crate::host::{host_arg, host_ret};
 
 mod ast;
+mod builder;
 mod bytecode;
 mod error;
 mod funcs;
+mod host;
 mod lexer;
+mod lint;
 mod nan_box;
 mod parser;
+mod program;
+mod rng;
 mod rt;
 mod span;
 mod vm;
+#[cfg(feature = "wasm")]
+pub mod wasm;
 
-pub fn run(path: &str, mut funcs: Vec<Function>) -> anyhow::Result<()> {
-    if !funcs.iter().any(|fun| fun.name == "println") {
-        funcs.push(Function {
-            params: &[RtType::String],
-            name: "println",
-            call: funcs::println,
-            var_len: true,
-        });
-    }
-    let tokens = lexer::lex(&fs::read_to_string(path).unwrap()).unwrap();
-    println!("lexed {} tokens: {:?}", tokens.len(), tokens);
-    let ast = parser::parse(tokens).unwrap();
-    println!("parsed ast: {:?}", ast);
-    let byte_code = bytecode::translate(&ast, &funcs);
-    println!(
-        "generated byte code ({} ops): {:?}",
-        byte_code.len(),
-        byte_code
-    );
-    let mut vm = vm::Vm::new(byte_code, funcs);
+/// The version of the script language the engine compiles, it's raised whenever scripts
+/// written for the previous version may no longer compile or behave differently.
+pub const LANGUAGE_VERSION: u32 = 1;
+
+#[cfg(feature = "fs")]
+pub fn run(path: &str, funcs: Vec<Function>) -> anyhow::Result<()> {
+    let program = EngineBuilder::new()
+        .functions(funcs)
+        .verbose(true)
+        .compile(&std::fs::read_to_string(path)?)?;
     println!("started vm");
-    vm.run();
-    Ok(())
+    program.execute()
+}
+
+/// Compiles the script source `src` against the passed host functions.
+/// Any problems with the script are reported as diagnostics.
+pub fn compile(src: &str, funcs: Vec<Function>) -> anyhow::Result<Program> {
+    EngineBuilder::new().functions(funcs).compile(src)
+}
+
+/// The signatures of the functions scripts compiled against `funcs` can call, including the
+/// builtins, sorted by name.
+pub fn registry(funcs: Vec<Function>) -> Vec<FunctionInfo> {
+    let mut infos = with_builtins(funcs)
+        .iter()
+        .map(Function::info)
+        .collect::<Vec<_>>();
+    infos.sort_by_key(|info| info.name);
+    infos
+}
+
+/// `registry` as json, the format external tools read it in.
+pub fn registry_json(funcs: Vec<Function>) -> String {
+    serde_json::to_string_pretty(&registry(funcs)).unwrap()
+}
+
+/// `funcs` together with the builtins they don't replace
+fn with_builtins(mut funcs: Vec<Function>) -> Vec<Function> {
+    for builtin in funcs::builtins() {
+        if !funcs.iter().any(|fun| fun.name == builtin.name) {
+            funcs.push(builtin);
+        }
+    }
+    funcs
+}
+
+fn compile_internal(
+    src: &str,
+    funcs: Vec<Function>,
+    denied: HashMap<&'static str, String>,
+    rules: Vec<LintRule>,
+    config: VmConfig,
+    verbose: bool,
+) -> anyhow::Result<Program> {
+    let funcs = with_builtins(funcs);
+    let tokens = lexer::lex(src)?;
+    if verbose {
+        println!("lexed {} tokens: {:?}", tokens.len(), tokens);
+    }
+    let ast = parser::parse(tokens)?;
+    if verbose {
+        println!("parsed ast: {:?}", ast);
+    }
+    if !denied.is_empty() {
+        let mut calls = vec![];
+        bytecode::called_fns(&ast, &mut calls);
+        // script functions take precedence over host functions of the same name
+        let script_fns = ast
+            .iter()
+            .filter_map(|stmt| match stmt {
+                parser::Stmt::DefineFn { name, .. } => Some(name.as_str()),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        for call in calls {
+            if let Some(reason) = denied.get(call).filter(|_| !script_fns.contains(&call)) {
+                return diagnostic_builder!(format!("`{}` may not be called: {}", call, reason));
+            }
+        }
+    }
+    let lints = lint::lint(&ast, &rules);
+    let mut consts = ConstPool::default();
+    let (byte_code, script_fns, max_depth) = bytecode::translate(&ast, &funcs, &mut consts)?;
+    if verbose {
+        println!(
+            "generated byte code ({} ops): {:?}",
+            byte_code.len(),
+            byte_code
+        );
+    }
+    Ok(Program {
+        code: Arc::new(byte_code),
+        consts: Arc::new(consts),
+        funcs: Arc::new(funcs),
+        script_fns: Arc::new(script_fns),
+        max_depth,
+        config: Arc::new(config),
+        lints: Arc::new(lints),
+    })
 }
@@ -0,0 +1,488 @@
+//! Lints point out code which compiles but most likely doesn't do what its author meant.
+//! They are only warnings, scripts with lints run just like any other script.
+
+use std::fmt::{Display, Formatter};
+
+use serde::Serialize;
+
+use crate::{
+    ast::{AstNode, BinOpKind},
+    bytecode::called_fns,
+    parser::Stmt,
+};
+
+#[derive(Clone, PartialEq, Debug, Serialize)]
+pub struct Lint {
+    /// the name the lint is known by, e.g. `unused_var`
+    pub name: &'static str,
+    pub message: String,
+}
+
+impl Display for Lint {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} [{}]", self.message, self.name)
+    }
+}
+
+/// Lints hosts add about the use of their functions, which the engine only knows by name.
+/// The `hint` of a rule is appended to its message and tells script authors why the code
+/// is suspicious.
+#[derive(Clone, Debug)]
+pub enum LintRule {
+    /// scripts which call `call` have to call one of `checks` as well
+    CalledWithout {
+        name: &'static str,
+        call: &'static str,
+        checks: &'static [&'static str],
+        hint: &'static str,
+    },
+    /// the values returned by `creates` have to be passed to one of `uses`, values which
+    /// are passed to the script's functions or returned are assumed to be used there
+    NeverUsed {
+        name: &'static str,
+        creates: &'static [&'static str],
+        uses: &'static [&'static str],
+        hint: &'static str,
+    },
+    /// loops whose condition never changes have to call one of `funcs`, either directly
+    /// or through the script's functions
+    LoopWithout {
+        name: &'static str,
+        funcs: &'static [&'static str],
+        hint: &'static str,
+    },
+}
+
+/// Checks the main code and then the functions in the order they are defined.
+pub(crate) fn lint(stmts: &[Stmt], rules: &[LintRule]) -> Vec<Lint> {
+    let script_fns = stmts
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::DefineFn { name, .. } => Some(name.as_str()),
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    let mut linter = Linter {
+        unit: "the main code".to_string(),
+        lints: vec![],
+        rules,
+        script_fns: &script_fns,
+        looping: looping(stmts, rules),
+    };
+    linter.block(stmts);
+    for stmt in stmts {
+        if let Stmt::DefineFn { name, stmts, .. } = stmt {
+            linter.unit = format!("`{}`", name);
+            linter.block(stmts);
+        }
+    }
+    let mut calls = vec![];
+    called_fns(stmts, &mut calls);
+    for rule in rules {
+        if let LintRule::CalledWithout {
+            name,
+            call,
+            checks,
+            hint,
+        } = rule
+        {
+            if calls.contains(call) && !calls.iter().any(|other| checks.contains(other)) {
+                linter.push(
+                    name,
+                    format!(
+                        "the script calls `{}` but never {}, {}",
+                        call,
+                        either(checks),
+                        hint
+                    ),
+                );
+            }
+        }
+    }
+    linter.lints
+}
+
+/// for every `LoopWithout` rule of `rules`, the script functions which call one of the
+/// rule's functions, directly or through other script functions
+fn looping<'a>(stmts: &'a [Stmt], rules: &[LintRule]) -> Vec<Vec<&'a str>> {
+    let bodies = stmts
+        .iter()
+        .filter_map(|stmt| match stmt {
+            Stmt::DefineFn { name, stmts, .. } => {
+                let mut calls = vec![];
+                called_fns(stmts, &mut calls);
+                Some((name.as_str(), calls))
+            }
+            _ => None,
+        })
+        .collect::<Vec<_>>();
+    rules
+        .iter()
+        .map(|rule| {
+            let LintRule::LoopWithout { funcs, .. } = rule else {
+                return vec![];
+            };
+            let mut calling = vec![];
+            // the functions calling the functions found so far are added until no more are
+            // found
+            loop {
+                let found = bodies
+                    .iter()
+                    .filter(|(name, calls)| {
+                        !calling.contains(name)
+                            && calls
+                                .iter()
+                                .any(|call| funcs.contains(call) || calling.contains(call))
+                    })
+                    .map(|(name, _)| *name)
+                    .collect::<Vec<_>>();
+                if found.is_empty() {
+                    return calling;
+                }
+                calling.extend(found);
+            }
+        })
+        .collect()
+}
+
+struct Linter<'a> {
+    /// the function being checked, as it's referred to in messages
+    unit: String,
+    lints: Vec<Lint>,
+    rules: &'a [LintRule],
+    script_fns: &'a [&'a str],
+    /// see `looping`
+    looping: Vec<Vec<&'a str>>,
+}
+
+impl Linter<'_> {
+    fn push(&mut self, name: &'static str, message: String) {
+        self.lints.push(Lint { name, message });
+    }
+
+    fn block(&mut self, stmts: &[Stmt]) {
+        let mut returned = false;
+        for (idx, stmt) in stmts.iter().enumerate() {
+            // functions are known before the code runs, so their definitions are never
+            // unreachable
+            if let Stmt::DefineFn { .. } = stmt {
+                continue;
+            }
+            if returned {
+                self.push(
+                    "unreachable_code",
+                    format!("{} has code after a return, which never runs", self.unit),
+                );
+                returned = false;
+            }
+            if let Stmt::DefineVar {
+                name,
+                reassign: false,
+                ..
+            } = stmt
+            {
+                if !stmts[idx + 1..].iter().any(|stmt| reads(stmt, name)) {
+                    self.push(
+                        "unused_var",
+                        format!("`{}` is defined in {} but never used", name, self.unit),
+                    );
+                } else {
+                    self.never_used(stmt, &stmts[idx + 1..]);
+                }
+            }
+            self.stmt(stmt);
+            returned |= returns(stmt);
+        }
+    }
+
+    fn stmt(&mut self, stmt: &Stmt) {
+        match stmt {
+            Stmt::DefineVar { val, .. } => self.node(val),
+            Stmt::CallFunc { args, .. } => args.iter().for_each(|arg| self.node(arg)),
+            Stmt::Loop { stmts, condition } => {
+                self.loop_without(stmts, condition);
+                self.block(stmts);
+                self.node(condition);
+            }
+            Stmt::Conditional { seq, fallback } => {
+                for (condition, stmts) in seq {
+                    self.node(condition);
+                    self.block(stmts);
+                }
+                self.block(fallback);
+            }
+            Stmt::Return { val } => {
+                if let Some(val) = val {
+                    self.node(val);
+                }
+            }
+            // functions are checked on their own
+            Stmt::DefineFn { .. } => {}
+        }
+    }
+
+    fn node(&mut self, node: &AstNode) {
+        match node {
+            AstNode::CallFunc { params, .. } => params.iter().for_each(|param| self.node(param)),
+            AstNode::UnaryOp { val, .. } => self.node(val),
+            AstNode::BinOp { lhs, rhs, op } => {
+                self.comparison(lhs, rhs, *op);
+                self.node(lhs);
+                self.node(rhs);
+            }
+            AstNode::Val(_) | AstNode::Var { .. } => {}
+        }
+    }
+
+    fn comparison(&mut self, lhs: &AstNode, rhs: &AstNode, op: BinOpKind) {
+        let always = match op {
+            BinOpKind::Eq | BinOpKind::Ge | BinOpKind::Le => true,
+            BinOpKind::Ne | BinOpKind::Gt | BinOpKind::Lt => false,
+            _ => return,
+        };
+        // calls may return something else every time, e.g. when they draw a card
+        if lhs == rhs && !calls(lhs) {
+            let what = match lhs {
+                AstNode::Var { name } => format!("`{}`", name),
+                _ => "a value".to_string(),
+            };
+            self.push(
+                "suspicious_comparison",
+                format!(
+                    "{} compares {} with itself, which is always {}",
+                    self.unit, what, always
+                ),
+            );
+            return;
+        }
+        if let (AstNode::Val(lhs), AstNode::Val(rhs)) = (lhs, rhs) {
+            if lhs.ty() != rhs.ty() && matches!(op, BinOpKind::Eq | BinOpKind::Ne) {
+                self.push(
+                    "suspicious_comparison",
+                    format!(
+                        "{} compares a {} with a {}, which are never equal",
+                        self.unit,
+                        lhs.ty(),
+                        rhs.ty()
+                    ),
+                );
+            }
+        }
+    }
+
+    /// checks the `NeverUsed` rules for the variable `def` defines, `rest` is the code
+    /// which can access the variable
+    fn never_used(&mut self, def: &Stmt, rest: &[Stmt]) {
+        let Stmt::DefineVar {
+            name: var,
+            val: AstNode::CallFunc { name: func, .. },
+            ..
+        } = def
+        else {
+            return;
+        };
+        for rule in self.rules {
+            let LintRule::NeverUsed {
+                name,
+                creates,
+                uses,
+                hint,
+            } = rule
+            else {
+                continue;
+            };
+            if !creates.contains(&func.as_str()) {
+                continue;
+            }
+            let (mut used, mut escapes) = (false, false);
+            each_read(rest, var, &mut |callee| match callee {
+                Some(callee) if uses.contains(&callee) => used = true,
+                Some(callee) if !self.script_fns.contains(&callee) => {}
+                _ => escapes = true,
+            });
+            if !used && !escapes {
+                self.push(
+                    name,
+                    format!(
+                        "`{}` is created in {} by `{}`, but {}",
+                        var, self.unit, func, hint
+                    ),
+                );
+            }
+        }
+    }
+
+    fn loop_without(&mut self, stmts: &[Stmt], condition: &AstNode) {
+        // loops which may change their condition or return are expected to end
+        let mut vars = vec![];
+        each_var(condition, &mut vars);
+        if calls(condition)
+            || stmts
+                .iter()
+                .any(|stmt| vars.iter().any(|var| assigns(stmt, var)) || has_return(stmt))
+        {
+            return;
+        }
+        let mut calls = vec![];
+        called_fns(stmts, &mut calls);
+        for (rule, looping) in self.rules.iter().zip(&self.looping) {
+            let LintRule::LoopWithout { name, funcs, hint } = rule else {
+                continue;
+            };
+            if !calls
+                .iter()
+                .any(|call| funcs.contains(call) || looping.contains(call))
+            {
+                self.lints.push(Lint {
+                    name,
+                    message: format!(
+                        "{} has a loop which neither changes its condition nor calls {}, {}",
+                        self.unit,
+                        either(funcs),
+                        hint
+                    ),
+                });
+            }
+        }
+    }
+}
+
+/// e.g. `` `a`, `b` or `c` ``
+fn either(funcs: &[&str]) -> String {
+    let funcs = funcs
+        .iter()
+        .map(|func| format!("`{}`", func))
+        .collect::<Vec<_>>();
+    match funcs.split_last() {
+        Some((last, others)) if !others.is_empty() => {
+            format!("{} or {}", others.join(", "), last)
+        }
+        _ => funcs.concat(),
+    }
+}
+
+/// whether the code after `stmt` can't be reached as `stmt` returns in every case
+fn returns(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Return { .. } => true,
+        Stmt::Conditional { seq, fallback } => {
+            !fallback.is_empty()
+                && seq
+                    .iter()
+                    .map(|(_, stmts)| stmts)
+                    .chain([fallback])
+                    .all(|stmts| stmts.iter().any(returns))
+        }
+        _ => false,
+    }
+}
+
+/// whether `stmt` reads the variable `name`
+fn reads(stmt: &Stmt, name: &str) -> bool {
+    let mut found = false;
+    each_read(std::slice::from_ref(stmt), name, &mut |_| found = true);
+    found
+}
+
+/// Calls `read` for every place `stmts` read the variable `name` at, with the function the
+/// variable is passed to there, if it's passed to one directly.
+fn each_read<'a>(stmts: &'a [Stmt], name: &str, read: &mut impl FnMut(Option<&'a str>)) {
+    fn visit<'a>(
+        node: &'a AstNode,
+        name: &str,
+        callee: Option<&'a str>,
+        read: &mut impl FnMut(Option<&'a str>),
+    ) {
+        match node {
+            AstNode::CallFunc { name: func, params } => params
+                .iter()
+                .for_each(|param| visit(param, name, Some(func), read)),
+            AstNode::UnaryOp { val, .. } => visit(val, name, None, read),
+            AstNode::BinOp { lhs, rhs, .. } => {
+                visit(lhs, name, None, read);
+                visit(rhs, name, None, read);
+            }
+            AstNode::Var { name: var } if var == name => read(callee),
+            AstNode::Val(_) | AstNode::Var { .. } => {}
+        }
+    }
+    for stmt in stmts {
+        match stmt {
+            Stmt::DefineVar { val, .. } => visit(val, name, None, read),
+            Stmt::CallFunc { name: func, args } => args
+                .iter()
+                .for_each(|arg| visit(arg, name, Some(func), read)),
+            Stmt::Loop { stmts, condition } => {
+                each_read(stmts, name, read);
+                visit(condition, name, None, read);
+            }
+            Stmt::Conditional { seq, fallback } => {
+                for (condition, stmts) in seq {
+                    visit(condition, name, None, read);
+                    each_read(stmts, name, read);
+                }
+                each_read(fallback, name, read);
+            }
+            Stmt::Return { val } => {
+                if let Some(val) = val {
+                    visit(val, name, None, read);
+                }
+            }
+            // functions can't see the variables of the code around them
+            Stmt::DefineFn { .. } => {}
+        }
+    }
+}
+
+/// whether `stmt` assigns a new value to the variable `name`
+fn assigns(stmt: &Stmt, name: &str) -> bool {
+    match stmt {
+        Stmt::DefineVar {
+            name: var,
+            reassign,
+            ..
+        } => *reassign && var == name,
+        Stmt::Loop { stmts, .. } => stmts.iter().any(|stmt| assigns(stmt, name)),
+        Stmt::Conditional { seq, fallback } => seq
+            .iter()
+            .flat_map(|(_, stmts)| stmts)
+            .chain(fallback)
+            .any(|stmt| assigns(stmt, name)),
+        _ => false,
+    }
+}
+
+fn has_return(stmt: &Stmt) -> bool {
+    match stmt {
+        Stmt::Return { .. } => true,
+        Stmt::Loop { stmts, .. } => stmts.iter().any(has_return),
+        Stmt::Conditional { seq, fallback } => seq
+            .iter()
+            .flat_map(|(_, stmts)| stmts)
+            .chain(fallback)
+            .any(has_return),
+        _ => false,
+    }
+}
+
+fn each_var<'a>(node: &'a AstNode, vars: &mut Vec<&'a str>) {
+    match node {
+        AstNode::CallFunc { params, .. } => params.iter().for_each(|param| each_var(param, vars)),
+        AstNode::UnaryOp { val, .. } => each_var(val, vars),
+        AstNode::BinOp { lhs, rhs, .. } => {
+            each_var(lhs, vars);
+            each_var(rhs, vars);
+        }
+        AstNode::Var { name } => vars.push(name),
+        AstNode::Val(_) => {}
+    }
+}
+
+fn calls(node: &AstNode) -> bool {
+    match node {
+        AstNode::CallFunc { .. } => true,
+        AstNode::UnaryOp { val, .. } => calls(val),
+        AstNode::BinOp { lhs, rhs, .. } => calls(lhs) || calls(rhs),
+        AstNode::Val(_) | AstNode::Var { .. } => false,
+    }
+}
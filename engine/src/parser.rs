@@ -163,7 +163,15 @@ impl Parser {
         }
         let mut args = vec![];
         while !self.try_eat(TokenKind::CloseBrace) {
-            args.push(self.parse_lit().unwrap());
+            match self.parse_lit() {
+                Some(arg) => args.push(arg),
+                None => {
+                    return diagnostic_builder!("Expected parameter name in function definition")
+                }
+            }
+            if !self.try_eat(TokenKind::Comma) && self.look_ahead() != Some(Token::CloseBrace) {
+                return diagnostic_builder!("Missing `,` between function parameters");
+            }
         }
         if !self.try_eat(TokenKind::OpenCurly) {
             return diagnostic_builder!("Can't find `{` in function definition");
@@ -0,0 +1,116 @@
+use std::{any::Any, fmt::Debug, sync::Arc};
+
+use crate::{
+    builder::VmConfig,
+    bytecode::{ByteCode, ConstPool, Function, ScriptFn},
+    diagnostic_builder,
+    host::FunctionInfo,
+    lint::Lint,
+    rng::Rng,
+    rt::RtRef,
+    vm::{Status, Vm},
+};
+
+/// A compiled script together with the functions it was compiled against.
+/// Programs are cheap to clone and can be executed any number of times.
+#[derive(Clone)]
+pub struct Program {
+    pub(crate) code: Arc<Vec<ByteCode>>,
+    pub(crate) consts: Arc<ConstPool>,
+    pub(crate) funcs: Arc<Vec<Function>>,
+    pub(crate) script_fns: Arc<Vec<ScriptFn>>,
+    /// the maximum number of values the main code keeps on the stack
+    pub(crate) max_depth: usize,
+    pub(crate) config: Arc<VmConfig>,
+    pub(crate) lints: Arc<Vec<Lint>>,
+}
+
+impl Program {
+    /// Executes the program with the seed it was configured with or a random one if
+    /// it wasn't configured with any.
+    pub fn execute(&self) -> anyhow::Result<()> {
+        finish(self.instantiate().run()?)
+    }
+
+    /// Executes the program with `data` attached for host functions to access via their `HostCtx`.
+    pub fn execute_with<T: Any + Send>(&self, data: T) -> anyhow::Result<()> {
+        let mut vm = self.instantiate();
+        vm.host_ctx().set_data(data);
+        finish(vm.run()?)
+    }
+
+    /// Executes the program, awaiting async host functions instead of blocking on them.
+    pub async fn execute_async(&self) -> anyhow::Result<()> {
+        finish(self.instantiate().run_async().await?)
+    }
+
+    pub async fn execute_with_async<T: Any + Send>(&self, data: T) -> anyhow::Result<()> {
+        let mut vm = self.instantiate();
+        vm.host_ctx().set_data(data);
+        finish(vm.run_async().await?)
+    }
+
+    /// Executes the program with its rng seeded by `seed`, so as long as all
+    /// inputs are the same, the execution can be reproduced exactly.
+    pub fn execute_seeded(&self, seed: u64) -> anyhow::Result<()> {
+        finish(self.instantiate_seeded(seed).run()?)
+    }
+
+    /// Creates a vm which is ready to execute the program, this allows the host to
+    /// drive the execution itself (e.g. to take snapshots in between steps).
+    pub fn instantiate(&self) -> Vm {
+        self.instantiate_seeded(self.config.seed.unwrap_or_else(Rng::entropy_seed))
+    }
+
+    pub fn instantiate_seeded(&self, seed: u64) -> Vm {
+        Vm::new(self, Rng::new(seed))
+    }
+
+    /// Calls the script function `name` with `args` on a fresh vm without running the main code,
+    /// see `Vm::call` for calling functions on a vm that's already running.
+    pub fn call(&self, name: &str, args: &[RtRef]) -> anyhow::Result<RtRef> {
+        self.instantiate().call(name, args)
+    }
+
+    /// whether the script defines a function named `name`, this allows hosts to only
+    /// trigger events scripts are interested in
+    pub fn has_fn(&self, name: &str) -> bool {
+        self.script_fns.iter().any(|func| func.name == name)
+    }
+
+    /// The signatures of the functions the program was compiled against, builtins included.
+    pub fn registry(&self) -> Vec<FunctionInfo> {
+        self.funcs.iter().map(Function::info).collect()
+    }
+
+    /// The warnings about the script, they don't keep it from running.
+    pub fn lints(&self) -> &[Lint] {
+        &self.lints
+    }
+
+    /// the number of instructions the program consists of
+    pub fn len(&self) -> usize {
+        self.code.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.code.is_empty()
+    }
+}
+
+/// executions started through the `execute` family of methods have no one to answer requests
+fn finish(status: Status) -> anyhow::Result<()> {
+    match status {
+        Status::Finished => Ok(()),
+        Status::Suspended(_) => diagnostic_builder!(
+            "The execution was suspended, suspending host functions require the vm to be driven \
+             by the host"
+        ),
+    }
+}
+
+impl Debug for Program {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_list().entries(self.code.iter()).finish()
+    }
+}
@@ -0,0 +1,76 @@
+#[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use serde::{Deserialize, Serialize};
+
+/// A small, seedable pseudo random number generator (xorshift64*).
+///
+/// It's not suitable for anything security related, but it's fast
+/// and reproducible which is all game logic needs.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Self {
+        // xorshift can never leave the all-zero state, so scramble the seed first
+        let state = splitmix64(seed);
+        Self {
+            state: if state == 0 { 1 } else { state },
+        }
+    }
+
+    pub fn from_entropy() -> Self {
+        Self::new(Self::entropy_seed())
+    }
+
+    /// generates a seed that differs between runs
+    #[cfg(not(all(target_arch = "wasm32", feature = "wasm")))]
+    pub fn entropy_seed() -> u64 {
+        SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|dur| dur.as_nanos() as u64)
+            .unwrap_or(0)
+    }
+
+    /// the system time isn't available in browsers, so javascript's rng is used instead
+    #[cfg(all(target_arch = "wasm32", feature = "wasm"))]
+    pub fn entropy_seed() -> u64 {
+        (js_sys::Math::random() * u64::MAX as f64) as u64
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        let mut x = self.state;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.state = x;
+        x.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    /// returns a value in `0..upper`, `upper` has to be non-zero
+    pub fn gen_range(&mut self, upper: usize) -> usize {
+        assert!(upper != 0, "can't generate a value in an empty range");
+        (self.next_u64() % upper as u64) as usize
+    }
+
+    pub fn gen_bool(&mut self) -> bool {
+        self.next_u64() & 1 == 1
+    }
+
+    /// shuffles the passed slice in place (Fisher-Yates)
+    pub fn shuffle<T>(&mut self, slice: &mut [T]) {
+        for i in (1..slice.len()).rev() {
+            let j = self.gen_range(i + 1);
+            slice.swap(i, j);
+        }
+    }
+}
+
+const fn splitmix64(seed: u64) -> u64 {
+    let mut z = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    z ^ (z >> 31)
+}
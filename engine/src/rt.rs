@@ -1,4 +1,6 @@
-use std::{fmt::Debug, mem::transmute};
+use std::{any::Any, fmt::Debug, mem::transmute};
+
+use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Copy, PartialEq)]
 pub struct RtRef {
@@ -71,6 +73,13 @@ impl RtRef {
         }
     }
 
+    pub fn card(val: CardVal) -> Self {
+        Self {
+            ty: RtType::Card,
+            val: val.0 as usize,
+        }
+    }
+
     pub fn list(val: Box<Vec<RtRef>>) -> Self {
         Self {
             ty: RtType::List,
@@ -78,6 +87,19 @@ impl RtRef {
         }
     }
 
+    /// Wraps a host value, `tag` has to be registered as an opaque type
+    /// for the vm to be able to clone and clean up the value.
+    pub fn opaque<T: Any + Send>(tag: u32, val: T) -> Self {
+        Self::opaque_boxed(tag, Box::new(val))
+    }
+
+    pub(crate) fn opaque_boxed(tag: u32, val: Box<dyn Any + Send>) -> Self {
+        Self {
+            ty: RtType::Opaque(tag),
+            val: Box::into_raw(Box::new(val)) as usize,
+        }
+    }
+
     pub fn get_player(self) -> Option<Player> {
         match self.ty() {
             RtType::Player => Some(Player(self.dst() as usize as u64)),
@@ -102,6 +124,18 @@ impl RtRef {
         }
     }
 
+    /// Returns the wrapped host value if this is an opaque value holding a `T`.
+    pub fn get_opaque<T: Any>(&self) -> Option<&T> {
+        match self.ty() {
+            RtType::Opaque(_) => unsafe { self.get_opaque_directly() }.downcast_ref::<T>(),
+            _ => None,
+        }
+    }
+
+    pub(crate) unsafe fn get_opaque_directly(&self) -> &(dyn Any + Send) {
+        unsafe { &**self.dst().cast::<Box<dyn Any + Send>>() }
+    }
+
     pub fn get_list(&self) -> Option<&Vec<RtRef>> {
         match self.ty() {
             RtType::List => Some(unsafe { &*self.dst().cast::<Vec<RtRef>>() }),
@@ -178,11 +212,124 @@ impl RtRef {
             RtType::None => "Null".to_string(),
             RtType::Bool => unsafe { transmute::<_, bool>(self.val as u8) }.to_string(),
             RtType::String => unsafe { (self.val as *const String).as_ref().unwrap() }.clone(),
-            RtType::Function => todo!(),
-            RtType::List => todo!(),
-            RtType::Player => todo!(),
-            RtType::Inventory => todo!(),
-            RtType::Card => todo!(),
+            RtType::Function => format!("Function({})", self.val),
+            RtType::List => format!(
+                "[{}]",
+                self.get_list()
+                    .unwrap()
+                    .iter()
+                    .map(|val| val.to_string())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ),
+            RtType::Player => format!("Player({})", self.get_player().unwrap().idx()),
+            RtType::Inventory => format!("Inventory({})", self.get_inventory().unwrap().0),
+            RtType::Card => format!("Card({})", self.get_card().unwrap().idx()),
+            RtType::Opaque(tag) => format!("Opaque({})", tag),
+        }
+    }
+
+    /// Compares two values, values of different types are never equal. Only numbers and
+    /// strings are ordered, lists are equal if all their elements are.
+    pub(crate) fn compare(self, other: RtRef) -> Ordering {
+        if self.ty() != other.ty() {
+            return Ordering::NotEqual;
+        }
+        match self.ty() {
+            RtType::Decimal => Ordering::from_std(unsafe {
+                self.get_decimal_directly()
+                    .total_cmp(&other.get_decimal_directly())
+            }),
+            RtType::None => Ordering::Equal,
+            RtType::String => Ordering::from_std(unsafe {
+                self.get_string_directly().cmp(other.get_string_directly())
+            }),
+            RtType::List => {
+                let (left, right) = (self.get_list().unwrap(), other.get_list().unwrap());
+                let equal = left.len() == right.len()
+                    && left
+                        .iter()
+                        .zip(right)
+                        .all(|(left, right)| left.compare(*right) == Ordering::Equal);
+                if equal {
+                    Ordering::Equal
+                } else {
+                    Ordering::NotEqual
+                }
+            }
+            // the remaining values are equal if they refer to the same thing,
+            // opaque values are only equal to themselves
+            RtType::Bool
+            | RtType::Function
+            | RtType::Player
+            | RtType::Inventory
+            | RtType::Card
+            | RtType::Opaque(_) => {
+                if self == other {
+                    Ordering::Equal
+                } else {
+                    Ordering::NotEqual
+                }
+            }
+        }
+    }
+}
+
+/// An owned representation of a runtime value which doesn't share any heap data
+/// and thus can be stored and serialized safely.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub enum RtValue {
+    None,
+    Decimal(f64),
+    Bool(bool),
+    String(String),
+    Function(usize),
+    List(Vec<RtValue>),
+    Player(Player),
+    Inventory(u64),
+    Card(CardVal),
+}
+
+impl RtValue {
+    /// deep copies the passed runtime value
+    pub fn from_ref(val: RtRef) -> Self {
+        match val.ty() {
+            RtType::Decimal => Self::Decimal(unsafe { val.get_decimal_directly() }),
+            RtType::None => Self::None,
+            RtType::Bool => Self::Bool(unsafe { val.get_bool_directly() }),
+            RtType::String => Self::String(unsafe { val.get_string_directly() }.clone()),
+            RtType::Function => Self::Function(val.get_func_idx().unwrap()),
+            RtType::List => Self::List(
+                val.get_list()
+                    .unwrap()
+                    .iter()
+                    .map(|val| Self::from_ref(*val))
+                    .collect::<Vec<_>>(),
+            ),
+            RtType::Player => Self::Player(val.get_player().unwrap()),
+            RtType::Inventory => Self::Inventory(val.get_inventory().unwrap().0),
+            RtType::Card => Self::Card(val.get_card().unwrap()),
+            // FIXME: opaque values are owned by the host and thus can't be persisted
+            RtType::Opaque(_) => Self::None,
+        }
+    }
+
+    /// converts the value into a runtime value, allocating its heap data (if any)
+    pub fn into_ref(self) -> RtRef {
+        match self {
+            RtValue::None => RtRef::NULL,
+            RtValue::Decimal(val) => RtRef::decimal(val),
+            RtValue::Bool(val) => RtRef::bool(val),
+            RtValue::String(val) => RtRef::string(Box::new(val)),
+            RtValue::Function(idx) => RtRef::function(idx),
+            RtValue::List(vals) => RtRef::list(Box::new(
+                vals.into_iter()
+                    .map(|val| val.into_ref())
+                    .collect::<Vec<_>>(),
+            )),
+            RtValue::Player(player) => RtRef::player(player),
+            RtValue::Inventory(idx) => RtRef::inventory(CardInventoryRef(idx)),
+            RtValue::Card(card) => RtRef::card(card),
         }
     }
 }
@@ -219,13 +366,43 @@ pub enum RtType {
     Player = 6,
     Inventory = 7,
     Card = 8,
+    /// a value owned by the host, the tag identifies the opaque type it belongs to
+    Opaque(u32) = 9,
 }
 
-#[derive(Clone, Copy, PartialEq)]
+impl std::fmt::Display for RtType {
+    /// the name scripts know the type by
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            RtType::Decimal => f.write_str("number"),
+            RtType::None => f.write_str("none"),
+            RtType::Bool => f.write_str("bool"),
+            RtType::String => f.write_str("string"),
+            RtType::Function => f.write_str("function"),
+            RtType::List => f.write_str("list"),
+            RtType::Player => f.write_str("player"),
+            RtType::Inventory => f.write_str("inventory"),
+            RtType::Card => f.write_str("card"),
+            RtType::Opaque(tag) => write!(f, "opaque {}", tag),
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 #[repr(transparent)]
 pub struct CardVal(u64);
 
-#[derive(Clone, Copy, PartialEq)]
+impl CardVal {
+    pub const fn new(idx: u64) -> Self {
+        Self(idx)
+    }
+
+    pub const fn idx(self) -> u64 {
+        self.0
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Debug, Serialize, Deserialize)]
 #[repr(transparent)]
 pub struct Player(u64);
 
@@ -239,10 +416,24 @@ impl Player {
     }
 }
 
+#[derive(Clone, Copy, PartialEq, Debug)]
 pub struct CardInventoryRef(pub u64);
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct CardInventory {
     pub slots: u64,
     pub vis: Option<Vec<Player>>,
     pub cards: Vec<CardVal>,
 }
+
+impl CardInventory {
+    /// whether all slots of the inventory are taken
+    pub fn is_full(&self) -> bool {
+        self.cards.len() as u64 >= self.slots
+    }
+
+    /// whether `player` is allowed to see the cards in the inventory
+    pub fn visible_to(&self, player: Player) -> bool {
+        self.vis.as_ref().map_or(true, |vis| vis.contains(&player))
+    }
+}
@@ -1,154 +1,368 @@
+use std::{
+    any::Any,
+    mem::{self, size_of},
+    sync::Arc,
+};
+
+use serde::{Deserialize, Serialize};
+
 use crate::{
-    bytecode::{ByteCode, Function},
-    rt::{Ordering, RtRef, RtType},
+    builder::{TraceEvent, VmConfig},
+    bytecode::{ByteCode, ConstPool, Function, ScriptFn},
+    diagnostic_builder,
+    host::{HostCtx, PendingCall},
+    program::Program,
+    rng::Rng,
+    rt::{Ordering, RtRef, RtType, RtValue},
 };
 
 pub struct Vm {
-    code: Vec<ByteCode>,
+    code: Arc<Vec<ByteCode>>,
+    consts: Arc<ConstPool>,
     ip: usize,
     stack: Vec<RtRef>,
-    funcs: Vec<Function>,
+    funcs: Arc<Vec<Function>>,
+    script_fns: Arc<Vec<ScriptFn>>,
+    /// the index of the current frame's first value on the stack,
+    /// all stack indices in the bytecode are relative to it
+    base: usize,
+    frames: Vec<Frame>,
+    /// set while a host function waits for the host to answer its request,
+    /// it holds whether the answer has to be pushed onto the stack
+    suspended: Option<bool>,
+    request: Option<Box<dyn Any + Send>>,
+    host: HostCtx,
+    config: Arc<VmConfig>,
+    /// the number of instructions executed so far
+    executed: u64,
+    /// reused for passing arguments, so calls don't have to allocate
+    scratch: Vec<RtRef>,
+    /// whether host calls are recorded, see `EngineBuilder::audit`
+    audit: bool,
+    /// the host calls which weren't taken yet, only recorded if auditing is enabled
+    audit_log: Vec<HostCall>,
+    /// the number of bytes the values on the stack own on the heap, only counted if there's
+    /// a memory limit
+    heap_bytes: usize,
 }
 
 impl Vm {
-    pub fn new(code: Vec<ByteCode>, funcs: Vec<Function>) -> Self {
+    pub(crate) fn new(program: &Program, rng: Rng) -> Self {
         Self {
-            code,
+            code: program.code.clone(),
+            consts: program.consts.clone(),
             ip: 0,
-            stack: vec![],
-            funcs,
+            // script functions reserve the space for their frames once they are called
+            stack: Vec::with_capacity(program.max_depth),
+            funcs: program.funcs.clone(),
+            script_fns: program.script_fns.clone(),
+            base: 0,
+            frames: vec![],
+            suspended: None,
+            request: None,
+            host: HostCtx::new(rng),
+            audit: program.config.audit,
+            config: program.config.clone(),
+            executed: 0,
+            scratch: vec![],
+            audit_log: vec![],
+            heap_bytes: 0,
         }
     }
 
-    fn cleanup(val: RtRef) {
+    pub(crate) fn cleanup(config: &VmConfig, val: RtRef) {
         // free up unused memory
         match val.ty() {
             RtType::String => {
                 let _ = unsafe { Box::from_raw(val.dst()) };
             }
-            RtType::List => todo!(),
+            RtType::List => {
+                let list = unsafe { Box::from_raw(val.dst().cast::<Vec<RtRef>>()) };
+                for val in list.into_iter() {
+                    Self::cleanup(config, val);
+                }
+            }
+            RtType::Opaque(tag) => {
+                let val = unsafe { Box::from_raw(val.dst().cast::<Box<dyn Any + Send>>()) };
+                // values of unregistered types never make it onto the stack, but a host
+                // function may still return one which has to be dropped
+                match config.opaque_types.get(&tag) {
+                    Some(ty) => (ty.cleanup)(*val),
+                    None => drop(val),
+                }
+            }
             _ => {}
         }
     }
 
-    fn clone_ref(val: RtRef) -> RtRef {
+    fn clone_ref(config: &VmConfig, val: RtRef) -> RtRef {
         match val.ty() {
             RtType::String => {
                 let cloned = unsafe { val.get_string_directly() }.clone();
                 RtRef::string(Box::new(cloned))
             }
             RtType::List => {
-                let cloned = val.get_list().unwrap().clone();
+                // the list owns its elements, so they have to be cloned as well
+                let cloned = val
+                    .get_list()
+                    .unwrap()
+                    .iter()
+                    .map(|val| Self::clone_ref(config, *val))
+                    .collect::<Vec<_>>();
                 RtRef::list(Box::new(cloned))
             },
+            RtType::Opaque(tag) => {
+                let val = unsafe { val.get_opaque_directly() };
+                let cloned = (config.opaque_types[&tag].clone)(val);
+                RtRef::opaque_boxed(tag, cloned)
+            }
             _ => val,
         }
     }
 
-    pub fn run(&mut self) {
+    /// checks that all opaque values within `val` belong to registered opaque types
+    fn check_opaque(config: &VmConfig, val: RtRef) -> bool {
+        match val.ty() {
+            RtType::Opaque(tag) => config.opaque_types.contains_key(&tag),
+            RtType::List => val
+                .get_list()
+                .unwrap()
+                .iter()
+                .all(|val| Self::check_opaque(config, *val)),
+            _ => true,
+        }
+    }
+
+    /// Executes instructions until the execution either finishes or a host function suspends it.
+    pub fn run(&mut self) -> anyhow::Result<Status> {
         // FIXME: run an optimizer on the bytecode beforehand, eliminating push/pop sequences
-        while let Some(curr) = self.code.get(self.ip) {
+        while self.step()? {}
+        Ok(match self.request.take() {
+            Some(request) => Status::Suspended(request),
+            None => Status::Finished,
+        })
+    }
+
+    /// Like `run`, but awaits the futures of async host functions instead of suspending.
+    /// Requests of host functions which suspend the execution themselves are returned as usual.
+    pub async fn run_async(&mut self) -> anyhow::Result<Status> {
+        loop {
+            let request = match self.run()? {
+                Status::Finished => return Ok(Status::Finished),
+                Status::Suspended(request) => request,
+            };
+            let pending = match request.downcast::<PendingCall>() {
+                Ok(pending) => pending,
+                Err(request) => return Ok(Status::Suspended(request)),
+            };
+            let val = match pending.future.await {
+                Ok(val) => val.unwrap_or(RtRef::NULL),
+                Err(err) => {
+                    return diagnostic_builder!(format!(
+                        "Host function `{}` failed: {}",
+                        pending.name, err
+                    ));
+                }
+            };
+            if !Self::check_opaque(&self.config, val) {
+                return diagnostic_builder!(format!(
+                    "Host function `{}` returned a value of an unregistered opaque type",
+                    pending.name
+                ));
+            }
+            self.resume(val)?;
+        }
+    }
+
+    /// Executes a single instruction and returns whether there are instructions left to execute,
+    /// which isn't the case while the vm is suspended.
+    pub fn step(&mut self) -> anyhow::Result<bool> {
+        if self.suspended.is_some() {
+            return diagnostic_builder!(
+                "The execution is suspended, it has to be resumed before it can continue"
+            );
+        }
+        if let Some(curr) = self.code.get(self.ip) {
+            self.executed += 1;
+            if let Some(limit) = self.config.instruction_limit {
+                if self.executed > limit {
+                    return diagnostic_builder!(format!(
+                        "Exceeded the instruction limit of {}",
+                        limit
+                    ));
+                }
+            }
+            if let Some(limit) = self.config.memory_limit {
+                let used = self.heap_bytes + self.stack.len() * size_of::<RtRef>();
+                if used > limit {
+                    return diagnostic_builder!(format!(
+                        "Exceeded the memory limit of {} bytes",
+                        limit
+                    ));
+                }
+            }
+            if let Some(trace) = &self.config.trace {
+                trace.trace(TraceEvent::Instruction {
+                    ip: self.ip,
+                    op: curr,
+                });
+            }
             match curr {
-                ByteCode::Push { val } => {
-                    self.stack.push(Self::clone_ref(*val));
+                ByteCode::Push { const_idx } => {
+                    let val = Self::clone_ref(&self.config, self.consts.get(*const_idx));
+                    self.push(val);
                 }
                 ByteCode::Pop { offset } => {
-                    let val = self.stack.remove(self.stack.len() - 1 - *offset as usize);
-                    Self::cleanup(val);
+                    // as offsets are at most 1, swapping the top value into the popped slot keeps
+                    // the order of the remaining values without shifting the stack
+                    let val = self.stack.swap_remove(self.stack.len() - 1 - *offset as usize);
+                    self.free(val);
                 }
                 ByteCode::Mov { src_idx, dst_idx } => {
-                    let prev = self.stack[*dst_idx as usize];
-
-                    self.stack[*dst_idx as usize] = Self::clone_ref(self.stack[*src_idx as usize]);
-                    Self::cleanup(prev);
+                    let dst_idx = self.base + *dst_idx as usize;
+                    let prev = self.stack[dst_idx];
+                    let val =
+                        Self::clone_ref(&self.config, self.stack[self.base + *src_idx as usize]);
+                    self.track(val);
+                    self.stack[dst_idx] = val;
+                    self.free(prev);
                 }
                 ByteCode::Call {
                     fn_idx,
                     push_val,
                     arg_indices,
                 } => {
-                    let func = &mut self.funcs[*fn_idx as usize];
-                    let args = {
-                        let mut args = vec![];
-                        // FIXME: perform type checking!
-                        for (i, idx) in arg_indices.iter().enumerate() {
-                            let val = self.stack.get(*idx as usize).unwrap();
-                            args.push(*val);
+                    let func = &self.funcs[*fn_idx as usize];
+                    let mut args = mem::take(&mut self.scratch);
+                    args.clear();
+                    // FIXME: perform type checking!
+                    args.extend(
+                        arg_indices
+                            .iter()
+                            .map(|idx| self.stack[self.base + *idx as usize]),
+                    );
+                    let res = (func.call)(&mut self.host, &args);
+                    if self.audit {
+                        self.audit_log.push(HostCall {
+                            executed: self.executed,
+                            name: func.name.to_string(),
+                            args: args.iter().map(|arg| RtValue::from_ref(*arg)).collect(),
+                            result: match &res {
+                                Ok(val) => Ok(val.map_or(RtValue::None, RtValue::from_ref)),
+                                Err(err) => Err(err.to_string()),
+                            },
+                        });
+                    }
+                    let val = match res {
+                        Ok(val) => val,
+                        Err(err) => {
+                            self.scratch = args;
+                            self.host.suspension = None;
+                            return diagnostic_builder!(format!(
+                                "Host function `{}` failed: {}",
+                                func.name, err
+                            ));
                         }
-                        args
                     };
-                    let fun = func.call;
-                    let val = fun(args);
+                    if let Some(val) = val {
+                        if !Self::check_opaque(&self.config, val) {
+                            self.scratch = args;
+                            self.host.suspension = None;
+                            Self::cleanup(&self.config, val);
+                            return diagnostic_builder!(format!(
+                                "Host function `{}` returned a value of an unregistered opaque \
+                                 type",
+                                func.name
+                            ));
+                        }
+                    }
+                    if let Some(trace) = &self.config.trace {
+                        trace.trace(TraceEvent::HostCall {
+                            name: func.name,
+                            args: &args,
+                            ret: val,
+                        });
+                    }
+                    self.scratch = args;
+                    if let Some(request) = self.host.suspension.take() {
+                        // the answer passed to `resume` takes the place of the return value
+                        if let Some(val) = val {
+                            Self::cleanup(&self.config, val);
+                        }
+                        self.suspended = Some(*push_val);
+                        self.request = Some(request);
+                        return Ok(false);
+                    }
                     if *push_val {
                         // FIXME: should we even push if the value is None?
-                        self.stack.push(val.unwrap_or(RtRef::NULL));
+                        self.push(val.unwrap_or(RtRef::NULL));
                     }
                 }
                 ByteCode::Add { arg1_idx, arg2_idx } => {
-                    let left = *self.stack.get(*arg1_idx as usize).unwrap();
-                    let right = *self.stack.get(*arg2_idx as usize).unwrap();
+                    let left = *self.stack.get(self.base + *arg1_idx as usize).unwrap();
+                    let right = *self.stack.get(self.base + *arg2_idx as usize).unwrap();
                     self.stack.push(RtRef::decimal(
                         left.get_decimal().unwrap() + right.get_decimal().unwrap(),
                     ));
                 }
                 ByteCode::Sub { arg1_idx, arg2_idx } => {
-                    let left = *self.stack.get(*arg1_idx as usize).unwrap();
-                    let right = *self.stack.get(*arg2_idx as usize).unwrap();
+                    let left = *self.stack.get(self.base + *arg1_idx as usize).unwrap();
+                    let right = *self.stack.get(self.base + *arg2_idx as usize).unwrap();
                     self.stack.push(RtRef::decimal(
                         left.get_decimal().unwrap() - right.get_decimal().unwrap(),
                     ));
                 }
                 ByteCode::Mul { arg1_idx, arg2_idx } => {
-                    let left = *self.stack.get(*arg1_idx as usize).unwrap();
-                    let right = *self.stack.get(*arg2_idx as usize).unwrap();
+                    let left = *self.stack.get(self.base + *arg1_idx as usize).unwrap();
+                    let right = *self.stack.get(self.base + *arg2_idx as usize).unwrap();
                     self.stack.push(RtRef::decimal(
                         left.get_decimal().unwrap() * right.get_decimal().unwrap(),
                     ));
                 }
                 ByteCode::Div { arg1_idx, arg2_idx } => {
-                    let left = *self.stack.get(*arg1_idx as usize).unwrap();
-                    let right = *self.stack.get(*arg2_idx as usize).unwrap();
+                    let left = *self.stack.get(self.base + *arg1_idx as usize).unwrap();
+                    let right = *self.stack.get(self.base + *arg2_idx as usize).unwrap();
                     self.stack.push(RtRef::decimal(
                         left.get_decimal().unwrap() / right.get_decimal().unwrap(),
                     ));
                 }
                 ByteCode::Mod { arg1_idx, arg2_idx } => {
-                    let left = *self.stack.get(*arg1_idx as usize).unwrap();
-                    let right = *self.stack.get(*arg2_idx as usize).unwrap();
+                    let left = *self.stack.get(self.base + *arg1_idx as usize).unwrap();
+                    let right = *self.stack.get(self.base + *arg2_idx as usize).unwrap();
                     self.stack.push(RtRef::decimal(
                         left.get_decimal().unwrap() % right.get_decimal().unwrap(),
                     ));
                 }
                 ByteCode::And { arg1_idx, arg2_idx } => {
-                    let left = *self.stack.get(*arg1_idx as usize).unwrap();
-                    let right = *self.stack.get(*arg2_idx as usize).unwrap();
+                    let left = *self.stack.get(self.base + *arg1_idx as usize).unwrap();
+                    let right = *self.stack.get(self.base + *arg2_idx as usize).unwrap();
                     self.stack.push(RtRef::bool(
                         left.get_bool().unwrap() && right.get_bool().unwrap(),
                     ));
                 }
                 ByteCode::Or { arg1_idx, arg2_idx } => {
-                    let left = *self.stack.get(*arg1_idx as usize).unwrap();
-                    let right = *self.stack.get(*arg2_idx as usize).unwrap();
+                    let left = *self.stack.get(self.base + *arg1_idx as usize).unwrap();
+                    let right = *self.stack.get(self.base + *arg2_idx as usize).unwrap();
                     self.stack.push(RtRef::bool(
                         left.get_bool().unwrap() || right.get_bool().unwrap(),
                     ));
                 }
                 ByteCode::Jump { relative_off } => {
                     self.ip = ((self.ip as isize) + *relative_off) as usize; // FIXME: guard against overflow!
-                    continue;
+                    return Ok(true);
                 }
                 ByteCode::JumpCond {
                     relative_off,
                     arg_idx,
                 } => {
-                    let val = *self.stack.get(*arg_idx as usize).unwrap(); // FIXME: guard against inval param
+                    let val = *self.stack.get(self.base + *arg_idx as usize).unwrap(); // FIXME: guard against inval param
                     if val.ty() != RtType::Bool {
                         panic!("invalid type {:?} {:?}", val.ty(), val.get_decimal());
                         // FIXME: auto convert to bool if possible
                     }
                     if val == RtRef::bool(true) {
                         self.ip = ((self.ip as isize) + *relative_off) as usize; // FIXME: guard against overflow!
-                        continue;
+                        return Ok(true);
                     }
                 }
                 ByteCode::Compare {
@@ -156,50 +370,411 @@ impl Vm {
                     arg2_idx,
                     expected,
                 } => {
-                    let left = *self.stack.get(*arg1_idx as usize).unwrap();
-                    let right = *self.stack.get(*arg2_idx as usize).unwrap();
+                    let left = *self.stack.get(self.base + *arg1_idx as usize).unwrap();
+                    let right = *self.stack.get(self.base + *arg2_idx as usize).unwrap();
                     // FIXME: add implicit conversion
-                    assert!(
-                        left.ty() == right.ty(),
-                        "got {:?} and {:?}",
-                        left.ty(),
-                        right.ty()
-                    );
-                    let cmp = match left.ty() {
-                        RtType::Decimal => Ordering::from_std(unsafe {
-                            left.get_decimal_directly()
-                                .total_cmp(&right.get_decimal_directly())
-                        }),
-                        RtType::None => Ordering::Equal,
-                        RtType::Bool => {
-                            if left == right {
-                                Ordering::Equal
-                            } else {
-                                Ordering::NotEqual
-                            }
-                        }
-                        RtType::String => todo!(),
-                        RtType::Function => todo!(),
-                        RtType::Player => todo!(),
-                        RtType::Inventory => todo!(),
-                        RtType::Card => todo!(),
-                        RtType::List => todo!(),
+                    let cmp = left.compare(right);
+                    let matches = match expected {
+                        Ordering::NotEqual => cmp != Ordering::Equal,
+                        expected => *expected == cmp,
                     };
-                    self.stack.push(RtRef::bool(*expected == cmp));
+                    self.push(RtRef::bool(matches));
                 }
-                ByteCode::Return { has_val } => {
-                    let idx = if *has_val { 1 } else { 0 };
-                    let ip = self.stack.remove(self.stack.len() - 1 - idx).get_decimal().unwrap() as i64 as u64 as usize;
-                    self.ip = ip;
-                    continue;
+                ByteCode::Return { val_idx } => {
+                    let Some(frame) = self.frames.pop() else {
+                        // returning from the main code ends the execution
+                        self.ip = self.code.len();
+                        return Ok(false);
+                    };
+                    let val = val_idx
+                        .map(|idx| {
+                            Self::clone_ref(&self.config, self.stack[self.base + idx as usize])
+                        })
+                        .unwrap_or(RtRef::NULL);
+                    self.truncate(self.base);
+                    self.base = frame.base;
+                    self.ip = frame.ret_ip;
+                    if frame.push_val {
+                        self.push(val);
+                    } else {
+                        Self::cleanup(&self.config, val);
+                    }
+                    return Ok(true);
+                },
+                ByteCode::CallLocal {
+                    fn_idx,
+                    push_val,
+                    arg_indices,
+                } => {
+                    let func = &self.script_fns[*fn_idx as usize];
+                    let offset = func.offset;
+                    self.stack.reserve(func.max_depth);
+                    self.frames.push(Frame {
+                        ret_ip: self.ip + 1,
+                        base: self.base,
+                        push_val: *push_val,
+                    });
+                    // the arguments are copied straight from the caller's frame into the new one
+                    let base = self.stack.len();
+                    let mut args = mem::take(&mut self.scratch);
+                    args.clear();
+                    args.extend(arg_indices.iter().map(|idx| {
+                        Self::clone_ref(&self.config, self.stack[self.base + *idx as usize])
+                    }));
+                    for arg in args.drain(..) {
+                        self.push(arg);
+                    }
+                    self.scratch = args;
+                    self.base = base;
+                    self.ip = offset;
+                    return Ok(true);
                 },
-                ByteCode::CallLocal { relative_off } => {
-                    self.stack.push(RtRef::decimal(self.ip as f64));
-                    self.ip = (self.ip as isize + *relative_off) as usize;
-                    continue;
+                ByteCode::TailCallLocal {
+                    fn_idx,
+                    arg_indices,
+                } => {
+                    // the arguments have to be copied before the frame they live in is discarded
+                    let mut args = mem::take(&mut self.scratch);
+                    args.clear();
+                    args.extend(arg_indices.iter().map(|idx| {
+                        Self::clone_ref(&self.config, self.stack[self.base + *idx as usize])
+                    }));
+                    let fn_idx = *fn_idx as usize;
+                    self.truncate(self.base);
+                    let func = &self.script_fns[fn_idx];
+                    self.stack.reserve(func.max_depth);
+                    let offset = func.offset;
+                    for arg in args.drain(..) {
+                        self.push(arg);
+                    }
+                    self.scratch = args;
+                    self.ip = offset;
+                    return Ok(true);
                 },
             }
             self.ip += 1;
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Continues a suspended execution, `answer` is returned to the script
+    /// as the result of the host function which suspended the execution.
+    pub fn resume(&mut self, answer: RtRef) -> anyhow::Result<()> {
+        let Some(push_val) = self.suspended.take() else {
+            return diagnostic_builder!("The execution isn't suspended");
+        };
+        self.request = None;
+        if push_val {
+            self.push(answer);
+        } else {
+            Self::cleanup(&self.config, answer);
+        }
+        self.ip += 1;
+        Ok(())
+    }
+
+    /// pushes `val`, which is owned by the stack from then on
+    fn push(&mut self, val: RtRef) {
+        self.track(val);
+        self.stack.push(val);
+    }
+
+    /// counts the heap data of `val` against the memory limit as it's put onto the stack
+    fn track(&mut self, val: RtRef) {
+        if self.config.memory_limit.is_some() {
+            self.heap_bytes += heap_size(val);
+        }
+    }
+
+    /// no longer counts the heap data of `val` against the memory limit as it's taken from
+    /// the stack
+    fn untrack(&mut self, val: RtRef) {
+        if self.config.memory_limit.is_some() {
+            self.heap_bytes = self.heap_bytes.saturating_sub(heap_size(val));
         }
     }
+
+    /// frees `val`, which was taken from the stack
+    fn free(&mut self, val: RtRef) {
+        self.untrack(val);
+        Self::cleanup(&self.config, val);
+    }
+
+    /// frees the values above the first `len` ones on the stack
+    fn truncate(&mut self, len: usize) {
+        while self.stack.len() > len {
+            let val = self.stack.pop().unwrap();
+            self.free(val);
+        }
+    }
+
+    pub fn is_suspended(&self) -> bool {
+        self.suspended.is_some()
+    }
+
+    /// Takes the request of the host function which suspended the execution,
+    /// this is only needed when driving the execution via `step`.
+    pub fn take_request(&mut self) -> Option<Box<dyn Any + Send>> {
+        self.request.take()
+    }
+
+    /// sets up a new frame holding copies of `args` and jumps to `offset`
+    fn enter_fn(&mut self, offset: usize, args: &[RtRef], ret_ip: usize, push_val: bool) {
+        self.frames.push(Frame {
+            ret_ip,
+            base: self.base,
+            push_val,
+        });
+        self.base = self.stack.len();
+        for arg in args {
+            let arg = Self::clone_ref(&self.config, *arg);
+            self.push(arg);
+        }
+        self.ip = offset;
+    }
+
+    /// Calls the script function `name` with copies of `args` and returns its return value,
+    /// which is owned by the caller from then on.
+    /// Functions that don't return anything return `null`.
+    /// This can be used to let scripts react to events, even after the main code finished.
+    pub fn call(&mut self, name: &str, args: &[RtRef]) -> anyhow::Result<RtRef> {
+        let Some(func) = self.script_fns.iter().find(|func| func.name == name) else {
+            return diagnostic_builder!(format!("There is no function named `{}`", name));
+        };
+        if func.params != args.len() {
+            return diagnostic_builder!(format!(
+                "Function `{}` expects {} arguments, but got {}",
+                name,
+                func.params,
+                args.len()
+            ));
+        }
+        let depth = self.frames.len();
+        // the execution continues where it was once the function returns
+        self.stack.reserve(func.max_depth);
+        self.enter_fn(func.offset, args, self.ip, true);
+        while self.frames.len() > depth {
+            let res = self.step().and_then(|_| {
+                if self.suspended.is_some() {
+                    // FIXME: support suspending calls from the host
+                    return diagnostic_builder!(format!(
+                        "Function `{}` can't be suspended as it was called by the host",
+                        name
+                    ));
+                }
+                Ok(())
+            });
+            if let Err(err) = res {
+                // don't leave the frames of the failed call behind
+                while self.frames.len() > depth {
+                    let frame = self.frames.pop().unwrap();
+                    self.truncate(self.base);
+                    self.base = frame.base;
+                    self.ip = frame.ret_ip;
+                }
+                self.suspended = None;
+                self.request = None;
+                return Err(err);
+            }
+        }
+        // the host owns the return value now
+        let val = self.stack.pop().unwrap();
+        self.untrack(val);
+        Ok(val)
+    }
+
+    /// whether the script defines a function named `name`, see `Program::has_fn`
+    pub fn has_fn(&self, name: &str) -> bool {
+        self.script_fns.iter().any(|func| func.name == name)
+    }
+
+    /// Frees a value the vm handed out to the host, e.g. the return value of `call`.
+    pub fn release(&self, val: RtRef) {
+        Self::cleanup(&self.config, val);
+    }
+
+    /// Captures the whole execution state of the vm, the code itself isn't part of the snapshot
+    /// and thus a snapshot may only be restored on a vm running the same code.
+    pub fn snapshot(&self) -> VmSnapshot {
+        VmSnapshot {
+            ip: self.ip,
+            stack: self
+                .stack
+                .iter()
+                .map(|val| RtValue::from_ref(*val))
+                .collect::<Vec<_>>(),
+            base: self.base,
+            frames: self.frames.clone(),
+            suspended: self.suspended,
+            rng: self.host.rng.clone(),
+        }
+    }
+
+    /// Enables or disables recording host calls for this execution only, regardless of
+    /// whether the program was compiled with auditing enabled.
+    pub fn set_audit(&mut self, audit: bool) {
+        self.audit = audit;
+    }
+
+    /// Takes the host calls recorded since the log was taken the last time, the log stays
+    /// empty unless auditing is enabled.
+    pub fn take_audit_log(&mut self) -> Vec<HostCall> {
+        mem::take(&mut self.audit_log)
+    }
+
+    /// the context host functions are called with
+    pub fn host_ctx(&mut self) -> &mut HostCtx {
+        &mut self.host
+    }
+
+    pub fn restore(&mut self, snapshot: VmSnapshot) {
+        self.truncate(0);
+        for val in snapshot.stack {
+            self.push(val.into_ref());
+        }
+        self.ip = snapshot.ip;
+        self.base = snapshot.base;
+        self.frames = snapshot.frames;
+        self.suspended = snapshot.suspended;
+        self.request = None;
+        self.host.rng = snapshot.rng;
+    }
+}
+
+/// the number of bytes `val` owns on the heap
+fn heap_size(val: RtRef) -> usize {
+    match val.ty() {
+        RtType::String => size_of::<String>() + unsafe { val.get_string_directly() }.capacity(),
+        RtType::List => {
+            let list = val.get_list().unwrap();
+            // the spare capacity is owned by the list as well
+            size_of::<Vec<RtRef>>()
+                + list.capacity() * size_of::<RtRef>()
+                + list.iter().map(|val| heap_size(*val)).sum::<usize>()
+        }
+        // the host owns whatever the value refers to
+        RtType::Opaque(_) => size_of::<Box<dyn Any + Send>>(),
+        _ => 0,
+    }
+}
+
+/// A call of a host function as recorded by the audit log, see `EngineBuilder::audit`.
+#[derive(Clone, PartialEq, Debug, Serialize, Deserialize)]
+pub struct HostCall {
+    /// the number of instructions the execution took up to the call
+    pub executed: u64,
+    pub name: String,
+    pub args: Vec<RtValue>,
+    /// the value the function returned or the error it failed with
+    pub result: Result<RtValue, String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct VmSnapshot {
+    pub ip: usize,
+    pub stack: Vec<RtValue>,
+    pub base: usize,
+    pub frames: Vec<Frame>,
+    /// whether the execution was suspended, the request itself isn't part of the snapshot
+    pub suspended: Option<bool>,
+    pub rng: Rng,
+}
+
+/// The reason the vm stopped executing.
+pub enum Status {
+    Finished,
+    /// A host function suspended the execution with this request and waits for an answer,
+    /// which has to be passed to `Vm::resume` to continue the execution.
+    Suspended(Box<dyn Any + Send>),
+}
+
+/// The state of a caller which is restored once the called script function returns.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Frame {
+    ret_ip: usize,
+    base: usize,
+    push_val: bool,
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::{Arc, Mutex};
+
+    use crate::{host_fn, EngineBuilder, HostCtx, HostError, RtRef};
+
+    host_fn! {
+        #[name = "record"]
+        fn record(ctx: &mut HostCtx, val: f64) -> Result<(), HostError> {
+            ctx.data::<Arc<Mutex<Vec<f64>>>>()
+                .ok_or_else(|| HostError::new("no values to record into"))?
+                .lock()
+                .unwrap()
+                .push(val);
+            Ok(())
+        }
+    }
+
+    host_fn! {
+        #[name = "big"]
+        fn big(_ctx: &mut HostCtx) -> Result<String, HostError> {
+            Ok("x".repeat(1024))
+        }
+    }
+
+    #[test]
+    fn calls_script_functions() {
+        let program = EngineBuilder::new()
+            .compile("fn add(a, b) {\n    return a + b\n}\n")
+            .unwrap();
+        let sum = program
+            .call("add", &[RtRef::decimal(2.0), RtRef::decimal(3.0)])
+            .unwrap();
+        assert_eq!(sum.get_decimal(), Some(5.0));
+    }
+
+    #[test]
+    fn executions_have_their_own_data() {
+        let program = EngineBuilder::new()
+            .function(record())
+            .compile("let i = 0\nwhile i < 3 {\n    record(i)\n    i = i + 1\n}\n")
+            .unwrap();
+        let first = Arc::new(Mutex::new(Vec::<f64>::new()));
+        let second = Arc::new(Mutex::new(Vec::<f64>::new()));
+        program.execute_with(first.clone()).unwrap();
+        program.execute_with(second.clone()).unwrap();
+        assert_eq!(*first.lock().unwrap(), [0.0, 1.0, 2.0]);
+        assert_eq!(*second.lock().unwrap(), [0.0, 1.0, 2.0]);
+        // without any data attached the host function fails instead of the vm
+        assert!(program.execute().is_err());
+    }
+
+    #[test]
+    fn aborts_executions_exceeding_the_memory_limit() {
+        let src = "let s = big()\n";
+        let limited = EngineBuilder::new()
+            .function(big())
+            .memory_limit(512)
+            .compile(src)
+            .unwrap();
+        let err = limited.execute().unwrap_err();
+        assert!(err.to_string().contains("memory limit"), "{err}");
+        let sufficient = EngineBuilder::new()
+            .function(big())
+            .memory_limit(4096)
+            .compile(src)
+            .unwrap();
+        sufficient.execute().unwrap();
+    }
+
+    #[test]
+    fn rejects_denied_functions() {
+        let builder = || EngineBuilder::new().function(big());
+        assert!(builder().compile("let s = big()\n").is_ok());
+        let err = builder()
+            .deny("big", "it's too big")
+            .compile("let s = big()\n")
+            .unwrap_err();
+        assert!(format!("{err:?}").contains("it's too big"), "{err:?}");
+    }
 }
@@ -0,0 +1,207 @@
+//! Javascript bindings which allow scripts to be compiled and run in the browser.
+
+use js_sys::Array;
+use wasm_bindgen::prelude::*;
+
+use crate::{EngineBuilder, Function, HostError, Program, RtRef, RtValue, Status, Vm};
+
+/// Collects the host functions implemented in javascript and compiles scripts against them.
+#[wasm_bindgen(js_name = Engine)]
+pub struct WasmEngine {
+    funcs: Vec<(&'static str, JsHostFn)>,
+    seed: Option<u64>,
+}
+
+#[wasm_bindgen(js_class = Engine)]
+impl WasmEngine {
+    #[wasm_bindgen(constructor)]
+    pub fn new() -> Self {
+        Self {
+            funcs: vec![],
+            seed: None,
+        }
+    }
+
+    /// Registers `func` as host function `name`. It gets called with the script's arguments
+    /// converted to javascript values and its return value is passed back to the script,
+    /// `undefined` and `null` mean that the function doesn't return anything.
+    pub fn register(&mut self, name: String, func: js_sys::Function) {
+        // function names have to outlive all programs compiled against them
+        let name: &'static str = Box::leak(name.into_boxed_str());
+        self.funcs.push((name, JsHostFn(func)));
+    }
+
+    /// Seeds the rng of all programs compiled afterwards, which makes their executions
+    /// reproducible.
+    pub fn seed(&mut self, seed: u64) {
+        self.seed = Some(seed);
+    }
+
+    pub fn compile(&self, src: &str) -> Result<WasmProgram, JsError> {
+        let funcs = self.funcs.iter().map(|(name, func)| {
+            let func = func.clone();
+            Function {
+                params: &[],
+                var_len: true,
+                name,
+                desc: "provided by the javascript host",
+                groups: &[],
+                call: Box::new(move |_ctx, args| func.call(args)),
+            }
+        });
+        let mut builder = EngineBuilder::new().functions(funcs);
+        if let Some(seed) = self.seed {
+            builder = builder.seed(seed);
+        }
+        builder
+            .compile(src)
+            .map(|program| WasmProgram { program })
+            .map_err(js_err)
+    }
+}
+
+#[wasm_bindgen(js_name = Program)]
+pub struct WasmProgram {
+    program: Program,
+}
+
+#[wasm_bindgen(js_class = Program)]
+impl WasmProgram {
+    /// Runs the program to completion, this blocks the page while the script is running.
+    pub fn run(&self) -> Result<(), JsError> {
+        self.program.execute().map_err(js_err)
+    }
+
+    /// Creates a vm which executes the program step by step, which allows long running
+    /// scripts to be spread over multiple frames.
+    pub fn instantiate(&self) -> WasmVm {
+        WasmVm {
+            vm: self.program.instantiate(),
+        }
+    }
+
+    /// Calls the script function `name`, the arguments and the return value are converted
+    /// just like the ones of host functions.
+    pub fn call(&self, name: &str, args: Vec<JsValue>) -> Result<JsValue, JsError> {
+        let args = args
+            .iter()
+            .map(from_js)
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(|err| JsError::new(&err.to_string()))?;
+        let ret = self.program.call(name, &args);
+        // the vm only received copies of the arguments
+        for arg in args {
+            Vm::cleanup(&self.program.config, arg);
+        }
+        let ret = ret.map_err(js_err)?;
+        let js_ret = to_js(RtValue::from_ref(ret));
+        Vm::cleanup(&self.program.config, ret);
+        Ok(js_ret)
+    }
+}
+
+#[wasm_bindgen(js_name = Vm)]
+pub struct WasmVm {
+    vm: Vm,
+}
+
+#[wasm_bindgen(js_class = Vm)]
+impl WasmVm {
+    /// Executes a single instruction, returns whether there is more to execute.
+    pub fn step(&mut self) -> Result<bool, JsError> {
+        self.vm.step().map_err(js_err)
+    }
+
+    pub fn run(&mut self) -> Result<(), JsError> {
+        match self.vm.run().map_err(js_err)? {
+            Status::Finished => Ok(()),
+            // javascript host functions can't suspend the execution
+            Status::Suspended(_) => unreachable!(),
+        }
+    }
+}
+
+impl Default for WasmEngine {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A host function implemented in javascript.
+#[derive(Clone)]
+struct JsHostFn(js_sys::Function);
+
+// SAFETY: wasm32 is single threaded, so the function never actually crosses threads
+unsafe impl Send for JsHostFn {}
+unsafe impl Sync for JsHostFn {}
+
+impl JsHostFn {
+    fn call(&self, args: &[RtRef]) -> Result<Option<RtRef>, HostError> {
+        let js_args = Array::new();
+        for arg in args {
+            js_args.push(&to_js(RtValue::from_ref(*arg)));
+        }
+        let ret = self
+            .0
+            .apply(&JsValue::UNDEFINED, &js_args)
+            .map_err(|err| HostError::new(format!("{:?}", err)))?;
+        if ret.is_undefined() || ret.is_null() {
+            return Ok(None);
+        }
+        from_js(&ret).map(Some)
+    }
+}
+
+/// converts a script value, players, inventories, cards and functions are passed as their
+/// indices
+fn to_js(val: RtValue) -> JsValue {
+    match val {
+        RtValue::None => JsValue::NULL,
+        RtValue::Decimal(val) => JsValue::from_f64(val),
+        RtValue::Bool(val) => JsValue::from_bool(val),
+        RtValue::String(val) => JsValue::from_str(&val),
+        RtValue::Function(idx) => JsValue::from_f64(idx as f64),
+        RtValue::List(vals) => {
+            let list = Array::new();
+            for val in vals {
+                list.push(&to_js(val));
+            }
+            list.into()
+        }
+        RtValue::Player(player) => JsValue::from_f64(player.idx() as f64),
+        RtValue::Inventory(idx) => JsValue::from_f64(idx as f64),
+        RtValue::Card(card) => JsValue::from_f64(card.idx() as f64),
+    }
+}
+
+fn from_js(val: &JsValue) -> Result<RtRef, HostError> {
+    Ok(from_js_val(val)?.into_ref())
+}
+
+fn from_js_val(val: &JsValue) -> Result<RtValue, HostError> {
+    if val.is_undefined() || val.is_null() {
+        Ok(RtValue::None)
+    } else if let Some(val) = val.as_f64() {
+        Ok(RtValue::Decimal(val))
+    } else if let Some(val) = val.as_bool() {
+        Ok(RtValue::Bool(val))
+    } else if let Some(val) = val.as_string() {
+        Ok(RtValue::String(val))
+    } else if Array::is_array(val) {
+        Ok(RtValue::List(
+            Array::from(val)
+                .iter()
+                .map(|val| from_js_val(&val))
+                .collect::<Result<Vec<_>, _>>()?,
+        ))
+    } else {
+        Err(HostError::new(format!(
+            "{:?} can't be passed to scripts",
+            val
+        )))
+    }
+}
+
+fn js_err(err: anyhow::Error) -> JsError {
+    JsError::new(&err.to_string())
+}
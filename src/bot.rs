@@ -0,0 +1,361 @@
+//! Players whose decisions are made by strategy scripts instead of people.
+//!
+//! Strategy scripts are written in the same language as games, but they only define
+//! functions which get called whenever their player has to decide something:
+//! `chooseCard(view)`, `choosePlayer(view)`, `chooseOption(view)` and `confirm(view)`.
+//! The view holds what the player may see of the game, the strategy selects options
+//! by their position using `pick(view, pos)`. Face down cards can be picked, but not
+//! looked at, see `optionVisible(view, pos)`. Confirmations are answered with yes by
+//! picking `1`. Decisions the strategy doesn't define a function for are made at random.
+//!
+//! Games without strategies can still be played against the built-in policies of `Bot`.
+
+use std::sync::{Arc, Mutex};
+
+use engine::{
+    host_fn, CardVal, EngineBuilder, Function, HostCtx, HostError, OpaqueType, Player, Program,
+    Rng, RtRef,
+};
+
+use crate::{
+    funcs::{
+        card_meta, card_name, card_ord, card_rank, card_suit, game, has_tag, is_connected,
+        player_cnt, player_name, round_number, team_of,
+    },
+    game_ctx::GameCtx,
+    query::Query,
+};
+
+#[cfg(feature = "fs")]
+pub const BOTS_DIR: &str = "./play_cards/bots/";
+
+/// the opaque type tag of views
+const VIEW: u32 = 1;
+
+/// What the player of a strategy sees of the decision they have to make.
+#[derive(Clone)]
+struct View {
+    query: Query,
+    /// shared by all copies of the view, so the picks reach the host
+    picks: Arc<Mutex<Vec<usize>>>,
+}
+
+fn view(val: RtRef) -> Result<View, HostError> {
+    val.get_opaque::<View>()
+        .cloned()
+        .ok_or_else(|| HostError::new("expected a view"))
+}
+
+/// The functions strategies can use, they can't change the game and only reveal
+/// what their player may see.
+pub fn bot_functions() -> Vec<Function> {
+    vec![
+        me(),
+        option_cnt(),
+        option_card(),
+        option_visible(),
+        option_player(),
+        option_text(),
+        select_cnt(),
+        pick(),
+        hand_size(),
+        discard_top(),
+        turn_number(),
+        player_cnt(),
+        player_name(),
+        is_connected(),
+        round_number(),
+        team_of(),
+        card_meta(),
+        card_name(),
+        card_ord(),
+        card_suit(),
+        card_rank(),
+        has_tag(),
+    ]
+}
+
+host_fn! {
+    /// returns the player the strategy decides for
+    #[name = "me"]
+    pub fn me(_ctx: &mut HostCtx, view: RtRef) -> Result<Player, HostError> {
+        Ok(Player::new(self::view(view)?.query.player() as u64))
+    }
+}
+
+host_fn! {
+    /// returns the number of options the player can pick from
+    #[name = "optionCount"]
+    pub fn option_cnt(_ctx: &mut HostCtx, view: RtRef) -> Result<usize, HostError> {
+        Ok(self::view(view)?.query.options())
+    }
+}
+
+host_fn! {
+    /// returns the card at `pos` of the cards the player selects from, which fails if the
+    /// card is face down to the player
+    #[name = "optionCard"]
+    pub fn option_card(
+        ctx: &mut HostCtx,
+        view: RtRef,
+        pos: usize
+    ) -> Result<CardVal, HostError> {
+        let query = self::view(view)?.query;
+        let card = option(&query, pos)?;
+        if !game(ctx)?.state().card_visible_to(card, query.player()) {
+            return Err(HostError::new(format!("option {pos} is a face down card")));
+        }
+        Ok(CardVal::new(card as u64))
+    }
+}
+
+host_fn! {
+    /// returns whether the player can see the card at `pos` of the cards they select from
+    #[name = "optionVisible"]
+    pub fn option_visible(ctx: &mut HostCtx, view: RtRef, pos: usize) -> Result<bool, HostError> {
+        let query = self::view(view)?.query;
+        let card = option(&query, pos)?;
+        Ok(game(ctx)?.state().card_visible_to(card, query.player()))
+    }
+}
+
+/// the card at `pos` of the cards selected from in `query`
+fn option(query: &Query, pos: usize) -> Result<usize, HostError> {
+    match query {
+        Query::SelectCards { cards, .. } => cards
+            .get(pos)
+            .copied()
+            .ok_or_else(|| HostError::new(format!("there is no option {pos}"))),
+        _ => Err(HostError::new("the player doesn't select cards")),
+    }
+}
+
+host_fn! {
+    /// returns the player at `pos` of the players the player selects from
+    #[name = "optionPlayer"]
+    pub fn option_player(
+        _ctx: &mut HostCtx,
+        view: RtRef,
+        pos: usize
+    ) -> Result<Player, HostError> {
+        match self::view(view)?.query {
+            Query::SelectPlayers { players, .. } if pos < players.len() => {
+                Ok(Player::new(players[pos] as u64))
+            }
+            Query::SelectPlayers { .. } => {
+                Err(HostError::new(format!("there is no option {pos}")))
+            }
+            _ => Err(HostError::new("the player doesn't select players")),
+        }
+    }
+}
+
+host_fn! {
+    /// returns the option at `pos` of a choice or the question of a confirmation
+    #[name = "optionText"]
+    pub fn option_text(
+        _ctx: &mut HostCtx,
+        view: RtRef,
+        pos: usize
+    ) -> Result<String, HostError> {
+        match self::view(view)?.query {
+            Query::Choice { options, .. } => options
+                .get(pos)
+                .cloned()
+                .ok_or_else(|| HostError::new(format!("there is no option {pos}"))),
+            Query::Confirm { question, .. } => Ok(question),
+            _ => Err(HostError::new("the player doesn't choose between texts")),
+        }
+    }
+}
+
+host_fn! {
+    /// returns the number of options the player has to pick
+    #[name = "selectCount"]
+    pub fn select_cnt(_ctx: &mut HostCtx, view: RtRef) -> Result<usize, HostError> {
+        Ok(match self::view(view)?.query {
+            Query::SelectCards { cnt, .. } | Query::SelectPlayers { cnt, .. } => cnt,
+            Query::Choice { .. } | Query::Confirm { .. } => 1,
+        })
+    }
+}
+
+host_fn! {
+    /// selects the option at `pos`, the picks are checked once the strategy returns
+    #[name = "pick"]
+    pub fn pick(_ctx: &mut HostCtx, view: RtRef, pos: usize) -> Result<(), HostError> {
+        self::view(view)?.picks.lock().unwrap().push(pos);
+        Ok(())
+    }
+}
+
+host_fn! {
+    /// returns the number of cards in the hand of the player, the cards themselves
+    /// might be hidden
+    #[name = "handSize"]
+    pub fn hand_size(ctx: &mut HostCtx, player: Player) -> Result<usize, HostError> {
+        let game = game(ctx)?;
+        let state = game.state();
+        let def = state
+            .players
+            .get(player.idx() as usize)
+            .ok_or_else(|| HostError::new(format!("player {} doesn't exist", player.idx())))?;
+        Ok(def
+            .hand
+            .map_or(0, |hand| state.inventories[hand as usize].cards.len()))
+    }
+}
+
+host_fn! {
+    /// returns the card on top of the discard pile, if there is any
+    #[name = "discardTop"]
+    pub fn discard_top(ctx: &mut HostCtx) -> Result<Option<CardVal>, HostError> {
+        Ok(game(ctx)?
+            .state()
+            .discard_top()
+            .map(|card| CardVal::new(card as u64)))
+    }
+}
+
+host_fn! {
+    /// returns the number of turns played so far
+    #[name = "turnNumber"]
+    pub fn turn_number(ctx: &mut HostCtx) -> Result<usize, HostError> {
+        Ok(game(ctx)?.state().turns)
+    }
+}
+
+/// A player controlled by the host.
+pub enum Bot {
+    /// picks random valid options
+    Random,
+    /// picks the cards with the highest value of the metadata `key` or the highest
+    /// ordinal if there is no key, face down cards are picked last and other decisions
+    /// are made at random
+    Greedy {
+        key: Option<String>,
+    },
+    Strategy(StrategyBot),
+}
+
+impl Bot {
+    /// Loads the bot called `name`, which is either `random`, `greedy`, `greedy:<key>`
+    /// or the name of a strategy in the bots directory.
+    #[cfg(feature = "fs")]
+    pub fn load(name: &str) -> anyhow::Result<Self> {
+        Ok(match name {
+            "random" => Bot::Random,
+            "greedy" => Bot::Greedy { key: None },
+            _ => match name.strip_prefix("greedy:") {
+                Some(key) => Bot::Greedy {
+                    key: Some(key.to_string()),
+                },
+                None => Bot::Strategy(StrategyBot::load(name)?),
+            },
+        })
+    }
+
+    /// Lets the bot answer `query`, only strategies can fail.
+    pub fn answer(&self, game: &Arc<GameCtx>, query: &Query) -> anyhow::Result<String> {
+        let mut rng = Rng::new(Rng::entropy_seed());
+        match self {
+            Bot::Random => Ok(query.random_answer(&game.game, &mut rng)),
+            Bot::Greedy { key } => {
+                let Query::SelectCards { cards, .. } = query else {
+                    return Ok(query.random_answer(&game.game, &mut rng));
+                };
+                let state = game.state();
+                let value = |card: usize| {
+                    // face down cards are unknown to the player, so they are all worth the
+                    // same and their order doesn't tell anything about them
+                    if !state.card_visible_to(card, query.player()) {
+                        return f64::NEG_INFINITY;
+                    }
+                    let card = &game.game.cards[card];
+                    match key {
+                        // cards without a numeric value are picked last
+                        Some(key) => card
+                            .metadata
+                            .get(key)
+                            .and_then(|val| val.parse::<f64>().ok())
+                            .unwrap_or(f64::NEG_INFINITY),
+                        None => card.ord as f64,
+                    }
+                };
+                let mut options = (0..cards.len()).collect::<Vec<_>>();
+                options.sort_by(|a, b| value(cards[*b]).total_cmp(&value(cards[*a])));
+                Ok(query.preferred_answer(&game.game, options))
+            }
+            Bot::Strategy(strategy) => strategy.answer(game, query),
+        }
+    }
+}
+
+/// A player controlled by a strategy script.
+pub struct StrategyBot {
+    program: Program,
+}
+
+impl StrategyBot {
+    /// Compiles the strategy script `src`.
+    pub fn new(src: &str) -> anyhow::Result<Self> {
+        let program = EngineBuilder::new()
+            .functions(bot_functions())
+            .opaque_type(VIEW, OpaqueType::new::<View>("view"))
+            .compile(src)?;
+        Ok(Self { program })
+    }
+
+    /// Loads the strategy called `name` from the bots directory.
+    #[cfg(feature = "fs")]
+    pub fn load(name: &str) -> anyhow::Result<Self> {
+        Self::new(&std::fs::read_to_string(format!(
+            "{}{}.pc",
+            BOTS_DIR, name
+        ))?)
+    }
+
+    /// Lets the strategy answer `query`, which fails if the strategy fails or picks
+    /// options that aren't a valid answer.
+    pub fn answer(&self, game: &Arc<GameCtx>, query: &Query) -> anyhow::Result<String> {
+        let name = match query {
+            Query::SelectCards { .. } => "chooseCard",
+            Query::SelectPlayers { .. } => "choosePlayer",
+            Query::Choice { .. } => "chooseOption",
+            Query::Confirm { .. } => "confirm",
+        };
+        let mut vm = self.program.instantiate();
+        if !vm.has_fn(name) {
+            return Ok(query.random_answer(&game.game, vm.host_ctx().rng()));
+        }
+        vm.host_ctx().set_data(game.clone());
+        let picks = Arc::new(Mutex::new(vec![]));
+        let view = RtRef::opaque(
+            VIEW,
+            View {
+                query: query.clone(),
+                picks: picks.clone(),
+            },
+        );
+        let ret = vm.call(name, &[view]);
+        // the vm only received a copy of the view
+        vm.release(view);
+        vm.release(ret?);
+        let picks = picks.lock().unwrap();
+        let answer = match query {
+            Query::Confirm { .. } => match picks.as_slice() {
+                [1] => "yes".to_string(),
+                _ => "no".to_string(),
+            },
+            _ => picks
+                .iter()
+                .map(|pos| pos.to_string())
+                .collect::<Vec<_>>()
+                .join(" "),
+        };
+        query
+            .parse(&game.game, &answer)
+            .map_err(|err| anyhow::anyhow!("The strategy picked invalid options: {}", err))?;
+        Ok(answer)
+    }
+}
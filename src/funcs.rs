@@ -1,123 +1,1492 @@
-use std::sync::atomic::Ordering;
+use std::{
+    cmp,
+    sync::{atomic::Ordering, Arc},
+};
 
-use engine::{CardInventory, CardInventoryRef, Player, RtRef};
+use serde::{Deserialize, Serialize};
 
-use crate::get_ctx;
+use engine::{
+    host_fn, CardInventory, CardInventoryRef, CardVal, Function, HostCtx, HostError, IntoRtRef,
+    LintRule, Player, RtRef, RtType, RtValue,
+};
 
-pub fn next_player(_args: Vec<RtRef>) -> Option<RtRef> {
-    let ctx = get_ctx();
-    let mut curr_player = ctx.curr_player.load(Ordering::Acquire);
-    loop {
-        curr_player = (curr_player + 1) % ctx.players.len();
-        if ctx.players[curr_player].active {
-            ctx.curr_player.store(curr_player, Ordering::Release);
-            return Some(RtRef::player(Player::new(curr_player as u64)));
+#[cfg(feature = "fs")]
+use crate::stats::Stats;
+use crate::{
+    game_ctx::{CardTemplate, GameCtx, GameEvent, GameState, GameTemplate, Hook, Zone},
+    query::Query,
+    stats::DEFAULT_RATING,
+};
+
+pub fn host_functions() -> Vec<Function> {
+    vec![
+        next_player(),
+        prev_player(),
+        skip_players(),
+        reverse_order(),
+        set_current_player(),
+        player_cnt(),
+        player_name(),
+        rand_players(),
+        select_cards(),
+        select_players(),
+        prompt_choice(),
+        confirm(),
+        assign_team(),
+        team_of(),
+        team_score(),
+        player_rating(),
+        create_inv_global(),
+        create_inv_restricted(),
+        draw_card(),
+        draw_cards(),
+        shuffle_draw_stack(),
+        shuffle_inv(),
+        set_hand(),
+        deal_cards(),
+        move_card(),
+        give_cards(),
+        transfer_all(),
+        swap_hands(),
+        discard(),
+        discard_pile(),
+        take_from_discard(),
+        peek_top(),
+        inv_size(),
+        inv_slots(),
+        inv_free_slots(),
+        inv_contains(),
+        cards_in(),
+        count_cards(),
+        sort_inventory(),
+        card_meta(),
+        cards_with_meta(),
+        card_name(),
+        card_ord(),
+        card_suit(),
+        card_rank(),
+        has_tag(),
+        cards_with_tag(),
+        card_by_name(),
+        reveal_card(),
+        end_game(),
+        eliminate_player(),
+        activate_player(),
+        is_connected(),
+        start_round(),
+        round_number(),
+        Function {
+            params: &[],
+            var_len: true,
+            name: "storeMeta",
+            desc:
+                "stores a meta value for a player (`player, name, val`) or the game (`name, val`)",
+            groups: &["meta"],
+            call: Box::new(store_meta),
+        },
+        Function {
+            params: &[],
+            var_len: true,
+            name: "loadMeta",
+            desc: "loads a meta value of a player (`player, name`) or the game (`name`)",
+            groups: &["meta"],
+            call: Box::new(load_meta),
+        },
+    ]
+}
+
+/// The card game specific lints scripts are checked for.
+pub fn lint_rules() -> Vec<LintRule> {
+    vec![
+        LintRule::CalledWithout {
+            name: "unchecked_player_count",
+            call: "nextPlayer",
+            checks: &["playerCount"],
+            hint: "the game may not work for every number of players it allows",
+        },
+        LintRule::NeverUsed {
+            name: "unfilled_inventory",
+            creates: &["createInvGlobal", "createInvRestricted"],
+            uses: &[
+                "drawCard",
+                "drawCards",
+                "setHand",
+                "moveCard",
+                "giveCards",
+                "transferAll",
+                "takeFromDiscard",
+            ],
+            hint: "no cards are ever put into it",
+        },
+        LintRule::LoopWithout {
+            name: "loop_without_prompt",
+            funcs: &[
+                "selectCards",
+                "selectPlayers",
+                "promptChoice",
+                "confirm",
+                "endGame",
+            ],
+            hint: "so nobody is ever asked anything and it most likely never ends",
+        },
+    ]
+}
+
+/// A group of host functions games have to declare in order to call them, functions which
+/// don't belong to any group can always be called. Host functions name their groups with
+/// `#[group = "..."]`.
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum Capability {
+    /// printing and reading the stats
+    Io,
+    /// shuffling, picking at random and asking players, who may be answered for at random
+    Rng,
+    /// storing and loading meta values
+    Meta,
+    /// creating inventories, looking into them and moving cards between them
+    Inventory,
+}
+
+impl Capability {
+    /// The capability called `group`.
+    pub fn of(group: &str) -> Option<Self> {
+        match group {
+            "io" => Some(Capability::Io),
+            "rng" => Some(Capability::Rng),
+            "meta" => Some(Capability::Meta),
+            "inventory" => Some(Capability::Inventory),
+            _ => None,
         }
     }
-}
 
-pub fn player_cnt(_args: Vec<RtRef>) -> Option<RtRef> {
-    let mut players = 0;
-    for player in get_ctx().players.iter() {
-        if player.active {
-            players += 1;
+    pub fn name(self) -> &'static str {
+        match self {
+            Capability::Io => "io",
+            Capability::Rng => "rng",
+            Capability::Meta => "meta",
+            Capability::Inventory => "inventory",
         }
     }
-    Some(RtRef::decimal(players as f64))
 }
 
-pub fn player_name(args: Vec<RtRef>) -> Option<RtRef> {
-    let player = if args.is_empty() {
-        Player::new(get_ctx().curr_player.load(Ordering::Acquire) as u64)
-    } else {
-        args.first().unwrap().get_player().unwrap()
-    };
-    Some(RtRef::string(Box::new(
-        get_ctx().players[player.idx() as usize].name.clone(),
-    )))
+/// the game the host function is called for
+pub(crate) fn game(ctx: &HostCtx) -> Result<Arc<GameCtx>, HostError> {
+    ctx.data::<Arc<GameCtx>>()
+        .cloned()
+        .ok_or_else(|| HostError::new("no game is attached to the execution"))
+}
+
+/// the player at `idx` of `args` which has to be part of the game
+fn player_arg(state: &GameState, args: &[RtRef], idx: usize) -> Result<Player, HostError> {
+    let player = args
+        .get(idx)
+        .and_then(|val| val.get_player())
+        .ok_or_else(|| HostError::invalid_arg(idx, RtType::Player))?;
+    check_player(state, player)
+}
+
+fn check_player(state: &GameState, player: Player) -> Result<Player, HostError> {
+    if player.idx() as usize >= state.players.len() {
+        return Err(HostError::new(format!(
+            "player {} isn't part of the game",
+            player.idx()
+        )));
+    }
+    Ok(player)
 }
 
-pub fn create_inv_global(args: Vec<RtRef>) -> Option<RtRef> {
-    let slots = args[0].get_decimal().unwrap();
-    get_ctx().inventories.lock().unwrap().push(CardInventory {
-        slots: slots as i64 as u64,
-        vis: None,
-        cards: vec![],
+fn slot_count(slots: f64) -> Result<u64, HostError> {
+    if slots < 0.0 || slots.fract() != 0.0 {
+        return Err(HostError::new(format!(
+            "expected a whole number of slots which isn't negative, but got {}",
+            slots
+        )));
+    }
+    Ok(slots as u64)
+}
+
+fn card_template(game: &GameTemplate, card: CardVal) -> Result<&CardTemplate, HostError> {
+    game.card(card)
+        .ok_or_else(|| HostError::new(format!("card {} doesn't exist", card.idx())))
+}
+
+fn inventory(state: &GameState, inv: CardInventoryRef) -> Result<&CardInventory, HostError> {
+    state
+        .inventories
+        .get(inv.0 as usize)
+        .ok_or_else(|| HostError::new(format!("inventory {} doesn't exist", inv.0)))
+}
+
+/// lets the player answer the query and records the answer,
+/// players nobody answers for decide at random and players who run out of time skip it
+fn ask(ctx: &mut HostCtx, game: &GameCtx, query: &Query) -> Result<Vec<usize>, HostError> {
+    let answer = game.decide(query.player(), || {
+        game.answer(query)
+            .unwrap_or_else(|| Some(query.random_answer(&game.game, ctx.rng())))
+    })?;
+    let answer = answer.unwrap_or_else(|| {
+        game.trigger(Hook::Timeout {
+            player: query.player(),
+        });
+        query.skip_answer(&game.game, ctx.rng())
     });
-    Some(RtRef::inventory(CardInventoryRef(
-        (get_ctx().inventories.lock().unwrap().len() - 1) as u64,
-    )))
-}
-
-pub fn create_inv_restricted(args: Vec<RtRef>) -> Option<RtRef> {
-    let slots = args[0].get_decimal().unwrap();
-    let players = args
-        .iter()
-        .skip(1)
-        .map(|val| val.get_player().unwrap())
-        .collect::<Vec<_>>();
-    get_ctx().inventories.lock().unwrap().push(CardInventory {
-        slots: slots as i64 as u64,
-        vis: Some(players),
-        cards: vec![],
+    let selected = query.parse(&game.game, &answer).map_err(HostError::new)?;
+    game.emit(&GameEvent::Answered {
+        player: query.player(),
+        answer,
     });
-    Some(RtRef::inventory(CardInventoryRef(
-        (get_ctx().inventories.lock().unwrap().len() - 1) as u64,
-    )))
-}
-
-pub fn store_meta(args: Vec<RtRef>) -> Option<RtRef> {
-    assert!(args.len() < 4 && args.len() > 1);
-    if args.len() == 3 {
-        let player = args[0].get_player().unwrap();
-        let meta_name = args[1].get_string().unwrap();
-        let meta_val = args[2];
-        get_ctx().players[player.idx() as usize]
-            .meta
-            .lock()
-            .unwrap()
-            .insert(meta_name.clone(), meta_val);
-    } else {
-        // FIXME: insert into game
-    }
-    None
-}
-
-pub fn load_meta(args: Vec<RtRef>) -> Option<RtRef> {
-    assert!(args.len() < 4 && args.len() > 1);
-    if args.len() == 2 {
-        let player = args[0].get_player().unwrap();
-        let meta_name = args[1].get_string().unwrap();
-        get_ctx().players[player.idx() as usize]
-            .meta
-            .lock()
-            .unwrap()
-            .get(meta_name)
-            .cloned()
+    Ok(selected)
+}
+
+fn inventory_mut(
+    state: &mut GameState,
+    inv: CardInventoryRef,
+) -> Result<&mut CardInventory, HostError> {
+    state
+        .inventories
+        .get_mut(inv.0 as usize)
+        .ok_or_else(|| HostError::new(format!("inventory {} doesn't exist", inv.0)))
+}
+
+/// the inventory holding the hand of the player
+fn hand(state: &GameState, player: Player) -> Result<CardInventoryRef, HostError> {
+    let player = &state.players[check_player(state, player)?.idx() as usize];
+    player
+        .hand
+        .map(CardInventoryRef)
+        .ok_or_else(|| HostError::new(format!("player {} doesn't have a hand", player.name)))
+}
+
+/// puts `card` coming `from` somewhere into the inventory, every card that enters an inventory
+/// has to go through here in order to respect its slots and to be logged. As this fails after
+/// the card was taken from wherever it was, it has to be called from within `with_state`.
+fn put(
+    state: &mut GameState,
+    from: Option<Zone>,
+    inv: CardInventoryRef,
+    card: CardVal,
+) -> Result<(), HostError> {
+    let target = inventory_mut(state, inv)?;
+    if target.is_full() {
+        return Err(HostError::new(format!("inventory {} is full", inv.0)));
+    }
+    target.cards.push(card);
+    state.pending.push(GameEvent::CardMoved {
+        card: card.idx() as usize,
+        from,
+        to: Zone::Inventory(inv.0 as usize),
+    });
+    Ok(())
+}
+
+/// moves the top card of the draw stack into the inventory
+fn draw(state: &mut GameState, inv: CardInventoryRef) -> Result<CardVal, HostError> {
+    let card = state
+        .draw_stack
+        .pop()
+        .ok_or_else(|| HostError::new("the draw stack is empty"))?;
+    let card = CardVal::new(card as u64);
+    put(state, Some(Zone::DrawStack), inv, card)?;
+    Ok(card)
+}
+
+/// removes `card` from wherever it is in order to move it into `inv` and returns where it was.
+/// The draw stack gives up any card, other inventories too, but the discard pile only its top
+/// card. If there are several copies of the card, only one of them is moved.
+fn take(state: &mut GameState, card: CardVal, inv: CardInventoryRef) -> Result<Zone, HostError> {
+    let idx = card.idx() as usize;
+    if let Some(pos) = state.draw_stack.iter().position(|other| *other == idx) {
+        state.draw_stack.remove(pos);
+        return Ok(Zone::DrawStack);
+    }
+    for (other, src) in state.inventories.iter_mut().enumerate() {
+        if other == inv.0 as usize {
+            continue;
+        }
+        if let Some(pos) = src.cards.iter().position(|other| *other == card) {
+            src.cards.remove(pos);
+            return Ok(Zone::Inventory(other));
+        }
+    }
+    if state.discard_top() == Some(idx) {
+        state.discard_pile.pop();
+        return Ok(Zone::DiscardPile);
+    }
+    Err(HostError::new(if state.discard_pile.contains(&idx) {
+        format!("card {} is buried in the discard pile", idx)
     } else {
-        // FIXME: load from game
-        todo!()
+        format!("card {} is already in inventory {}", idx, inv.0)
+    }))
+}
+
+/// moves `card` from the inventory `from` into the inventory `to`
+fn transfer(
+    state: &mut GameState,
+    from: CardInventoryRef,
+    to: CardInventoryRef,
+    card: CardVal,
+) -> Result<(), HostError> {
+    let src = inventory_mut(state, from)?;
+    let Some(pos) = src.cards.iter().position(|val| *val == card) else {
+        return Err(HostError::new(format!(
+            "card {} isn't in inventory {}",
+            card.idx(),
+            from.0
+        )));
+    };
+    let card = src.cards.remove(pos);
+    put(state, Some(Zone::Inventory(from.0 as usize)), to, card)
+}
+
+/// compares metadata values, numbers are compared by their value and cards without
+/// the value come last
+fn cmp_meta(a: Option<&String>, b: Option<&String>) -> cmp::Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => match (a.parse::<f64>(), b.parse::<f64>()) {
+            (Ok(a), Ok(b)) => a.total_cmp(&b),
+            _ => a.cmp(b),
+        },
+        (Some(_), None) => cmp::Ordering::Less,
+        (None, Some(_)) => cmp::Ordering::Greater,
+        (None, None) => cmp::Ordering::Equal,
     }
 }
 
-pub fn rand_players(args: Vec<RtRef>) -> Option<RtRef> {
-    let ctx = get_ctx();
-    if args.is_empty() {
+/// replaces the engine's `println` for games that are run without anyone watching
+pub fn silent_println() -> Function {
+    Function {
+        params: &[RtType::String],
+        var_len: true,
+        name: "println",
+        desc: "formats the values into the `{}`s of the string and prints it",
+        groups: &["io"],
+        call: Box::new(|_ctx, _args| Ok(None)),
+    }
+}
+
+/// makes the active player `steps` seats away from the current one the current player,
+/// negative `steps` go against the direction of play
+fn move_turn(state: &mut GameState, steps: isize) -> Result<usize, HostError> {
+    if !state.players.iter().any(|player| player.active) {
+        return Err(HostError::new("there are no active players left"));
+    }
+    let forward = (steps >= 0) != state.reversed;
+    for _ in 0..steps.unsigned_abs() {
         loop {
-            let player = ctx.players[]
+            state.curr_player = if forward {
+                (state.curr_player + 1) % state.players.len()
+            } else {
+                (state.curr_player + state.players.len() - 1) % state.players.len()
+            };
+            if state.players[state.curr_player].active {
+                break;
+            }
+        }
+    }
+    Ok(state.curr_player)
+}
+
+/// the other active players in the order they play in, starting next to the current player
+/// and going with the direction of play if `forward` is set or against it otherwise
+fn others_in_order(state: &GameState, forward: bool) -> Vec<usize> {
+    let len = state.players.len();
+    (1..len)
+        .map(|offset| {
+            if forward != state.reversed {
+                (state.curr_player + offset) % len
+            } else {
+                (state.curr_player + len - offset) % len
+            }
+        })
+        .filter(|player| state.players[*player].active)
+        .collect()
+}
+
+/// starts the turn of the player `steps` seats away from the current one
+fn start_turn(ctx: &HostCtx, steps: isize) -> Result<Player, HostError> {
+    let game = game(ctx)?;
+    let (player, turn) = game.with_state(|state| {
+        let player = move_turn(state, steps)?;
+        state.turns += 1;
+        Ok((player, state.turns))
+    })?;
+    game.start_timer();
+    game.emit(&GameEvent::TurnStarted { turn, player });
+    Ok(Player::new(player as u64))
+}
+
+host_fn! {
+    /// starts the turn of the next active player and returns them
+    #[name = "nextPlayer"]
+    pub fn next_player(ctx: &mut HostCtx) -> Result<Player, HostError> {
+        start_turn(ctx, 1)
+    }
+}
+
+host_fn! {
+    /// starts the turn of the previous player, going against the direction of play
+    #[name = "prevPlayer"]
+    pub fn prev_player(ctx: &mut HostCtx) -> Result<Player, HostError> {
+        start_turn(ctx, -1)
+    }
+}
+
+host_fn! {
+    /// skips the turns of the next `amount` players, 1 if no amount is passed
+    #[name = "skipPlayers"]
+    pub fn skip_players(ctx: &mut HostCtx, ..amount: usize) -> Result<(), HostError> {
+        let amount = match amount.as_slice() {
+            [] => 1,
+            [amount] => *amount,
+            _ => return Err(HostError::new("expected at most 1 amount")),
+        };
+        game(ctx)?.with_state(|state| move_turn(state, amount as isize).map(|_| ()))
+    }
+}
+
+host_fn! {
+    /// reverses the direction of play, returns whether the order is reversed now
+    #[name = "reverseOrder"]
+    pub fn reverse_order(ctx: &mut HostCtx) -> Result<bool, HostError> {
+        game(ctx)?.with_state(|state| {
+            state.reversed = !state.reversed;
+            Ok(state.reversed)
+        })
+    }
+}
+
+host_fn! {
+    /// starts the turn of the passed player regardless of the order of play
+    #[name = "setCurrentPlayer"]
+    pub fn set_current_player(ctx: &mut HostCtx, player: Player) -> Result<(), HostError> {
+        let game = game(ctx)?;
+        let (player, turn) = game.with_state(|state| {
+            let player = check_player(state, player)?.idx() as usize;
+            if !state.players[player].active {
+                return Err(HostError::new(format!(
+                    "{} isn't active",
+                    state.players[player].name
+                )));
+            }
+            state.curr_player = player;
+            state.turns += 1;
+            Ok((player, state.turns))
+        })?;
+        game.start_timer();
+        game.emit(&GameEvent::TurnStarted { turn, player });
+        Ok(())
+    }
+}
+
+host_fn! {
+    /// ends the game with the passed player as its winner or as a draw if no player is passed,
+    /// the script doesn't continue past this call
+    #[name = "endGame"]
+    pub fn end_game(ctx: &mut HostCtx, ..winner: Player) -> Result<(), HostError> {
+        let game = game(ctx)?;
+        game.with_state(|state| {
+            state.winner = match winner.as_slice() {
+                [] => None,
+                [winner] => Some(check_player(state, *winner)?.idx() as usize),
+                _ => return Err(HostError::new("expected at most 1 winner")),
+            };
+            Ok(())
+        })?;
+        // the session stops executing the script once it notices this
+        game.finished.store(true, Ordering::Release);
+        Ok(())
+    }
+}
+
+host_fn! {
+    /// removes the player from the order of play, once only one active player is left
+    /// the game ends with them as its winner
+    #[name = "eliminatePlayer"]
+    pub fn eliminate_player(ctx: &mut HostCtx, player: Player) -> Result<(), HostError> {
+        let game = game(ctx)?;
+        let ended = game.with_state(|state| {
+            let player = check_player(state, player)?;
+            state.players[player.idx() as usize].active = false;
+            let mut active = state
+                .players
+                .iter()
+                .enumerate()
+                .filter(|(_, player)| player.active);
+            if let (Some((winner, _)), None) = (active.next(), active.next()) {
+                state.winner = Some(winner);
+                return Ok(true);
+            }
+            Ok(false)
+        })?;
+        if ended {
+            game.finished.store(true, Ordering::Release);
         }
+        Ok(())
+    }
+}
+
+host_fn! {
+    /// lets an eliminated player take part in the order of play again
+    #[name = "activatePlayer"]
+    pub fn activate_player(ctx: &mut HostCtx, player: Player) -> Result<(), HostError> {
+        game(ctx)?.with_state(|state| {
+            let player = check_player(state, player)?;
+            state.players[player.idx() as usize].active = true;
+            Ok(())
+        })
     }
-    let amount = if !args.is_empty() { args[0].get_num().unwrap() } else { 1 };
-    let mut ret = vec![];
-    let mut indices = vec![];
-    for _ in 0..amount {
-        
+}
+
+host_fn! {
+    /// whether the player is connected to the host, only players of networked games can
+    /// lose their connection
+    #[name = "isConnected"]
+    pub fn is_connected(ctx: &mut HostCtx, player: Player) -> Result<bool, HostError> {
+        let game = game(ctx)?;
+        let state = game.state();
+        let player = check_player(&state, player)?;
+        Ok(!state.players[player.idx() as usize].disconnected)
+    }
+}
+
+host_fn! {
+    /// ends the current round, which calls the script's `onRoundEnd(round)` if it has one,
+    /// and starts the next one, returns the number of the new round
+    #[name = "startRound"]
+    pub fn start_round(ctx: &mut HostCtx) -> Result<usize, HostError> {
+        let game = game(ctx)?;
+        let round = game.with_state(|state| {
+            state.round += 1;
+            Ok(state.round)
+        })?;
+        if round > 1 {
+            game.trigger(Hook::RoundEnd { round: round - 1 });
+        }
+        game.emit(&GameEvent::RoundStarted { round });
+        Ok(round)
+    }
+}
+
+host_fn! {
+    /// returns the number of the current round, 0 if no round was started yet
+    #[name = "roundNumber"]
+    pub fn round_number(ctx: &mut HostCtx) -> Result<usize, HostError> {
+        Ok(game(ctx)?.state().round)
+    }
+}
+
+host_fn! {
+    /// the number of players who are still in the game
+    #[name = "playerCount"]
+    pub fn player_cnt(ctx: &mut HostCtx) -> Result<usize, HostError> {
+        let mut players = 0;
+        for player in game(ctx)?.state().players.iter() {
+            if player.active {
+                players += 1;
+            }
+        }
+        Ok(players)
+    }
+}
+
+host_fn! {
+    /// returns the name of the passed player or the current one if no player is passed
+    #[name = "playerName"]
+    pub fn player_name(ctx: &mut HostCtx, ..player: Player) -> Result<String, HostError> {
+        let game = game(ctx)?;
+        let state = game.state();
+        let player = match player.as_slice() {
+            [] => Player::new(state.curr_player as u64),
+            [player] => check_player(&state, *player)?,
+            _ => return Err(HostError::new("expected at most 1 player")),
+        };
+        Ok(state.players[player.idx() as usize].name.clone())
+    }
+}
+
+host_fn! {
+    /// creates an inventory with `slots` slots everyone can see
+    #[name = "createInvGlobal"]
+    #[group = "inventory"]
+    pub fn create_inv_global(ctx: &mut HostCtx, slots: f64) -> Result<CardInventoryRef, HostError> {
+        let slots = slot_count(slots)?;
+        game(ctx)?.with_state(|state| {
+            state.inventories.push(CardInventory {
+                slots,
+                vis: None,
+                cards: vec![],
+            });
+            Ok(CardInventoryRef((state.inventories.len() - 1) as u64))
+        })
+    }
+}
+
+host_fn! {
+    /// creates an inventory which is only visible to the passed players
+    #[name = "createInvRestricted"]
+    #[group = "inventory"]
+    pub fn create_inv_restricted(
+        ctx: &mut HostCtx,
+        slots: f64,
+        ..players: Player
+    ) -> Result<CardInventoryRef, HostError> {
+        let slots = slot_count(slots)?;
+        game(ctx)?.with_state(|state| {
+            for player in players.iter() {
+                check_player(state, *player)?;
+            }
+            state.inventories.push(CardInventory {
+                slots,
+                vis: Some(players),
+                cards: vec![],
+            });
+            Ok(CardInventoryRef((state.inventories.len() - 1) as u64))
+        })
+    }
+}
+
+/// stores a meta value either for a player (`player, name, val`)
+/// or for the whole game (`name, val`)
+pub fn store_meta(ctx: &mut HostCtx, args: &[RtRef]) -> Result<Option<RtRef>, HostError> {
+    if args.len() > 3 || args.len() < 2 {
+        return Err(HostError::new(format!(
+            "expected 2 or 3 arguments, but got {}",
+            args.len()
+        )));
+    }
+    let name_idx = args.len() - 2;
+    let meta_name = args[name_idx]
+        .get_string()
+        .ok_or_else(|| HostError::invalid_arg(name_idx, RtType::String))?
+        .clone();
+    // the value is copied as the original is owned by the script
+    let meta_val = RtValue::from_ref(args[name_idx + 1]);
+    game(ctx)?.with_state(|state| {
+        if args.len() == 3 {
+            let player = player_arg(state, args, 0)?;
+            state.players[player.idx() as usize]
+                .meta
+                .insert(meta_name, meta_val);
+        } else {
+            state.meta.insert(meta_name, meta_val);
+        }
+        Ok(None)
+    })
+}
+
+/// loads a meta value either of a player (`player, name`) or of the whole game (`name`)
+pub fn load_meta(ctx: &mut HostCtx, args: &[RtRef]) -> Result<Option<RtRef>, HostError> {
+    if args.len() > 2 || args.is_empty() {
+        return Err(HostError::new(format!(
+            "expected 1 or 2 arguments, but got {}",
+            args.len()
+        )));
+    }
+    let name_idx = args.len() - 1;
+    let meta_name = args[name_idx]
+        .get_string()
+        .ok_or_else(|| HostError::invalid_arg(name_idx, RtType::String))?;
+    let game = game(ctx)?;
+    let state = game.state();
+    let meta = if args.len() == 2 {
+        let player = player_arg(&state, args, 0)?;
+        &state.players[player.idx() as usize].meta
+    } else {
+        &state.meta
+    };
+    Ok(meta.get(meta_name).map(|val| val.clone().into_ref()))
+}
+
+host_fn! {
+    /// draws the top card of the draw stack into the inventory and returns it
+    #[name = "drawCard"]
+    #[group = "inventory"]
+    pub fn draw_card(ctx: &mut HostCtx, inv: CardInventoryRef) -> Result<CardVal, HostError> {
+        game(ctx)?.with_state(|state| draw(state, inv))
+    }
+}
+
+host_fn! {
+    /// draws `amount` cards into the inventory and returns them,
+    /// if there aren't enough cards or slots, it fails without drawing any card
+    #[name = "drawCards"]
+    #[group = "inventory"]
+    pub fn draw_cards(
+        ctx: &mut HostCtx,
+        inv: CardInventoryRef,
+        amount: usize
+    ) -> Result<Vec<CardVal>, HostError> {
+        game(ctx)?.with_state(|state| {
+            (0..amount).map(|_| draw(state, inv)).collect()
+        })
+    }
+}
+
+host_fn! {
+    /// shuffles the draw stack
+    #[name = "shuffleDrawStack"]
+    #[group = "rng"]
+    #[group = "inventory"]
+    pub fn shuffle_draw_stack(ctx: &mut HostCtx) -> Result<(), HostError> {
+        game(ctx)?.with_state(|state| {
+            ctx.rng().shuffle(&mut state.draw_stack);
+            Ok(())
+        })
     }
 }
 
-pub fn player_play(args: Vec<RtRef>) -> Option<RtRef> {
-    let player = args[0].get_player().unwrap();
-    let card_check_func = args[1].get_func_idx().unwrap();
-    let draw_invs = args.into_iter().skip(2).collect::<Vec<_>>();
-    todo!()
+host_fn! {
+    /// shuffles the cards of an inventory, e.g. a discard pile before it's recycled
+    #[name = "shuffleInv"]
+    #[group = "rng"]
+    #[group = "inventory"]
+    pub fn shuffle_inv(ctx: &mut HostCtx, inv: CardInventoryRef) -> Result<(), HostError> {
+        game(ctx)?.with_state(|state| {
+            ctx.rng().shuffle(&mut inventory_mut(state, inv)?.cards);
+            Ok(())
+        })
+    }
+}
+
+host_fn! {
+    /// makes `inv` the inventory the player's cards are dealt into
+    #[name = "setHand"]
+    #[group = "inventory"]
+    pub fn set_hand(
+        ctx: &mut HostCtx,
+        player: Player,
+        inv: CardInventoryRef
+    ) -> Result<(), HostError> {
+        game(ctx)?.with_state(|state| {
+            let player = check_player(state, player)?;
+            inventory_mut(state, inv)?;
+            state.players[player.idx() as usize].hand = Some(inv.0);
+            Ok(())
+        })
+    }
+}
+
+host_fn! {
+    /// deals `per_player` cards from the draw stack into the hand of every active player,
+    /// one card at a time in player order
+    #[name = "dealCards"]
+    #[group = "inventory"]
+    pub fn deal_cards(ctx: &mut HostCtx, per_player: usize) -> Result<(), HostError> {
+        game(ctx)?.with_state(|state| {
+            let mut hands = vec![];
+            for player in state.players.iter().filter(|player| player.active) {
+                let hand = player.hand.ok_or_else(|| {
+                    HostError::new(format!("player {} doesn't have a hand", player.name))
+                })?;
+                hands.push(CardInventoryRef(hand));
+            }
+            for _ in 0..per_player {
+                for hand in hands.iter() {
+                    draw(state, *hand)?;
+                }
+            }
+            Ok(())
+        })
+    }
+}
+
+host_fn! {
+    /// moves the card from one inventory to another
+    #[name = "moveCard"]
+    #[group = "inventory"]
+    pub fn move_card(
+        ctx: &mut HostCtx,
+        from: CardInventoryRef,
+        to: CardInventoryRef,
+        card: CardVal
+    ) -> Result<(), HostError> {
+        game(ctx)?.with_state(|state| transfer(state, from, to, card))
+    }
+}
+
+host_fn! {
+    /// puts the cards into the inventory, taking them from the draw stack, another inventory
+    /// or the top of the discard pile, in that order
+    #[name = "giveCards"]
+    #[group = "inventory"]
+    pub fn give_cards(
+        ctx: &mut HostCtx,
+        inv: CardInventoryRef,
+        cards: Vec<CardVal>
+    ) -> Result<(), HostError> {
+        let game = game(ctx)?;
+        let card_cnt = game.game.cards.len();
+        game.with_state(|state| {
+            for card in cards {
+                if card.idx() as usize >= card_cnt {
+                    return Err(HostError::new(format!("card {} doesn't exist", card.idx())));
+                }
+                let from = take(state, card, inv)?;
+                put(state, Some(from), inv, card)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+host_fn! {
+    /// moves all cards from the inventory `from` into the inventory `to`,
+    /// if they don't all fit, no card is moved
+    #[name = "transferAll"]
+    #[group = "inventory"]
+    pub fn transfer_all(
+        ctx: &mut HostCtx,
+        from: CardInventoryRef,
+        to: CardInventoryRef
+    ) -> Result<(), HostError> {
+        game(ctx)?.with_state(|state| {
+            inventory_mut(state, to)?;
+            let cards = std::mem::take(&mut inventory_mut(state, from)?.cards);
+            for card in cards {
+                put(state, Some(Zone::Inventory(from.0 as usize)), to, card)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+host_fn! {
+    /// exchanges the cards in the hands of the two players,
+    /// fails without changing any hand if the cards don't fit
+    #[name = "swapHands"]
+    #[group = "inventory"]
+    pub fn swap_hands(ctx: &mut HostCtx, first: Player, second: Player) -> Result<(), HostError> {
+        game(ctx)?.with_state(|state| {
+            let first = hand(state, first)?;
+            let second = hand(state, second)?;
+            let first_cards = std::mem::take(&mut inventory_mut(state, first)?.cards);
+            let second_cards = std::mem::take(&mut inventory_mut(state, second)?.cards);
+            for card in second_cards {
+                put(state, Some(Zone::Inventory(second.0 as usize)), first, card)?;
+            }
+            for card in first_cards {
+                put(state, Some(Zone::Inventory(first.0 as usize)), second, card)?;
+            }
+            Ok(())
+        })
+    }
+}
+
+host_fn! {
+    /// moves the card from the inventory holding it onto the discard pile
+    #[name = "discard"]
+    #[group = "inventory"]
+    pub fn discard(ctx: &mut HostCtx, card: CardVal) -> Result<(), HostError> {
+        game(ctx)?.with_state(|state| {
+            let pos = state.inventories.iter().enumerate().find_map(|(inv, val)| {
+                val.cards
+                    .iter()
+                    .position(|val| *val == card)
+                    .map(|pos| (inv, pos))
+            });
+            let Some((inv, pos)) = pos else {
+                return Err(HostError::new(format!(
+                    "card {} isn't in any inventory",
+                    card.idx()
+                )));
+            };
+            state.inventories[inv].cards.remove(pos);
+            state.discard_pile.push(card.idx() as usize);
+            state.pending.push(GameEvent::CardMoved {
+                card: card.idx() as usize,
+                from: Some(Zone::Inventory(inv)),
+                to: Zone::DiscardPile,
+            });
+            Ok(())
+        })
+    }
+}
+
+host_fn! {
+    /// returns the discarded cards, the last one is on top
+    #[name = "discardPile"]
+    #[group = "inventory"]
+    pub fn discard_pile(ctx: &mut HostCtx) -> Result<Vec<CardVal>, HostError> {
+        Ok(game(ctx)?
+            .state()
+            .discard_pile
+            .iter()
+            .map(|card| CardVal::new(*card as u64))
+            .collect())
+    }
+}
+
+host_fn! {
+    /// returns the top `n` cards of the stack, the top card first, without taking them.
+    /// `stack` is either `"draw"` for the draw stack, `"discard"` for the discard pile
+    /// or an inventory whose last cards are on top. The cards are only revealed to the
+    /// script, not to any player.
+    #[name = "peekTop"]
+    #[group = "inventory"]
+    pub fn peek_top(ctx: &mut HostCtx, stack: RtRef, n: usize) -> Result<Vec<CardVal>, HostError> {
+        let game = game(ctx)?;
+        let state = game.state();
+        let cards = if let Some(inv) = stack.get_inventory() {
+            inventory(&state, inv)?
+                .cards
+                .iter()
+                .map(|card| card.idx() as usize)
+                .collect()
+        } else {
+            match stack.get_string().map(String::as_str) {
+                Some("draw") => state.draw_stack.clone(),
+                Some("discard") => state.discard_pile.clone(),
+                _ => {
+                    return Err(HostError::new(
+                        "expected `\"draw\"`, `\"discard\"` or an inventory as the stack",
+                    ))
+                }
+            }
+        };
+        Ok(cards
+            .into_iter()
+            .rev()
+            .take(n)
+            .map(|card| CardVal::new(card as u64))
+            .collect())
+    }
+}
+
+host_fn! {
+    /// moves the top card of the discard pile into the inventory and returns it
+    #[name = "takeFromDiscard"]
+    #[group = "inventory"]
+    pub fn take_from_discard(
+        ctx: &mut HostCtx,
+        inv: CardInventoryRef
+    ) -> Result<CardVal, HostError> {
+        game(ctx)?.with_state(|state| {
+            let card = state
+                .discard_pile
+                .pop()
+                .ok_or_else(|| HostError::new("the discard pile is empty"))?;
+            let card = CardVal::new(card as u64);
+            put(state, Some(Zone::DiscardPile), inv, card)?;
+            Ok(card)
+        })
+    }
+}
+
+host_fn! {
+    /// returns the number of cards in the inventory
+    #[name = "invSize"]
+    #[group = "inventory"]
+    pub fn inv_size(ctx: &mut HostCtx, inv: CardInventoryRef) -> Result<usize, HostError> {
+        Ok(inventory(&game(ctx)?.state(), inv)?.cards.len())
+    }
+}
+
+host_fn! {
+    /// returns the number of cards the inventory can hold
+    #[name = "invSlots"]
+    #[group = "inventory"]
+    pub fn inv_slots(ctx: &mut HostCtx, inv: CardInventoryRef) -> Result<usize, HostError> {
+        Ok(inventory(&game(ctx)?.state(), inv)?.slots as usize)
+    }
+}
+
+host_fn! {
+    /// returns the number of cards that still fit into the inventory
+    #[name = "invFreeSlots"]
+    #[group = "inventory"]
+    pub fn inv_free_slots(ctx: &mut HostCtx, inv: CardInventoryRef) -> Result<usize, HostError> {
+        let game = game(ctx)?;
+        let state = game.state();
+        let inv = inventory(&state, inv)?;
+        Ok((inv.slots as usize).saturating_sub(inv.cards.len()))
+    }
+}
+
+host_fn! {
+    /// returns whether the inventory holds a card called `card_name`
+    #[name = "invContains"]
+    #[group = "inventory"]
+    pub fn inv_contains(
+        ctx: &mut HostCtx,
+        inv: CardInventoryRef,
+        card_name: String
+    ) -> Result<bool, HostError> {
+        let game = game(ctx)?;
+        let state = game.state();
+        Ok(inventory(&state, inv)?
+            .cards
+            .iter()
+            .any(|card| game.game.card(*card).is_some_and(|card| card.name == card_name)))
+    }
+}
+
+host_fn! {
+    /// returns the number of cards in the inventory which are either called `card`,
+    /// if a name is passed, or have the ordinal `card`, if a number is passed
+    #[name = "countCards"]
+    #[group = "inventory"]
+    pub fn count_cards(
+        ctx: &mut HostCtx,
+        inv: CardInventoryRef,
+        card: RtRef
+    ) -> Result<usize, HostError> {
+        let game = game(ctx)?;
+        let state = game.state();
+        let (name, ord) = (card.get_string(), card.get_num());
+        if name.is_none() && ord.is_none() {
+            return Err(HostError::new("expected a card name or ordinal"));
+        }
+        let mut cnt = 0;
+        for card in inventory(&state, inv)?.cards.iter() {
+            let card = card_template(&game.game, *card)?;
+            let matches = match name {
+                Some(name) => card.name == *name,
+                None => Some(card.ord as u64) == ord,
+            };
+            if matches {
+                cnt += 1;
+            }
+        }
+        Ok(cnt)
+    }
+}
+
+host_fn! {
+    /// sorts the cards in the inventory by their ordinal if `by` is `"ord"`, by their name if
+    /// it's `"name"` or by the metadata value `by` otherwise, cards without the value come
+    /// last. Cards which compare equal keep their order, `descending` reverses the order.
+    #[name = "sortInventory"]
+    #[group = "inventory"]
+    pub fn sort_inventory(
+        ctx: &mut HostCtx,
+        inv: CardInventoryRef,
+        by: String,
+        ..descending: bool
+    ) -> Result<(), HostError> {
+        let game = game(ctx)?;
+        let descending = match descending.as_slice() {
+            [] => false,
+            [descending] => *descending,
+            _ => return Err(HostError::new("expected at most 1 order")),
+        };
+        game.with_state(|state| {
+            let inv = inventory_mut(state, inv)?;
+            let mut cards = inv
+                .cards
+                .iter()
+                .map(|card| Ok((*card, card_template(&game.game, *card)?)))
+                .collect::<Result<Vec<_>, HostError>>()?;
+            // `sort_by` is stable
+            cards.sort_by(|(_, a), (_, b)| {
+                let (ord, both) = match by.as_str() {
+                    "ord" => (a.ord.cmp(&b.ord), true),
+                    "name" => (a.name.cmp(&b.name), true),
+                    key => {
+                        let (a, b) = (a.metadata.get(key), b.metadata.get(key));
+                        (cmp_meta(a, b), a.is_some() && b.is_some())
+                    }
+                };
+                // cards without the value stay last in either order
+                if descending && both {
+                    ord.reverse()
+                } else {
+                    ord
+                }
+            });
+            inv.cards = cards.into_iter().map(|(card, _)| card).collect();
+            Ok(())
+        })
+    }
+}
+
+host_fn! {
+    /// returns a list of the cards in the inventory
+    #[name = "cardsIn"]
+    #[group = "inventory"]
+    pub fn cards_in(ctx: &mut HostCtx, inv: CardInventoryRef) -> Result<Vec<CardVal>, HostError> {
+        Ok(inventory(&game(ctx)?.state(), inv)?.cards.clone())
+    }
+}
+
+host_fn! {
+    /// returns the card's metadata value `key`, if it has one
+    #[name = "cardMeta"]
+    pub fn card_meta(
+        ctx: &mut HostCtx,
+        card: CardVal,
+        key: String
+    ) -> Result<Option<String>, HostError> {
+        Ok(card_template(&game(ctx)?.game, card)?.metadata.get(&key).cloned())
+    }
+}
+
+host_fn! {
+    /// returns all cards of the game whose metadata value `key` is `value`
+    #[name = "cardsWithMeta"]
+    pub fn cards_with_meta(
+        ctx: &mut HostCtx,
+        key: String,
+        value: String
+    ) -> Result<Vec<CardVal>, HostError> {
+        Ok(game(ctx)?
+            .game
+            .cards
+            .iter()
+            .enumerate()
+            .filter(|(_, card)| card.metadata.get(&key) == Some(&value))
+            .map(|(idx, _)| CardVal::new(idx as u64))
+            .collect())
+    }
+}
+
+host_fn! {
+    /// returns the name of the card
+    #[name = "cardName"]
+    pub fn card_name(ctx: &mut HostCtx, card: CardVal) -> Result<String, HostError> {
+        Ok(card_template(&game(ctx)?.game, card)?.name.clone())
+    }
+}
+
+host_fn! {
+    /// returns the ordinal of the card, which is used to rank cards
+    #[name = "cardOrd"]
+    pub fn card_ord(ctx: &mut HostCtx, card: CardVal) -> Result<usize, HostError> {
+        Ok(card_template(&game(ctx)?.game, card)?.ord)
+    }
+}
+
+host_fn! {
+    /// returns the suit of the card, if it has one
+    #[name = "cardSuit"]
+    pub fn card_suit(ctx: &mut HostCtx, card: CardVal) -> Result<Option<String>, HostError> {
+        Ok(card_template(&game(ctx)?.game, card)?.suit.clone())
+    }
+}
+
+host_fn! {
+    /// returns the rank of the card, if it has one
+    #[name = "cardRank"]
+    pub fn card_rank(ctx: &mut HostCtx, card: CardVal) -> Result<Option<String>, HostError> {
+        Ok(card_template(&game(ctx)?.game, card)?.rank.clone())
+    }
+}
+
+host_fn! {
+    /// returns whether the card is tagged with `tag`
+    #[name = "hasTag"]
+    pub fn has_tag(ctx: &mut HostCtx, card: CardVal, tag: String) -> Result<bool, HostError> {
+        Ok(card_template(&game(ctx)?.game, card)?.tags.contains(&tag))
+    }
+}
+
+host_fn! {
+    /// returns the cards in the inventory which are tagged with `tag`
+    #[name = "cardsWithTag"]
+    pub fn cards_with_tag(
+        ctx: &mut HostCtx,
+        inv: CardInventoryRef,
+        tag: String
+    ) -> Result<Vec<CardVal>, HostError> {
+        let game = game(ctx)?;
+        let state = game.state();
+        let mut cards = vec![];
+        for card in inventory(&state, inv)?.cards.iter() {
+            if card_template(&game.game, *card)?.tags.contains(&tag) {
+                cards.push(*card);
+            }
+        }
+        Ok(cards)
+    }
+}
+
+host_fn! {
+    /// returns the card called `name`, fails if there isn't exactly one such card
+    #[name = "cardByName"]
+    pub fn card_by_name(ctx: &mut HostCtx, name: String) -> Result<CardVal, HostError> {
+        let game = game(ctx)?;
+        let mut cards = game
+            .game
+            .cards
+            .iter()
+            .enumerate()
+            .filter(|(_, card)| card.name == name)
+            .map(|(idx, _)| CardVal::new(idx as u64));
+        match (cards.next(), cards.next()) {
+            (Some(card), None) => Ok(card),
+            (None, _) => Err(HostError::new(format!("there is no card called {name}"))),
+            (Some(_), Some(_)) => Err(HostError::new(format!(
+                "there are multiple cards called {name}"
+            ))),
+        }
+    }
+}
+
+host_fn! {
+    /// makes the player a member of `team`, teams are identified by arbitrary numbers
+    #[name = "assignTeam"]
+    pub fn assign_team(ctx: &mut HostCtx, player: Player, team: usize) -> Result<(), HostError> {
+        game(ctx)?.with_state(|state| {
+            let player = check_player(state, player)?;
+            state.players[player.idx() as usize].team = Some(team);
+            Ok(())
+        })
+    }
+}
+
+host_fn! {
+    /// returns the team of the player, if it was assigned to one
+    #[name = "teamOf"]
+    pub fn team_of(ctx: &mut HostCtx, player: Player) -> Result<Option<usize>, HostError> {
+        let game = game(ctx)?;
+        let state = game.state();
+        let player = check_player(&state, player)?;
+        Ok(state.players[player.idx() as usize].team)
+    }
+}
+
+host_fn! {
+    /// adds the passed points to the score of the team, if any, and returns its score
+    #[name = "teamScore"]
+    pub fn team_score(ctx: &mut HostCtx, team: usize, ..points: f64) -> Result<f64, HostError> {
+        game(ctx)?.with_state(|state| {
+            let score = state.team_scores.entry(team).or_insert(0.0);
+            *score += points.iter().sum::<f64>();
+            Ok(*score)
+        })
+    }
+}
+
+host_fn! {
+    /// lets the current player select `cnt` of the cards they can see or, if inventories are
+    /// passed, of the cards in them and returns the selected cards.
+    /// `force_different` requires the cards to have different names,
+    /// `allow_partial` allows selecting fewer than `cnt` cards
+    #[name = "selectCards"]
+    #[group = "inventory"]
+    #[group = "rng"]
+    pub fn select_cards(
+        ctx: &mut HostCtx,
+        cnt: usize,
+        force_different: bool,
+        allow_partial: bool,
+        ..invs: CardInventoryRef
+    ) -> Result<Vec<CardVal>, HostError> {
+        let game = game(ctx)?;
+        let (player, cards) = {
+            let state = game.state();
+            let player = state.curr_player;
+            let mut cards = vec![];
+            if invs.is_empty() {
+                for inv in state
+                    .inventories
+                    .iter()
+                    .filter(|inv| inv.visible_to(Player::new(player as u64)))
+                {
+                    cards.extend_from_slice(&inv.cards);
+                }
+            } else {
+                for inv in invs {
+                    cards.extend_from_slice(&inventory(&state, inv)?.cards);
+                }
+            }
+            (player, cards)
+        };
+        if !allow_partial && cards.len() < cnt {
+            return Err(HostError::new(format!(
+                "can't select {} cards out of {}",
+                cnt,
+                cards.len()
+            )));
+        }
+        let query = Query::SelectCards {
+            player,
+            cards: cards.iter().map(|card| card.idx() as usize).collect(),
+            cnt,
+            force_different,
+            allow_partial,
+        };
+        let selected = ask(ctx, &game, &query)?;
+        Ok(selected.into_iter().map(|pos| cards[pos]).collect())
+    }
+}
+
+host_fn! {
+    /// returns up to `cnt` of the other active players, fails if there are fewer unless
+    /// `allow_partial` is set. `src` determines which players are chosen:
+    /// `random`: random players,
+    /// `lastRound`: the players who played before the current one, the latest first,
+    /// `nextRound`: the players who play after the current one, the next first,
+    /// `select`: the current player selects them
+    #[name = "selectPlayers"]
+    #[group = "rng"]
+    pub fn select_players(
+        ctx: &mut HostCtx,
+        cnt: usize,
+        src: String,
+        allow_partial: bool
+    ) -> Result<Vec<Player>, HostError> {
+        let game = game(ctx)?;
+        let (player, mut players) = {
+            let state = game.state();
+            let forward = src != "lastRound";
+            (state.curr_player, others_in_order(&state, forward))
+        };
+        if !allow_partial && players.len() < cnt {
+            return Err(HostError::new(format!(
+                "can't select {} players out of {}",
+                cnt,
+                players.len()
+            )));
+        }
+        match src.as_str() {
+            "random" => {
+                ctx.rng().shuffle(&mut players);
+                players.truncate(cnt);
+            }
+            "lastRound" | "nextRound" => players.truncate(cnt),
+            "select" => {
+                let query = Query::SelectPlayers {
+                    player,
+                    players: players.clone(),
+                    cnt,
+                    allow_partial,
+                };
+                players = ask(ctx, &game, &query)?
+                    .into_iter()
+                    .map(|pos| players[pos])
+                    .collect();
+            }
+            _ => {
+                return Err(HostError::new(format!(
+                    "unknown player source `{}`, expected `random`, `lastRound`, \
+                     `nextRound` or `select`",
+                    src
+                )))
+            }
+        }
+        Ok(players
+            .into_iter()
+            .map(|player| Player::new(player as u64))
+            .collect())
+    }
+}
+
+host_fn! {
+    /// lets the player pick one of the options and returns its index
+    #[name = "promptChoice"]
+    #[group = "rng"]
+    pub fn prompt_choice(
+        ctx: &mut HostCtx,
+        player: Player,
+        ..options: String
+    ) -> Result<usize, HostError> {
+        let game = game(ctx)?;
+        let player = check_player(&game.state(), player)?.idx() as usize;
+        if options.is_empty() {
+            return Err(HostError::new("expected at least 1 option"));
+        }
+        let query = Query::Choice { player, options };
+        Ok(ask(ctx, &game, &query)?[0])
+    }
+}
+
+host_fn! {
+    /// asks the player a yes/no question and returns whether they agreed
+    #[name = "confirm"]
+    #[group = "rng"]
+    pub fn confirm(ctx: &mut HostCtx, player: Player, question: String) -> Result<bool, HostError> {
+        let game = game(ctx)?;
+        let player = check_player(&game.state(), player)?.idx() as usize;
+        let query = Query::Confirm { player, question };
+        Ok(ask(ctx, &game, &query)?[0] == 1)
+    }
+}
+
+host_fn! {
+    /// shows the card to the passed players or to everyone if no players are passed,
+    /// even if it's in an inventory they can't see
+    #[name = "revealCard"]
+    #[group = "inventory"]
+    pub fn reveal_card(
+        ctx: &mut HostCtx,
+        card: CardVal,
+        ..players: Player
+    ) -> Result<(), HostError> {
+        let game = game(ctx)?;
+        card_template(&game.game, card)?;
+        let players = {
+            let state = game.state();
+            if players.is_empty() {
+                (0..state.players.len()).collect()
+            } else {
+                players
+                    .into_iter()
+                    .map(|player| check_player(&state, player).map(|player| player.idx() as usize))
+                    .collect::<Result<Vec<_>, _>>()?
+            }
+        };
+        game.emit(&GameEvent::CardRevealed {
+            card: card.idx() as usize,
+            players,
+        });
+        Ok(())
+    }
+}
+
+host_fn! {
+    /// returns the rating the player called `name` has in this game, based on all recorded
+    /// games. The rating is recorded, so replays see the same value.
+    #[name = "playerRating"]
+    #[group = "io"]
+    pub fn player_rating(ctx: &mut HostCtx, name: String) -> Result<f64, HostError> {
+        let game = game(ctx)?;
+        let player = game.state().curr_player;
+        let rating = game.decide(player, || {
+            #[cfg(feature = "fs")]
+            let rating = Stats::load()
+                .map_or(DEFAULT_RATING, |stats| stats.rating(&game.game.name, &name));
+            #[cfg(not(feature = "fs"))]
+            let rating = DEFAULT_RATING;
+            Some(rating.to_string())
+        })?;
+        rating
+            .and_then(|rating| rating.parse::<f64>().ok())
+            .ok_or_else(|| HostError::new("the replay diverged, its rating isn't a number"))
+    }
+}
+
+host_fn! {
+    /// returns a random active player if no amount is passed,
+    /// otherwise a list of the requested amount of distinct active players
+    #[name = "randPlayers"]
+    #[group = "rng"]
+    pub fn rand_players(ctx: &mut HostCtx, ..amount: usize) -> Result<RtRef, HostError> {
+        let mut active = game(ctx)?
+            .state()
+            .players
+            .iter()
+            .enumerate()
+            .filter(|(_, player)| player.active)
+            .map(|(idx, _)| idx)
+            .collect::<Vec<_>>();
+        let amount = match amount.as_slice() {
+            [] => {
+                if active.is_empty() {
+                    return Err(HostError::new("there are no active players left"));
+                }
+                let player = active[ctx.rng().gen_range(active.len())];
+                return Ok(RtRef::player(Player::new(player as u64)));
+            }
+            [amount] => (*amount).min(active.len()),
+            _ => return Err(HostError::new("expected at most 1 amount")),
+        };
+        ctx.rng().shuffle(&mut active);
+        let players = active
+            .into_iter()
+            .take(amount)
+            .map(|idx| Player::new(idx as u64))
+            .collect::<Vec<_>>();
+        Ok(players.into_ref())
+    }
 }
@@ -0,0 +1,523 @@
+use std::{
+    mem,
+    sync::{atomic::Ordering, Arc, Mutex},
+};
+
+use engine::{
+    EngineBuilder, Function, HostCall, Lint, Program, Rng, RtValue, Vm, LANGUAGE_VERSION,
+};
+
+use crate::{
+    funcs::{host_functions, lint_rules, Capability},
+    game_ctx::{GameCtx, GameEvent, GameTemplate, Request, UndoPolicy},
+    query::Query,
+    replay::{Decision, Decisions, Replay},
+    save::SaveGame,
+};
+
+/// the maximum number of bytes the values of a game's script may occupy, no card game needs
+/// anywhere near as much, so scripts exceeding it are most likely broken or malicious
+const MEMORY_LIMIT: usize = 64 * 1024 * 1024;
+
+/// A game whose script was compiled, it can be played any number of times.
+pub struct Game {
+    template: Arc<GameTemplate>,
+    program: Program,
+}
+
+impl Game {
+    /// Compiles the game's script `src`, `funcs` are provided to the script in addition to
+    /// the game's host functions.
+    pub fn new(template: GameTemplate, src: &str, funcs: Vec<Function>) -> anyhow::Result<Self> {
+        if template.engine_version > LANGUAGE_VERSION {
+            return Err(anyhow::anyhow!(
+                "{} needs a newer engine, it was written for version {} of the script language \
+                 but this engine only knows version {}",
+                template.name,
+                template.engine_version,
+                LANGUAGE_VERSION
+            ));
+        }
+        let mut builder = lint_rules()
+            .into_iter()
+            .fold(EngineBuilder::new(), EngineBuilder::lint);
+        let mut all_funcs = host_functions();
+        all_funcs.extend(funcs);
+        if let Some(allowed) = &template.capabilities {
+            let builtins = engine::builtins();
+            // the functions the script gets to call, the passed ones replace the builtins
+            let callable = all_funcs.iter().chain(
+                builtins
+                    .iter()
+                    .filter(|builtin| !all_funcs.iter().any(|func| func.name == builtin.name)),
+            );
+            for func in callable {
+                let missing = func
+                    .groups
+                    .iter()
+                    .filter_map(|group| Capability::of(group))
+                    .find(|group| !allowed.contains(group));
+                if let Some(group) = missing {
+                    builder = builder.deny(
+                        func.name,
+                        format!(
+                            "{} doesn't declare the {} capability",
+                            template.name,
+                            group.name()
+                        ),
+                    );
+                }
+            }
+        }
+        let program = builder
+            .functions(all_funcs)
+            .memory_limit(MEMORY_LIMIT)
+            .compile(src)
+            .map_err(|err| {
+                // the diagnostics alone don't tell the author that the language changed
+                if template.engine_version < LANGUAGE_VERSION {
+                    err.context(format!(
+                        "{} was written for version {} of the script language, its script may \
+                         have to be updated to version {}",
+                        template.name, template.engine_version, LANGUAGE_VERSION
+                    ))
+                } else {
+                    err
+                }
+            })?;
+        Ok(Self {
+            template: Arc::new(template),
+            program,
+        })
+    }
+
+    /// Loads the game called `name` together with its cards and compiles its script.
+    #[cfg(feature = "fs")]
+    pub fn load(name: &str) -> anyhow::Result<Self> {
+        Self::load_with(name, vec![])
+    }
+
+    /// Like `load`, but `funcs` are provided to the script in addition to the game's
+    /// host functions, e.g. `silent_println` for games nobody is watching.
+    #[cfg(feature = "fs")]
+    pub fn load_with(name: &str, funcs: Vec<Function>) -> anyhow::Result<Self> {
+        let template = GameTemplate::load(name)?;
+        let src = std::fs::read_to_string(&template.code_path).map_err(|err| {
+            anyhow::anyhow!(
+                "The script {} of {} couldn't be read: {}",
+                template.code_path,
+                name,
+                err
+            )
+        })?;
+        Self::new(template, &src, funcs)
+    }
+
+    pub fn template(&self) -> &GameTemplate {
+        &self.template
+    }
+
+    /// The warnings about the game's script, see `engine::Lint`.
+    pub fn lints(&self) -> &[Lint] {
+        self.program.lints()
+    }
+
+    /// Starts a new session of the game with the passed players, which fails if the game
+    /// can't be played by that many players.
+    /// The game doesn't progress until the session is run.
+    pub fn start(&self, players: Vec<String>) -> anyhow::Result<Session> {
+        self.start_seeded(players, Rng::entropy_seed())
+    }
+
+    pub fn start_seeded(&self, players: Vec<String>, seed: u64) -> anyhow::Result<Session> {
+        let (min, max) = (self.template.min_players, self.template.max_players);
+        if players.len() < min || players.len() > max {
+            return Err(anyhow::anyhow!(
+                "{} is played by {} to {} players, not by {}",
+                self.template.name,
+                min,
+                max,
+                players.len()
+            ));
+        }
+        let ctx = GameCtx::new(self.template.clone(), players);
+        Ok(self.session(ctx, seed, None))
+    }
+
+    /// Starts a session which plays back the recorded game.
+    pub fn replay(&self, replay: &Replay) -> anyhow::Result<Session> {
+        if replay.game != self.template.name {
+            return Err(anyhow::anyhow!(
+                "The replay was recorded for {}, not for {}",
+                replay.game,
+                self.template.name
+            ));
+        }
+        let mut ctx = GameCtx::new(self.template.clone(), replay.players.clone());
+        ctx.decisions = Mutex::new(Decisions::Replay(replay.decisions.clone().into()));
+        Ok(self.session(ctx, replay.seed, Some(replay.decisions.clone())))
+    }
+
+    /// Continues a saved session, the decisions made before it was saved become part of
+    /// the replay of the resumed session.
+    pub fn resume(&self, save: &SaveGame) -> anyhow::Result<Session> {
+        if save.game != self.template.name {
+            return Err(anyhow::anyhow!(
+                "The save game belongs to {}, not to {}",
+                save.game,
+                self.template.name
+            ));
+        }
+        let mut ctx = GameCtx::restore(self.template.clone(), save.state.clone());
+        ctx.decisions = Mutex::new(Decisions::Record(save.decisions.clone()));
+        let mut session = self.session(ctx, save.seed, None);
+        session.vm.restore(save.vm.clone());
+        // the current player gets their time anew
+        session.ctx.start_timer();
+        Ok(session)
+    }
+
+    fn session(&self, ctx: GameCtx, seed: u64, replayed: Option<Vec<Decision>>) -> Session {
+        let ctx = Arc::new(ctx);
+        let mut vm = self.program.instantiate_seeded(seed);
+        vm.host_ctx().set_data(ctx.clone());
+        Session {
+            ctx,
+            vm,
+            seed,
+            replayed,
+            history: vec![],
+            undone: vec![],
+            autosave: None,
+            audit: None,
+        }
+    }
+}
+
+/// A single play-through of a game.
+pub struct Session {
+    ctx: Arc<GameCtx>,
+    vm: Vm,
+    seed: u64,
+    /// the decisions of the replay this session plays back
+    replayed: Option<Vec<Decision>>,
+    /// the starts of the turns which can be gone back to, the current turn is the last one
+    history: Vec<SaveGame>,
+    /// the starts of the turns which were undone, the most recently undone one is the last one
+    undone: Vec<SaveGame>,
+    autosave: Option<Box<dyn FnMut(SaveGame) + Send>>,
+    audit: Option<Box<dyn FnMut(HostCall) + Send>>,
+}
+
+impl Session {
+    /// The state of the game, it stays accessible from other threads while the session runs
+    /// and can be used to pause it.
+    pub fn ctx(&self) -> &Arc<GameCtx> {
+        &self.ctx
+    }
+
+    /// The seed the session's rng started with, starting the game with the same players
+    /// and this seed deals the same cards again.
+    pub fn seed(&self) -> u64 {
+        self.seed
+    }
+
+    /// Registers a callback which gets invoked on the thread running the session
+    /// whenever something happens in the game.
+    pub fn on_event(&self, listener: impl Fn(&GameCtx, &GameEvent) + Send + Sync + 'static) {
+        self.ctx.subscribe(listener);
+    }
+
+    /// Registers the callback which answers the decisions players have to make, it gets
+    /// invoked on the thread running the session. Without one, players decide at random.
+    /// Handlers return `None` if the player didn't answer before the `GameCtx::deadline`.
+    pub fn on_query(
+        &self,
+        handler: impl Fn(&GameCtx, &Query) -> Option<String> + Send + Sync + 'static,
+    ) {
+        self.ctx.set_query_handler(handler);
+    }
+
+    /// Registers a callback which receives a save game at the start of every turn, it gets
+    /// invoked on the thread running the session.
+    pub fn autosave(&mut self, autosave: impl FnMut(SaveGame) + Send + 'static) {
+        self.autosave = Some(Box::new(autosave));
+    }
+
+    /// Registers a callback which receives every host function call of the game's script
+    /// with its arguments and result, it gets invoked on the thread running the session.
+    pub fn audit(&mut self, audit: impl FnMut(HostCall) + Send + 'static) {
+        // the calls are only recorded if anyone reads them
+        self.vm.set_audit(true);
+        self.audit = Some(Box::new(audit));
+    }
+
+    /// Executes a single instruction of the game's script, returns whether there is more
+    /// to execute. This ignores whether the game is paused.
+    pub fn step(&mut self) -> anyhow::Result<bool> {
+        if self.ctx.finished.load(Ordering::Acquire) {
+            return Ok(false);
+        }
+        let turns = self.ctx.state().turns;
+        let res = self.vm.step().and_then(|more| {
+            if more {
+                self.run_hooks()?;
+            }
+            Ok(more)
+        });
+        let calls = self.vm.take_audit_log();
+        if let Some(audit) = &mut self.audit {
+            calls.into_iter().for_each(audit);
+        }
+        // the instruction which started a turn is done, so the new turn can be saved
+        if matches!(res, Ok(true)) && self.ctx.state().turns != turns {
+            self.turn_started();
+        }
+        match res {
+            // the script may have ended the game early
+            Ok(true) if !self.ctx.finished.load(Ordering::Acquire) => Ok(true),
+            Ok(_) => {
+                self.ctx.finished.store(true, Ordering::Release);
+                let winner = self.ctx.state().winner;
+                self.ctx.emit(&GameEvent::Finished { winner });
+                Ok(false)
+            }
+            Err(err) => {
+                self.ctx.finished.store(true, Ordering::Release);
+                Err(err)
+            }
+        }
+    }
+
+    fn turn_started(&mut self) {
+        let save = self.save();
+        // the undone turns can't be redone anymore once the game took a different course
+        self.undone.clear();
+        match self.ctx.game.undo {
+            UndoPolicy::Allowed => self.history.push(save.clone()),
+            UndoPolicy::Forbidden => {}
+            UndoPolicy::LastTurns(turns) => {
+                self.history.push(save.clone());
+                if self.history.len() > turns + 1 {
+                    self.history.remove(0);
+                }
+            }
+        }
+        if let Some(autosave) = &mut self.autosave {
+            autosave(save);
+        }
+    }
+
+    /// goes back to the start of the previous turn, returns that turn
+    fn undo(&mut self) -> Result<usize, String> {
+        if let UndoPolicy::Forbidden = self.ctx.game.undo {
+            return Err(format!(
+                "{} doesn't allow undoing turns",
+                self.ctx.game.name
+            ));
+        }
+        if self.replayed.is_some() {
+            return Err("replays can't be changed".to_string());
+        }
+        if self.history.len() < 2 {
+            return Err("there is no turn to go back to".to_string());
+        }
+        self.undone.push(self.history.pop().unwrap());
+        let save = self.history.last().unwrap().clone();
+        Ok(self.restore(save))
+    }
+
+    /// takes back the last undo, returns the turn it went forth to
+    fn redo(&mut self) -> Result<usize, String> {
+        let save = self
+            .undone
+            .pop()
+            .ok_or_else(|| "there is no undone turn".to_string())?;
+        self.history.push(save.clone());
+        Ok(self.restore(save))
+    }
+
+    /// continues the session from the save game, returns the turn it was saved at
+    fn restore(&mut self, save: SaveGame) -> usize {
+        let turn = save.state.turns;
+        self.ctx.set_state(save.state);
+        self.vm.restore(save.vm);
+        *self.ctx.decisions.lock().unwrap() = Decisions::Record(save.decisions);
+        self.ctx.start_timer();
+        self.ctx.emit(&GameEvent::Restored { turn });
+        turn
+    }
+
+    /// answers the requests the session received through its `GameControl`
+    fn handle(&mut self, requests: Vec<Request>) {
+        // nobody might be waiting for the results anymore
+        for request in requests {
+            match request {
+                Request::Save(reply) => {
+                    let _ = reply.send(self.save());
+                }
+                Request::Undo(reply) => {
+                    let _ = reply.send(self.undo());
+                }
+                Request::Redo(reply) => {
+                    let _ = reply.send(self.redo());
+                }
+            }
+        }
+    }
+
+    /// calls the script's functions for the hooks the last instruction triggered
+    fn run_hooks(&mut self) -> anyhow::Result<()> {
+        let hooks = mem::take(&mut *self.ctx.hooks.lock().unwrap());
+        for hook in hooks {
+            if !self.vm.has_fn(hook.name()) {
+                continue;
+            }
+            let args = hook
+                .args()
+                .into_iter()
+                .map(RtValue::into_ref)
+                .collect::<Vec<_>>();
+            let ret = self.vm.call(hook.name(), &args);
+            // the vm only received copies of the arguments
+            for arg in args {
+                self.vm.release(arg);
+            }
+            self.vm.release(ret?);
+        }
+        Ok(())
+    }
+
+    /// Captures the session, it can be continued later on using `Game::resume`.
+    /// Sessions running on another thread have to be saved through `GameControl::request_save`.
+    pub fn save(&self) -> SaveGame {
+        let decisions = match &*self.ctx.decisions.lock().unwrap() {
+            Decisions::Record(decisions) => decisions.clone(),
+            // only the decisions which were played back so far
+            Decisions::Replay(left) => {
+                let replayed = self.replayed.as_deref().unwrap_or_default();
+                replayed[..replayed.len() - left.len()].to_vec()
+            }
+        };
+        SaveGame {
+            game: self.ctx.game.name.clone(),
+            seed: self.seed,
+            state: self.ctx.snapshot(),
+            vm: self.vm.snapshot(),
+            decisions,
+        }
+    }
+
+    /// Plays the game to completion on the calling thread and returns its replay.
+    pub fn run(mut self) -> anyhow::Result<Replay> {
+        loop {
+            // instruction boundaries are safe points to suspend, save and restore the game at
+            let (requests, paused) = self.ctx.control.wait_while_paused();
+            // the players can't do anything while the game is paused
+            self.ctx.delay_deadline(paused);
+            if !requests.is_empty() {
+                self.handle(requests);
+                continue;
+            }
+            let more = self.step();
+            if !matches!(more, Ok(true)) {
+                // nobody can save the game anymore
+                self.ctx.control.cancel_requests();
+            }
+            if !more? {
+                break;
+            }
+        }
+
+        let decisions = match self.replayed {
+            Some(decisions) => decisions,
+            None => match &mut *self.ctx.decisions.lock().unwrap() {
+                Decisions::Record(decisions) => mem::take(decisions),
+                Decisions::Replay(_) => unreachable!(),
+            },
+        };
+        let players = self
+            .ctx
+            .state()
+            .players
+            .iter()
+            .map(|player| player.name.clone())
+            .collect::<Vec<_>>();
+        Ok(Replay {
+            game: self.ctx.game.name.clone(),
+            players,
+            seed: self.seed,
+            decisions,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use image::DynamicImage;
+
+    use super::Game;
+    use crate::{
+        funcs::Capability,
+        game_ctx::{GameTemplate, UndoPolicy},
+        replay::{Decision, Replay},
+    };
+
+    fn template(capabilities: Option<Vec<Capability>>) -> GameTemplate {
+        GameTemplate {
+            name: "Test".to_string(),
+            max_players: 2,
+            min_players: 1,
+            cards: vec![],
+            card_paths: vec![],
+            code_path: String::new(),
+            undo: UndoPolicy::default(),
+            turn_time: None,
+            card_back_path: None,
+            card_back: Arc::new(DynamicImage::default()),
+            engine_version: engine::LANGUAGE_VERSION,
+            capabilities,
+        }
+    }
+
+    #[test]
+    fn denies_undeclared_capabilities() {
+        let src = "println(\"hi\")\n";
+        let err = Game::new(template(Some(vec![])), src, vec![])
+            .err()
+            .unwrap();
+        assert!(
+            format!("{err:?}").contains("Test doesn't declare the io capability"),
+            "{err:?}"
+        );
+        assert!(Game::new(template(Some(vec![Capability::Io])), src, vec![]).is_ok());
+        assert!(Game::new(template(None), src, vec![]).is_ok());
+    }
+
+    #[test]
+    fn detects_diverging_replays() {
+        let game = Game::new(template(None), "let x = 1\n", vec![]).unwrap();
+        let replay = Replay {
+            game: "Test".to_string(),
+            players: vec!["A".to_string(), "B".to_string()],
+            seed: 0,
+            decisions: vec![Decision {
+                player: 0,
+                answer: "1".to_string(),
+                timed_out: false,
+            }],
+        };
+
+        let session = game.replay(&replay).unwrap();
+        let answer = session.ctx().decide(0, || unreachable!()).unwrap();
+        assert_eq!(answer.as_deref(), Some("1"));
+        let err = session.ctx().decide(0, || unreachable!()).unwrap_err();
+        assert!(err.to_string().contains("diverged"), "{err}");
+
+        let session = game.replay(&replay).unwrap();
+        let err = session.ctx().decide(1, || unreachable!()).unwrap_err();
+        assert!(err.to_string().contains("diverged"), "{err}");
+    }
+}
@@ -1,12 +1,37 @@
 use std::{
     collections::HashMap,
-    sync::{atomic::AtomicUsize, Arc, Mutex},
+    iter,
+    sync::{
+        atomic::AtomicBool,
+        mpsc::{self, Receiver, Sender},
+        Arc, Condvar, Mutex, PoisonError, RwLock, RwLockReadGuard,
+    },
+    time::{Duration, Instant},
 };
 
-use engine::{CardInventory, RtRef};
+use engine::{CardInventory, CardVal, HostError, Player, RtValue};
 use image::DynamicImage;
 use serde::{Deserialize, Serialize};
 
+#[cfg(feature = "fs")]
+use crate::templates;
+use crate::{
+    funcs::Capability,
+    query::Query,
+    replay::{Decision, Decisions},
+    save::SaveGame,
+};
+
+#[cfg(feature = "fs")]
+pub const GAMES_DIR: &str = "./play_cards/games/";
+#[cfg(feature = "fs")]
+pub const CARDS_DIR: &str = "./play_cards/cards/";
+#[cfg(feature = "fs")]
+pub const IMAGES_DIR: &str = "./play_cards/images/";
+
+/// the width and height normalized card images have, the proportions of poker cards
+pub const CARD_IMAGE_SIZE: (u32, u32) = (500, 700);
+
 #[derive(Deserialize, Serialize, Debug)]
 pub struct GameTemplate {
     pub name: String,
@@ -14,8 +39,108 @@ pub struct GameTemplate {
     pub min_players: usize,
     #[serde(skip)]
     pub cards: Vec<CardTemplate>,
-    pub card_paths: Vec<String>,
+    pub card_paths: Vec<CardEntry>,
     pub code_path: String,
+    /// whether players may take back their turns
+    #[serde(default)]
+    pub undo: UndoPolicy,
+    /// the number of seconds players have for their turn, players who run out of time skip
+    /// the decision they have to make and the script's `onTimeout` gets called
+    #[serde(default)]
+    pub turn_time: Option<u64>,
+    /// the image face down cards are shown with, games without one get a plain back
+    #[serde(default)]
+    pub card_back_path: Option<String>,
+    #[serde(skip)]
+    pub card_back: Arc<DynamicImage>,
+    /// the version of the script language the script was written for, templates without
+    /// one were written before versions were recorded and count as the first version
+    #[serde(default = "first_version")]
+    pub engine_version: u32,
+    /// the groups of host functions the script may call, `None` allows all of them
+    #[serde(default)]
+    pub capabilities: Option<Vec<Capability>>,
+}
+
+fn first_version() -> u32 {
+    1
+}
+
+#[derive(Deserialize, Serialize, Clone, Copy, Debug, Default)]
+pub enum UndoPolicy {
+    /// any turn may be taken back, meant for casual play
+    #[default]
+    Allowed,
+    Forbidden,
+    /// only the last few turns may be taken back
+    LastTurns(usize),
+}
+
+impl GameTemplate {
+    /// The template of the card the script refers to as `card`.
+    pub fn card(&self, card: CardVal) -> Option<&CardTemplate> {
+        self.cards.get(card.idx() as usize)
+    }
+
+    /// The number of copies of the `idx`th card the deck consists of.
+    pub fn copies(&self, idx: usize) -> usize {
+        self.card_paths.get(idx).map_or(1, CardEntry::count)
+    }
+}
+
+/// A card of a game's deck, either just the path of the card template or the path together
+/// with the number of copies of the card, e.g. `{"path": "skip.json", "count": 4}`.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+#[serde(untagged)]
+pub enum CardEntry {
+    Path(String),
+    Counted { path: String, count: usize },
+}
+
+impl CardEntry {
+    pub fn path(&self) -> &str {
+        match self {
+            CardEntry::Path(path) | CardEntry::Counted { path, .. } => path,
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        match self {
+            CardEntry::Path(_) => 1,
+            CardEntry::Counted { count, .. } => *count,
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl GameTemplate {
+    /// Loads the template of the game called `name` together with its cards.
+    pub fn load(name: &str) -> anyhow::Result<Self> {
+        let path = templates::existing(GAMES_DIR, "game", name)?;
+        let mut game: GameTemplate = templates::read(path)?;
+        game.cards = game
+            .card_paths
+            .iter()
+            .map(|entry| {
+                let mut card: CardTemplate = templates::read(entry.path())?;
+                card.migrate();
+                card.load_image()?;
+                Ok(card)
+            })
+            .collect::<anyhow::Result<Vec<CardTemplate>>>()?;
+        game.load_card_back()?;
+        Ok(game)
+    }
+
+    /// Decodes the image at the card back path, games without one keep the empty image.
+    pub fn load_card_back(&mut self) -> anyhow::Result<()> {
+        if let Some(path) = &self.card_back_path {
+            self.card_back = load_image(path).map_err(|err| {
+                anyhow::anyhow!("The card back `{}` couldn't be loaded: {}", path, err)
+            })?;
+        }
+        Ok(())
+    }
 }
 
 #[derive(Deserialize, Serialize, Debug)]
@@ -25,21 +150,668 @@ pub struct CardTemplate {
     pub image_path: String,
     #[serde(skip)]
     pub image: Arc<DynamicImage>,
+    /// the suit of cards from standard decks
+    #[serde(default)]
+    pub suit: Option<String>,
+    /// the rank of cards from standard decks, e.g. `10` or `Queen`
+    #[serde(default)]
+    pub rank: Option<String>,
+    /// groups the card belongs to, e.g. `wild` or `action card`
+    #[serde(default)]
+    pub tags: Vec<String>,
     pub metadata: HashMap<String, String>,
 }
 
+impl CardTemplate {
+    /// Cards created before suits and ranks had their own fields stored them as metadata,
+    /// those values are taken over if the fields are missing.
+    pub fn migrate(&mut self) {
+        if self.suit.is_none() {
+            self.suit = self.metadata.get("suit").cloned();
+        }
+        if self.rank.is_none() {
+            self.rank = self.metadata.get("rank").cloned();
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl CardTemplate {
+    /// Decodes the image at the card's image path, cards without one keep the empty image.
+    pub fn load_image(&mut self) -> anyhow::Result<()> {
+        if self.image_path.is_empty() {
+            return Ok(());
+        }
+        self.image = load_image(&self.image_path).map_err(|err| {
+            anyhow::anyhow!(
+                "The image `{}` of card {} couldn't be loaded: {}",
+                self.image_path,
+                self.name,
+                err
+            )
+        })?;
+        Ok(())
+    }
+}
+
+/// the decoded images by their path
+#[cfg(feature = "fs")]
+fn images() -> &'static Mutex<HashMap<String, Arc<DynamicImage>>> {
+    static IMAGES: std::sync::OnceLock<Mutex<HashMap<String, Arc<DynamicImage>>>> =
+        std::sync::OnceLock::new();
+    IMAGES.get_or_init(Default::default)
+}
+
+/// Decodes the image at `path`, every image is only decoded once and then shared by all
+/// cards and games using it.
+#[cfg(feature = "fs")]
+fn load_image(path: &str) -> image::ImageResult<Arc<DynamicImage>> {
+    if let Some(image) = images().lock().unwrap().get(path) {
+        return Ok(image.clone());
+    }
+    // the cache isn't locked while decoding, so games don't wait for each other's images
+    let image = Arc::new(image::open(path)?);
+    images()
+        .lock()
+        .unwrap()
+        .insert(path.to_string(), image.clone());
+    Ok(image)
+}
+
+/// Crops the image at `path` to the aspect ratio of cards, scales it to `CARD_IMAGE_SIZE`
+/// and stores it as `name` in the images directory, so games don't depend on files outside
+/// of the data directory. Returns the path of the stored image.
+#[cfg(feature = "fs")]
+pub fn normalize_image(path: &str, name: &str) -> anyhow::Result<String> {
+    let image = image::open(path)
+        .map_err(|err| anyhow::anyhow!("The image `{}` couldn't be loaded: {}", path, err))?;
+    let (width, height) = (image.width() as u64, image.height() as u64);
+    let (card_width, card_height) = (CARD_IMAGE_SIZE.0 as u64, CARD_IMAGE_SIZE.1 as u64);
+    // the largest part of the center with the right aspect ratio
+    let (crop_width, crop_height) = if width * card_height > height * card_width {
+        (height * card_width / card_height, height)
+    } else {
+        (width, width * card_height / card_width)
+    };
+    let image = image
+        .crop_imm(
+            ((width - crop_width) / 2) as u32,
+            ((height - crop_height) / 2) as u32,
+            crop_width as u32,
+            crop_height as u32,
+        )
+        .resize_exact(
+            CARD_IMAGE_SIZE.0,
+            CARD_IMAGE_SIZE.1,
+            image::imageops::FilterType::Lanczos3,
+        );
+    let normalized = format!("{}{}.png", IMAGES_DIR, name.replace(' ', "_"));
+    image.save_with_format(&normalized, image::ImageFormat::Png)?;
+    // an older image stored under the same name might still be cached
+    images()
+        .lock()
+        .unwrap()
+        .insert(normalized.clone(), Arc::new(image));
+    Ok(normalized)
+}
+
+type Listener = Arc<dyn Fn(&GameCtx, &GameEvent) + Send + Sync>;
+type QueryHandler = Box<dyn Fn(&GameCtx, &Query) -> Option<String> + Send + Sync>;
+
 pub struct GameCtx {
-    pub game: GameTemplate,
+    pub game: Arc<GameTemplate>,
+    state: RwLock<GameState>,
+    pub decisions: Mutex<Decisions>,
+    pub control: GameControl,
+    pub finished: AtomicBool,
+    /// hooks which were triggered by the current instruction
+    pub(crate) hooks: Mutex<Vec<Hook>>,
+    deadline: Mutex<Option<Instant>>,
+    listeners: Mutex<Vec<Listener>>,
+    query_handler: RwLock<Option<QueryHandler>>,
+}
+
+impl GameCtx {
+    /// Starts a game with a draw stack which holds as many copies of every card of the
+    /// template as the template declares.
+    pub fn new(game: Arc<GameTemplate>, players: Vec<String>) -> Self {
+        let draw_stack = (0..game.cards.len())
+            .flat_map(|idx| iter::repeat(idx).take(game.copies(idx)))
+            .collect::<Vec<_>>();
+        Self::restore(
+            game,
+            GameState {
+                players: players
+                    .into_iter()
+                    .map(|name| PlayerDef {
+                        name,
+                        inventories: vec![],
+                        hand: None,
+                        team: None,
+                        meta: HashMap::new(),
+                        active: true,
+                        disconnected: false,
+                    })
+                    .collect::<Vec<_>>(),
+                inventories: vec![],
+                draw_stack,
+                discard_pile: vec![],
+                meta: HashMap::new(),
+                team_scores: HashMap::new(),
+                curr_player: 0,
+                reversed: false,
+                turns: 0,
+                round: 0,
+                winner: None,
+                pending: vec![],
+            },
+        )
+    }
+
+    /// Recreates a game from a snapshot that was taken of a game using the same template.
+    pub fn restore(game: Arc<GameTemplate>, state: GameState) -> Self {
+        Self {
+            game,
+            state: RwLock::new(state),
+            decisions: Mutex::new(Decisions::Record(vec![])),
+            control: GameControl::new(),
+            finished: AtomicBool::new(false),
+            hooks: Mutex::new(vec![]),
+            deadline: Mutex::new(None),
+            listeners: Mutex::new(vec![]),
+            query_handler: RwLock::new(None),
+        }
+    }
+
+    /// Lets the session call the hook once the current instruction finished.
+    pub(crate) fn trigger(&self, hook: Hook) {
+        self.hooks.lock().unwrap().push(hook);
+    }
+
+    /// Registers a callback which gets invoked whenever something happens in the game.
+    /// Listeners must not block, the game waits for them.
+    pub fn subscribe(&self, listener: impl Fn(&GameCtx, &GameEvent) + Send + Sync + 'static) {
+        self.listeners
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(Arc::new(listener));
+    }
+
+    /// Notifies all listeners, this must not be called while holding the state lock.
+    pub(crate) fn emit(&self, event: &GameEvent) {
+        // the listeners are called without holding the lock, so others can subscribe meanwhile
+        let listeners = self
+            .listeners
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone();
+        for listener in listeners {
+            listener(self, event);
+        }
+    }
+
+    /// Lets `handler` answer the decisions players have to make, without a handler
+    /// the players decide at random. The handler returns `None` if the player didn't
+    /// answer before the `deadline`.
+    pub fn set_query_handler(
+        &self,
+        handler: impl Fn(&GameCtx, &Query) -> Option<String> + Send + Sync + 'static,
+    ) {
+        *self
+            .query_handler
+            .write()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Box::new(handler));
+    }
+
+    /// Asks the query handler for an answer, this must not be called while holding the
+    /// state lock.
+    pub(crate) fn answer(&self, query: &Query) -> Option<Option<String>> {
+        self.query_handler
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+            .map(|handler| handler(self, query))
+    }
+
+    /// The outcome of the game as it stands, games without a winner are draws.
+    pub fn result(&self) -> GameResult {
+        let state = self.state();
+        GameResult {
+            game: self.game.name.clone(),
+            players: state
+                .players
+                .iter()
+                .enumerate()
+                .map(|(idx, player)| PlayerResult {
+                    name: player.name.clone(),
+                    won: state.winner == Some(idx),
+                    score: match player.meta.get("score") {
+                        Some(RtValue::Decimal(score)) => Some(*score),
+                        _ => None,
+                    },
+                })
+                .collect(),
+            turns: state.turns,
+        }
+    }
+
+    /// Captures the mutable state of the game, the template itself isn't part of it.
+    pub fn snapshot(&self) -> GameState {
+        self.state().clone()
+    }
+
+    /// replaces the state of the game with a snapshot of it
+    pub(crate) fn set_state(&self, state: GameState) {
+        *self.state.write().unwrap_or_else(PoisonError::into_inner) = state;
+    }
+
+    /// Grants read access to the state of the game, host functions which want to modify it
+    /// have to use `with_state` instead.
+    pub fn state(&self) -> RwLockReadGuard<'_, GameState> {
+        // the state is only ever replaced as a whole,
+        // so it's consistent even if the lock got poisoned
+        self.state.read().unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Runs `f` with exclusive access to the state of the game. The changes `f` makes are only
+    /// applied if it succeeds, so a failing (or panicking) host function can't leave the game
+    /// in a half updated state. The events `f` adds to `pending` are emitted once the changes
+    /// were applied.
+    pub fn with_state<R>(
+        &self,
+        f: impl FnOnce(&mut GameState) -> Result<R, HostError>,
+    ) -> Result<R, HostError> {
+        let mut state = self.state.write().unwrap_or_else(PoisonError::into_inner);
+        let mut tx = state.clone();
+        let ret = f(&mut tx)?;
+        let events = std::mem::take(&mut tx.pending);
+        *state = tx;
+        drop(state);
+        for event in &events {
+            self.emit(event);
+        }
+        Ok(ret)
+    }
+
+    /// Lets `player` make a decision using `ask` and records it, `None` means the player
+    /// ran out of time. If a replay is being played back, the recorded decision is
+    /// returned instead, which fails if the game took a different course than the recorded one.
+    pub fn decide(
+        &self,
+        player: usize,
+        ask: impl FnOnce() -> Option<String>,
+    ) -> Result<Option<String>, HostError> {
+        let mut decisions = self
+            .decisions
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        match &mut *decisions {
+            Decisions::Record(decisions) => {
+                let answer = ask();
+                decisions.push(Decision {
+                    player,
+                    answer: answer.clone().unwrap_or_default(),
+                    timed_out: answer.is_none(),
+                });
+                Ok(answer)
+            }
+            Decisions::Replay(decisions) => {
+                let decision = decisions.pop_front().ok_or_else(|| {
+                    HostError::new("the replay diverged, it doesn't contain any more decisions")
+                })?;
+                if decision.player != player {
+                    return Err(HostError::new(format!(
+                        "the replay diverged, player {} decided next instead of player {}",
+                        decision.player, player
+                    )));
+                }
+                Ok((!decision.timed_out).then_some(decision.answer))
+            }
+        }
+    }
+
+    /// Marks `player` as disconnected and calls the script's `onPlayerDisconnect(player)`
+    /// once the current instruction finished. Unlike other events, `Disconnected` is
+    /// emitted on the calling thread.
+    pub fn disconnect(&self, player: usize) {
+        if self.set_disconnected(player, true) {
+            self.trigger(Hook::Disconnect { player });
+        }
+    }
+
+    /// Marks `player` as connected again, `Reconnected` is emitted on the calling thread.
+    pub fn reconnect(&self, player: usize) {
+        self.set_disconnected(player, false);
+    }
+
+    /// returns whether the player wasn't already marked like that
+    fn set_disconnected(&self, player: usize, disconnected: bool) -> bool {
+        self.with_state(|state| {
+            let def = &mut state.players[player];
+            if def.disconnected == disconnected {
+                return Ok(false);
+            }
+            def.disconnected = disconnected;
+            state.pending.push(if disconnected {
+                GameEvent::Disconnected { player }
+            } else {
+                GameEvent::Reconnected { player }
+            });
+            Ok(true)
+        })
+        // nothing in here fails
+        .unwrap_or(false)
+    }
+
+    /// The time the current player has to finish their turn by,
+    /// `None` if the game doesn't limit the time of turns.
+    pub fn deadline(&self) -> Option<Instant> {
+        *self.deadline.lock().unwrap()
+    }
+
+    /// starts the clock for the turn of the current player
+    pub(crate) fn start_timer(&self) {
+        *self.deadline.lock().unwrap() = self
+            .game
+            .turn_time
+            .map(|secs| Instant::now() + Duration::from_secs(secs));
+    }
+
+    /// gives the current player more time, e.g. because the game was paused
+    pub(crate) fn delay_deadline(&self, by: Duration) {
+        if let Some(deadline) = &mut *self.deadline.lock().unwrap() {
+            *deadline += by;
+        }
+    }
+}
+
+/// Something that happened in a game, hosts get notified about these through listeners.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub enum GameEvent {
+    /// `player` is now playing the `turn`th turn of the game
+    TurnStarted { turn: usize, player: usize },
+    /// the `round`th round of the game started
+    RoundStarted { round: usize },
+    /// `card` is shown to `players`, no matter where it is
+    CardRevealed { card: usize, players: Vec<usize> },
+    /// `card` was moved from `from` to `to`, cards which weren't anywhere before come from `None`
+    CardMoved {
+        card: usize,
+        from: Option<Zone>,
+        to: Zone,
+    },
+    /// `player` gave `answer` to a decision they had to make
+    Answered { player: usize, answer: String },
+    /// the game was set back to the start of `turn` by an undo or forth by a redo
+    Restored { turn: usize },
+    /// `player` lost the connection to the host
+    Disconnected { player: usize },
+    /// `player` is connected to the host again
+    Reconnected { player: usize },
+    /// the script finished running or ended the game, `winner` is `None` for draws
+    Finished { winner: Option<usize> },
+}
+
+/// A place cards can be in.
+#[derive(Deserialize, Serialize, Clone, Copy, Debug)]
+pub enum Zone {
+    DrawStack,
+    DiscardPile,
+    Inventory(usize),
+}
+
+/// The outcome of a game, meant to be recorded once the game finished.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GameResult {
+    pub game: String,
+    pub players: Vec<PlayerResult>,
+    pub turns: usize,
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PlayerResult {
+    pub name: String,
+    pub won: bool,
+    /// the player's `score` meta value, if the game keeps scores
+    pub score: Option<f64>,
+}
+
+/// A script function which gets called by the session once the instruction which
+/// triggered it finished, scripts which don't define the function simply ignore it.
+pub(crate) enum Hook {
+    /// `onRoundEnd(round)`
+    RoundEnd { round: usize },
+    /// `onTimeout(player)`, the player ran out of time to make a decision
+    Timeout { player: usize },
+    /// `onPlayerDisconnect(player)`, the player lost the connection to the host
+    Disconnect { player: usize },
+}
+
+impl Hook {
+    pub(crate) fn name(&self) -> &'static str {
+        match self {
+            Hook::RoundEnd { .. } => "onRoundEnd",
+            Hook::Timeout { .. } => "onTimeout",
+            Hook::Disconnect { .. } => "onPlayerDisconnect",
+        }
+    }
+
+    pub(crate) fn args(&self) -> Vec<RtValue> {
+        match self {
+            Hook::RoundEnd { round } => vec![RtValue::Decimal(*round as f64)],
+            Hook::Timeout { player } | Hook::Disconnect { player } => {
+                vec![RtValue::Player(Player::new(*player as u64))]
+            }
+        }
+    }
+}
+
+/// Everything about a game that changes while it's played.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct GameState {
     pub players: Vec<PlayerDef>,
-    pub inventories: Mutex<Vec<CardInventory>>,
-    pub draw_stack: Mutex<Vec<usize>>, // list of card indices
-    pub meta: HashMap<String, RtRef>,
-    pub curr_player: AtomicUsize,
+    pub inventories: Vec<CardInventory>,
+    pub draw_stack: Vec<usize>, // list of card indices
+    /// the discarded cards, the last one is on top
+    #[serde(default)]
+    pub discard_pile: Vec<usize>,
+    pub meta: HashMap<String, RtValue>,
+    /// the scores of the teams which scored so far
+    #[serde(default)]
+    pub team_scores: HashMap<usize, f64>,
+    pub curr_player: usize,
+    /// whether the order of play is reversed
+    #[serde(default)]
+    pub reversed: bool,
+    /// the number of turns that were played so far
+    pub turns: usize,
+    /// the round that is currently played, 0 until the script starts the first round
+    #[serde(default)]
+    pub round: usize,
+    pub winner: Option<usize>,
+    /// the events of the changes `with_state` didn't apply yet
+    #[serde(skip)]
+    pub(crate) pending: Vec<GameEvent>,
+}
+
+/// What a player can see of an inventory.
+#[derive(Clone, Debug)]
+pub enum InventoryView {
+    /// the indices of the cards in the inventory
+    Visible(Vec<usize>),
+    /// only the number of cards is known, they lie face down
+    Hidden(usize),
+}
+
+impl GameState {
+    /// The inventories as `viewer` sees them, spectators (`None`) only see the cards
+    /// in inventories which are visible to everyone.
+    pub fn inventories_for(&self, viewer: Option<usize>) -> Vec<InventoryView> {
+        self.inventories
+            .iter()
+            .map(|inv| {
+                let visible = match viewer {
+                    Some(viewer) => inv.visible_to(Player::new(viewer as u64)),
+                    None => inv.vis.is_none(),
+                };
+                if visible {
+                    InventoryView::Visible(
+                        inv.cards.iter().map(|card| card.idx() as usize).collect(),
+                    )
+                } else {
+                    InventoryView::Hidden(inv.cards.len())
+                }
+            })
+            .collect()
+    }
+
+    /// whether `player` can see `card`, which is the case if it's in an inventory visible
+    /// to them or on top of the discard pile
+    pub fn card_visible_to(&self, card: usize, player: usize) -> bool {
+        self.discard_top() == Some(card)
+            || self.inventories.iter().any(|inv| {
+                inv.visible_to(Player::new(player as u64))
+                    && inv.cards.contains(&CardVal::new(card as u64))
+            })
+    }
+
+    /// Whether the event only reveals what `viewer` is allowed to see, see `inventories_for`.
+    /// This has to be checked right after the event happened.
+    pub fn event_visible_to(&self, event: &GameEvent, viewer: Option<usize>) -> bool {
+        match event {
+            GameEvent::CardMoved { to, .. } => match to {
+                Zone::DrawStack => false,
+                Zone::DiscardPile => true,
+                Zone::Inventory(inv) => {
+                    self.inventories.get(*inv).is_some_and(|inv| match viewer {
+                        Some(viewer) => inv.visible_to(Player::new(viewer as u64)),
+                        None => inv.vis.is_none(),
+                    })
+                }
+            },
+            GameEvent::CardRevealed { players, .. } => {
+                viewer.is_some_and(|viewer| players.contains(&viewer))
+            }
+            GameEvent::Answered { player, .. } => viewer == Some(*player),
+            GameEvent::TurnStarted { .. }
+            | GameEvent::RoundStarted { .. }
+            | GameEvent::Restored { .. }
+            | GameEvent::Disconnected { .. }
+            | GameEvent::Reconnected { .. }
+            | GameEvent::Finished { .. } => true,
+        }
+    }
+
+    /// The top card of the discard pile, unlike the rest of the pile it's visible to everyone.
+    pub fn discard_top(&self) -> Option<usize> {
+        self.discard_pile.last().copied()
+    }
+}
+
+/// Allows a game which is running in the background to be suspended.
+pub struct GameControl {
+    state: Mutex<ControlState>,
+    changed: Condvar,
+}
+
+struct ControlState {
+    paused: bool,
+    /// the requests which are answered at the next safe point
+    requests: Vec<Request>,
+}
+
+/// Something the session has to do at the next safe point, the result is sent through
+/// the channel.
+pub(crate) enum Request {
+    Save(Sender<SaveGame>),
+    /// the undo (or redo) either returns the turn it went back (or forth) to or why it can't
+    Undo(Sender<Result<usize, String>>),
+    Redo(Sender<Result<usize, String>>),
+}
+
+impl GameControl {
+    fn new() -> Self {
+        Self {
+            state: Mutex::new(ControlState {
+                paused: false,
+                requests: vec![],
+            }),
+            changed: Condvar::new(),
+        }
+    }
+
+    /// returns whether the game wasn't paused already
+    pub fn pause(&self) -> bool {
+        !std::mem::replace(&mut self.state.lock().unwrap().paused, true)
+    }
+
+    /// returns whether the game was paused
+    pub fn resume(&self) -> bool {
+        let was_paused = std::mem::replace(&mut self.state.lock().unwrap().paused, false);
+        self.changed.notify_all();
+        was_paused
+    }
+
+    pub fn is_paused(&self) -> bool {
+        self.state.lock().unwrap().paused
+    }
+
+    fn request<R>(&self, request: impl FnOnce(Sender<R>) -> Request) -> Receiver<R> {
+        let (sender, receiver) = mpsc::channel();
+        self.state.lock().unwrap().requests.push(request(sender));
+        self.changed.notify_all();
+        receiver
+    }
+
+    /// Requests a save game of the session, which gets sent through the returned channel
+    /// once the session reaches a safe point, even if it's paused.
+    pub fn request_save(&self) -> Receiver<SaveGame> {
+        self.request(Request::Save)
+    }
+
+    /// Requests the session to go back to the start of the previous turn, like saves this
+    /// happens at the next safe point.
+    pub fn request_undo(&self) -> Receiver<Result<usize, String>> {
+        self.request(Request::Undo)
+    }
+
+    /// Requests the session to take back the last undo.
+    pub fn request_redo(&self) -> Receiver<Result<usize, String>> {
+        self.request(Request::Redo)
+    }
+
+    /// Blocks the calling thread for as long as the game is paused, returns the requests
+    /// which have to be answered before the game continues and how long it was paused.
+    pub(crate) fn wait_while_paused(&self) -> (Vec<Request>, Duration) {
+        let state = self.state.lock().unwrap();
+        let paused_at = state.paused.then(Instant::now);
+        let mut state = self
+            .changed
+            .wait_while(state, |state| state.paused && state.requests.is_empty())
+            .unwrap();
+        let paused = paused_at.map_or(Duration::ZERO, |paused_at| paused_at.elapsed());
+        (std::mem::take(&mut state.requests), paused)
+    }
+
+    /// drops the pending requests, e.g. because the game ended
+    pub(crate) fn cancel_requests(&self) {
+        self.state.lock().unwrap().requests.clear();
+    }
 }
 
+#[derive(Deserialize, Serialize, Clone, Debug)]
 pub struct PlayerDef {
     pub name: String,
-    pub inventories: Mutex<Vec<CardInventory>>,
-    pub meta: Mutex<HashMap<String, RtRef>>,
+    pub inventories: Vec<CardInventory>,
+    /// the index of the game inventory the player's cards are dealt into
+    #[serde(default)]
+    pub hand: Option<u64>,
+    /// the team the player plays for in partnership games
+    #[serde(default)]
+    pub team: Option<usize>,
+    pub meta: HashMap<String, RtValue>,
     pub active: bool,
+    /// the player lost the connection to the host, they keep their seat until they rejoin
+    #[serde(default)]
+    pub disconnected: bool,
 }
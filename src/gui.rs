@@ -0,0 +1,380 @@
+//! A window showing a running session with the art of its cards, selections are made by
+//! clicking the cards, players or options instead of typing their positions.
+
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    time::Duration,
+};
+
+use eframe::egui::{
+    self, load::SizedTexture, Color32, ColorImage, ImageButton, RichText, ScrollArea,
+    SelectableLabel, TextureHandle, TextureOptions, Ui, Vec2,
+};
+use image::DynamicImage;
+use play_cards::{
+    game_ctx::{GameState, InventoryView},
+    GameCtx, GameEvent, Query,
+};
+
+use crate::{describe_event, session_state, Prompts};
+
+/// the size cards are drawn at, their art gets scaled to it
+const CARD_SIZE: Vec2 = Vec2::new(90.0, 126.0);
+/// the number of events the log shows
+const EVENTS: usize = 12;
+/// how often the window is redrawn if nothing happens in it, host functions change the
+/// game without notifying anyone
+const REFRESH: Duration = Duration::from_millis(100);
+
+/// Shows session `id` as `viewer` sees it until the window is closed,
+/// spectators (`None`) only see what's public.
+pub fn show(
+    id: usize,
+    game: Arc<GameCtx>,
+    viewer: Option<usize>,
+    prompts: Prompts,
+) -> anyhow::Result<()> {
+    let (sender, events) = mpsc::channel();
+    game.subscribe(move |_, event| {
+        // the window might have been closed already
+        let _ = sender.send(event.clone());
+    });
+    let title = format!("{} (session {})", game.game.name, id);
+    let table = Table {
+        id,
+        game,
+        viewer,
+        prompts,
+        events,
+        log: VecDeque::new(),
+        textures: HashMap::new(),
+        back: None,
+        selected: vec![],
+        status: String::new(),
+    };
+    eframe::run_native(
+        &title,
+        eframe::NativeOptions::default(),
+        Box::new(|_| Ok(Box::new(table))),
+    )
+    .map_err(|err| anyhow::anyhow!("The window couldn't be opened: {}", err))
+}
+
+struct Table {
+    id: usize,
+    game: Arc<GameCtx>,
+    viewer: Option<usize>,
+    prompts: Prompts,
+    events: Receiver<GameEvent>,
+    /// the latest events the viewer may see
+    log: VecDeque<String>,
+    /// the art of the cards by their index, `None` for cards without art
+    textures: HashMap<usize, Option<TextureHandle>>,
+    /// the game's card back, `None` if it has none, loaded with the first face down card
+    back: Option<Option<TextureHandle>>,
+    /// the positions of the options selected in the current prompt
+    selected: Vec<usize>,
+    status: String,
+}
+
+impl eframe::App for Table {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        ctx.request_repaint_after(REFRESH);
+        while let Ok(event) = self.events.try_recv() {
+            self.record(&event);
+        }
+        let game = self.game.clone();
+        let state = game.state();
+        let prompt = self
+            .prompts
+            .lock()
+            .unwrap()
+            .get(&self.id)
+            .map(|prompt| (prompt.lines.clone(), prompt.query.clone()));
+
+        egui::TopBottomPanel::top("header").show(ctx, |ui| {
+            ui.heading(format!(
+                "{} (session {}, {}) - turn {}, round {}",
+                game.game.name,
+                self.id,
+                session_state(&game),
+                state.turns,
+                state.round
+            ));
+        });
+        egui::TopBottomPanel::bottom("prompt").show(ctx, |ui| {
+            ui.add_space(4.0);
+            match &prompt {
+                Some((lines, query)) => self.prompt(ui, &game, &state, lines, query.as_ref()),
+                None => {
+                    self.selected.clear();
+                    ui.label("The game isn't waiting for an answer");
+                }
+            }
+            if !self.status.is_empty() {
+                ui.colored_label(Color32::RED, &self.status);
+            }
+            ui.add_space(4.0);
+        });
+        egui::SidePanel::right("events").show(ctx, |ui| {
+            ui.heading("Events");
+            for line in &self.log {
+                ui.label(line);
+            }
+        });
+        egui::CentralPanel::default().show(ctx, |ui| {
+            ScrollArea::vertical().show(ui, |ui| self.table(ui, &game, &state));
+        });
+    }
+}
+
+impl Table {
+    fn record(&mut self, event: &GameEvent) {
+        let state = self.game.state();
+        if !state.event_visible_to(event, self.viewer) {
+            return;
+        }
+        let players = state
+            .players
+            .iter()
+            .map(|player| player.name.clone())
+            .collect::<Vec<_>>();
+        self.log
+            .push_back(describe_event(&self.game.game, &players, event));
+        if self.log.len() > EVENTS {
+            self.log.pop_front();
+        }
+    }
+
+    /// the piles, the players with their hands and the inventories which don't belong
+    /// to anyone
+    fn table(&mut self, ui: &mut Ui, game: &GameCtx, state: &GameState) {
+        ui.horizontal(|ui| {
+            ui.vertical(|ui| {
+                ui.label(format!("draw stack: {} cards", state.draw_stack.len()));
+                self.hidden_card(ui, game);
+            });
+            ui.vertical(|ui| {
+                ui.label(format!("discard pile: {} cards", state.discard_pile.len()));
+                match state.discard_top() {
+                    Some(card) => {
+                        self.card(ui, game, card, false);
+                    }
+                    None => {
+                        ui.allocate_space(CARD_SIZE);
+                    }
+                }
+            });
+            let mut teams = state.team_scores.iter().collect::<Vec<_>>();
+            teams.sort_by_key(|(team, _)| **team);
+            ui.vertical(|ui| {
+                for (team, score) in teams {
+                    ui.label(format!("team {}: {} points", team, score));
+                }
+            });
+        });
+        ui.separator();
+
+        let inventories = state.inventories_for(self.viewer);
+        for (idx, player) in state.players.iter().enumerate() {
+            let mut name = player.name.clone();
+            if let Some(engine::RtValue::Decimal(score)) = player.meta.get("score") {
+                name += &format!(", {} points", score);
+            }
+            if !player.active {
+                name += ", out";
+            }
+            if player.disconnected {
+                name += ", disconnected";
+            }
+            let mut name = RichText::new(name).strong();
+            if idx == state.curr_player {
+                name = name.color(Color32::YELLOW);
+            }
+            ui.label(name);
+            if let Some(hand) = player.hand {
+                self.inventory(ui, game, &inventories[hand as usize]);
+            }
+        }
+        let hands = state
+            .players
+            .iter()
+            .filter_map(|player| player.hand)
+            .collect::<Vec<_>>();
+        for (idx, inv) in inventories.iter().enumerate() {
+            if !hands.contains(&(idx as u64)) {
+                ui.label(RichText::new(format!("inventory {}", idx)).strong());
+                self.inventory(ui, game, inv);
+            }
+        }
+    }
+
+    fn inventory(&mut self, ui: &mut Ui, game: &GameCtx, inv: &InventoryView) {
+        ui.horizontal_wrapped(|ui| match inv {
+            InventoryView::Visible(cards) if cards.is_empty() => {
+                ui.label("no cards");
+            }
+            InventoryView::Visible(cards) => {
+                for card in cards {
+                    self.card(ui, game, *card, false);
+                }
+            }
+            InventoryView::Hidden(cards) => {
+                for _ in 0..*cards {
+                    self.hidden_card(ui, game);
+                }
+            }
+        });
+    }
+
+    /// draws `card` with its art or its name if it has none, returns whether it was clicked
+    fn card(&mut self, ui: &mut Ui, game: &GameCtx, card: usize, selected: bool) -> bool {
+        let template = &game.game.cards[card];
+        let texture = self
+            .textures
+            .entry(card)
+            .or_insert_with(|| texture(ui, format!("card {}", card), &template.image))
+            .clone();
+        let response = match texture {
+            Some(texture) => ui.add(
+                ImageButton::new(SizedTexture::new(texture.id(), CARD_SIZE)).selected(selected),
+            ),
+            None => ui.add_sized(
+                CARD_SIZE,
+                SelectableLabel::new(selected, template.name.as_str()),
+            ),
+        };
+        response.on_hover_text(template.name.as_str()).clicked()
+    }
+
+    /// draws a face down card with the game's card back or a plain one if it has none
+    fn hidden_card(&mut self, ui: &mut Ui, game: &GameCtx) {
+        let back = self
+            .back
+            .get_or_insert_with(|| texture(ui, "card back".to_string(), &game.game.card_back));
+        match back {
+            Some(back) => ui.add(egui::Image::new(SizedTexture::new(back.id(), CARD_SIZE))),
+            None => ui.add_sized(CARD_SIZE, egui::Button::new("?").fill(Color32::DARK_BLUE)),
+        };
+    }
+
+    /// shows the prompt the session is waiting for, selections are sent once confirmed
+    fn prompt(
+        &mut self,
+        ui: &mut Ui,
+        game: &GameCtx,
+        state: &GameState,
+        lines: &[String],
+        query: Option<&Query>,
+    ) {
+        let Some(query) = query else {
+            for line in lines {
+                ui.label(line);
+            }
+            if ui.button("ok").clicked() {
+                self.send("ok".to_string());
+            }
+            return;
+        };
+        if let Some(line) = lines.first() {
+            ui.label(RichText::new(line).strong());
+        }
+        match query {
+            Query::SelectCards { cards, cnt, .. } => {
+                ui.label(format!("select {} of the cards", cnt));
+                ui.horizontal_wrapped(|ui| {
+                    for (pos, card) in cards.iter().enumerate() {
+                        let selected = self.selected.contains(&pos);
+                        if self.card(ui, game, *card, selected) {
+                            self.toggle(pos);
+                        }
+                    }
+                });
+                self.confirm_selection(ui);
+            }
+            Query::SelectPlayers { players, cnt, .. } => {
+                ui.label(format!("select {} of the players", cnt));
+                ui.horizontal_wrapped(|ui| {
+                    for (pos, player) in players.iter().enumerate() {
+                        let selected = self.selected.contains(&pos);
+                        let name = state.players[*player].name.as_str();
+                        if ui.selectable_label(selected, name).clicked() {
+                            self.toggle(pos);
+                        }
+                    }
+                });
+                self.confirm_selection(ui);
+            }
+            Query::Choice { options, .. } => {
+                ui.horizontal_wrapped(|ui| {
+                    for (pos, option) in options.iter().enumerate() {
+                        if ui.button(option).clicked() {
+                            self.send(pos.to_string());
+                        }
+                    }
+                });
+            }
+            Query::Confirm { question, .. } => {
+                ui.label(question);
+                ui.horizontal(|ui| {
+                    if ui.button("yes").clicked() {
+                        self.send("yes".to_string());
+                    }
+                    if ui.button("no").clicked() {
+                        self.send("no".to_string());
+                    }
+                });
+            }
+        }
+    }
+
+    fn toggle(&mut self, pos: usize) {
+        match self.selected.iter().position(|other| *other == pos) {
+            Some(idx) => {
+                self.selected.remove(idx);
+            }
+            None => self.selected.push(pos),
+        }
+    }
+
+    fn confirm_selection(&mut self, ui: &mut Ui) {
+        if ui.button("confirm").clicked() {
+            let answer = self
+                .selected
+                .iter()
+                .map(|pos| pos.to_string())
+                .collect::<Vec<_>>()
+                .join(" ");
+            self.send(answer);
+        }
+    }
+
+    fn send(&mut self, answer: String) {
+        self.selected.clear();
+        self.status = match self.prompts.lock().unwrap().get(&self.id) {
+            Some(prompt) => {
+                // the prompt only disappears once the answer was accepted
+                let _ = prompt.answers.send(answer);
+                String::new()
+            }
+            None => "The game isn't waiting for an answer".to_string(),
+        };
+    }
+}
+
+/// uploads `image` to the gpu, cards without art carry an empty image
+fn texture(ui: &Ui, name: String, image: &DynamicImage) -> Option<TextureHandle> {
+    (image.width() != 0).then(|| {
+        let rgba = image.to_rgba8();
+        let size = [rgba.width() as usize, rgba.height() as usize];
+        ui.ctx().load_texture(
+            name,
+            ColorImage::from_rgba_unmultiplied(size, rgba.as_raw()),
+            TextureOptions::default(),
+        )
+    })
+}
@@ -0,0 +1,39 @@
+//! The play-cards game runtime.
+//!
+//! Games are loaded from their templates and played as independent sessions,
+//! the host (a CLI, a GUI or a server) gets notified about the progress of a session
+//! through event callbacks:
+//!
+//! ```no_run
+//! use play_cards::{Game, GameEvent};
+//!
+//! let game = Game::load("poker")?;
+//! let session = game.start(vec!["Alice".to_string(), "Bob".to_string()])?;
+//! session.on_event(|game, event| {
+//!     if let GameEvent::TurnStarted { player, .. } = event {
+//!         println!("it's {}'s turn", game.state().players[*player].name);
+//!     }
+//! });
+//! let replay = session.run()?;
+//! # Ok::<(), anyhow::Error>(())
+//! ```
+
+pub use crate::{
+    game::{Game, Session},
+    game_ctx::{GameCtx, GameEvent},
+    query::Query,
+};
+
+pub mod bot;
+pub mod funcs;
+mod game;
+pub mod game_ctx;
+#[cfg(feature = "python")]
+mod python;
+pub mod query;
+pub mod replay;
+pub mod save;
+pub mod spectator;
+pub mod stats;
+#[cfg(feature = "fs")]
+pub mod templates;
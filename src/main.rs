@@ -1,9 +1,19 @@
 use std::{
     collections::HashMap,
-    fs,
+    fs::{self, OpenOptions},
+    io::{self, Write},
+    mem,
+    net::TcpStream,
     num::{NonZero, NonZeroUsize},
-    path::Path,
-    sync::{atomic::AtomicUsize, Arc, Mutex},
+    panic::{self, AssertUnwindSafe},
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Instant, SystemTime, UNIX_EPOCH},
 };
 
 use clitty::{
@@ -14,28 +24,111 @@ use clitty::{
     ui::{CLIBuilder, CmdLineInterface, PrintFallback},
 };
 use conc_once_cell::ConcurrentOnceCell;
-use engine::{Function, RtType};
-use funcs::{
-    create_inv_global, create_inv_restricted, load_meta, next_player, player_cnt, player_name,
-    player_play, store_meta,
+use crossterm::{
+    cursor::MoveTo,
+    style::Stylize,
+    terminal::{Clear, ClearType},
+};
+use engine::{HostCall, Rng};
+use image::{DynamicImage, ImageFormat};
+use net::{ClientMsg, Lobby};
+use play_cards::{
+    bot::{Bot, BOTS_DIR},
+    funcs::{host_functions, silent_println},
+    game_ctx::{
+        normalize_image, CardEntry, CardTemplate, GameState, GameTemplate, InventoryView,
+        UndoPolicy, Zone, CARDS_DIR, GAMES_DIR, IMAGES_DIR,
+    },
+    replay::{Replay, LOGS_DIR, REPLAYS_DIR},
+    save::{SaveGame, SAVES_DIR},
+    spectator::Spectator,
+    stats::Stats,
+    templates, Game, GameCtx, GameEvent, Query, Session,
 };
-use game_ctx::{CardTemplate, GameCtx, GameTemplate, PlayerDef};
-use image::DynamicImage;
-use swap_it::{SwapArcOption, SwapGuard};
+use preview::Protocol;
+use tournament::{Entrant, Format, Tournament};
 
 mod conc_once_cell;
-mod funcs;
-mod game_ctx;
+#[cfg(feature = "gui")]
+mod gui;
+mod net;
+mod preview;
 mod sized_box;
+mod tournament;
+mod tui;
+
+/// the number of turns which are autosaved per session
+const AUTOSAVES: usize = 3;
+
+static CLI: ConcurrentOnceCell<CmdLineInterface<CliCtx>> = ConcurrentOnceCell::new();
+/// the context commands are executed with, for threads which outlive the command that
+/// spawned them
+static CTX: ConcurrentOnceCell<CliCtx> = ConcurrentOnceCell::new();
+
+/// the state shared between all commands
+struct CliCtx {
+    /// all games started in the background, addressable by their session id
+    sessions: Mutex<HashMap<usize, Arc<GameCtx>>>,
+    next_session: AtomicUsize,
+    /// the sessions waiting for a player to answer through the `answer` command
+    prompts: Prompts,
+    /// the hosted games which didn't start yet, they share their ids with the sessions
+    lobbies: Mutex<HashMap<usize, Arc<Lobby>>>,
+    /// the connections to the games hosted by others which were joined, by the id they
+    /// are answered with
+    remotes: Mutex<HashMap<usize, TcpStream>>,
+}
+
+type Prompts = Arc<Mutex<HashMap<usize, Prompt>>>;
 
-static CTX: SwapArcOption<GameCtx> = SwapArcOption::new_empty();
-static CLI: ConcurrentOnceCell<CmdLineInterface<()>> = ConcurrentOnceCell::new();
+/// What a session is waiting for the player to answer.
+struct Prompt {
+    lines: Vec<String>,
+    /// the decision the player has to make, other prompts are confirmed with `ok`
+    #[cfg_attr(not(feature = "gui"), allow(dead_code))]
+    query: Option<Query>,
+    answers: Sender<String>,
+}
+
+impl CliCtx {
+    /// registers the game as a new session and returns its id
+    fn start_session(&self, game: Arc<GameCtx>) -> usize {
+        let id = self.next_session.fetch_add(1, Ordering::Relaxed);
+        self.sessions.lock().unwrap().insert(id, game);
+        id
+    }
+
+    fn session(&self, id: &str) -> anyhow::Result<Arc<GameCtx>> {
+        let id = id.parse::<usize>()?;
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("There is no session with id {}", id))
+    }
+
+    fn running_session(&self, id: &str) -> anyhow::Result<Arc<GameCtx>> {
+        let game = self.session(id)?;
+        if game.finished.load(Ordering::Acquire) {
+            return Err(anyhow::anyhow!(
+                "The game of session {} already finished",
+                id
+            ));
+        }
+        Ok(game)
+    }
+}
 
 fn main() {
     fs::create_dir_all(GAMES_DIR).unwrap();
     fs::create_dir_all(CARDS_DIR).unwrap();
+    fs::create_dir_all(IMAGES_DIR).unwrap();
+    fs::create_dir_all(REPLAYS_DIR).unwrap();
+    fs::create_dir_all(LOGS_DIR).unwrap();
+    fs::create_dir_all(SAVES_DIR).unwrap();
+    fs::create_dir_all(BOTS_DIR).unwrap();
 
-    // FIXME: add UI
     let window = CLIBuilder::new()
         .command(
             CommandBuilder::new("play", CmdPlay).params(
@@ -57,6 +150,22 @@ fn main() {
                     }),
             ),
         )
+        .command(
+            CommandBuilder::new("tournament", CmdTournament).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "game",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    })
+                    .required(CommandParam {
+                        name: "entrants",
+                        ty: CommandParamTy::Unbound {
+                            minimum: NonZeroUsize::new(2).unwrap(),
+                            param: Box::new(CommandParamTy::String(CmdParamStrConstraints::None)),
+                        },
+                    }),
+            ),
+        )
         .command(
             CommandBuilder::new("create", CmdCreate).params(
                 UsageBuilder::new()
@@ -85,217 +194,2685 @@ fn main() {
                     }),
             ),
         )
-        .command(CommandBuilder::new("games", CmdGames))
         .command(
-            CommandBuilder::new("mkcard", CmdCreateCard).params(
+            CommandBuilder::new("rmgame", CmdRemoveGame).params(UsageBuilder::new().required(
+                CommandParam {
+                    name: "name",
+                    ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                },
+            )),
+        )
+        .command(
+            CommandBuilder::new("rmcard", CmdRemoveCard).params(
                 UsageBuilder::new()
                     .required(CommandParam {
                         name: "name",
                         ty: CommandParamTy::String(CmdParamStrConstraints::None),
                     })
+                    .optional(CommandParam {
+                        name: "--force",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("clonegame", CmdCloneGame).params(
+                UsageBuilder::new()
                     .required(CommandParam {
-                        name: "ordinal",
-                        ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
+                        name: "name",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
                     })
                     .required(CommandParam {
-                        name: "image path",
+                        name: "new name",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    })
+                    .optional(CommandParam {
+                        name: "--script",
                         ty: CommandParamTy::String(CmdParamStrConstraints::None),
                     }),
             ),
         )
-        .fallback(Box::new(PrintFallback::new(
-            "This command is not known".to_string(),
-        )))
-        .prompt("Enter a command: ".to_string())
-        .build();
-    CLI.get_or_init(|| CmdLineInterface::new(window));
-    loop {
-        CLI.get().unwrap().await_input(&()).unwrap();
-    }
-}
-
-pub fn get_ctx<'a>() -> SwapGuard<Arc<GameCtx>, GameCtx> {
-    CTX.load().unwrap()
-}
-
-struct CmdPlay;
-
-impl CommandImpl for CmdPlay {
-    type CTX = ();
-
-    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
-        let mut game: GameTemplate = serde_json::from_str(
-            fs::read_to_string(format!("{}{}.json", GAMES_DIR, input[0]))
-                .unwrap()
-                .as_str(),
+        .command(
+            CommandBuilder::new("renamegame", CmdRenameGame).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "name",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    })
+                    .required(CommandParam {
+                        name: "new name",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    }),
+            ),
         )
-        .unwrap();
-        let cards: Vec<CardTemplate> = game
-            .card_paths
-            .iter()
-            .map(|path| serde_json::from_str(fs::read_to_string(path).unwrap().as_str()).unwrap())
-            .collect::<Vec<_>>();
-        game.cards = cards;
-        let code_path = game.code_path.clone();
-        // FIXME: enforce player limits
-        let game = GameCtx {
-            game,
-            players: input
-                .iter()
-                .skip(1)
-                .map(|player| PlayerDef {
-                    name: player.to_string(),
-                    inventories: Mutex::new(vec![]),
-                    meta: Mutex::new(HashMap::new()),
-                    active: true,
-                })
-                .collect::<Vec<_>>(),
-            inventories: Mutex::new(vec![]),
-            draw_stack: Mutex::new(vec![]),
-            meta: HashMap::new(),
-            curr_player: AtomicUsize::new(0),
-        };
-        CTX.store(Arc::new(game));
-        // start game
-        engine::run(
-            &code_path,
-            vec![
-                Function {
-                    params: &[],
-                    var_len: false,
-                    name: "nextPlayer",
-                    call: next_player,
+        .command(
+            CommandBuilder::new("renamecard", CmdRenameCard).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "name",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    })
+                    .required(CommandParam {
+                        name: "new name",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("editgame", CmdEditGame).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "name",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    })
+                    .required(CommandParam {
+                        name: "options",
+                        ty: CommandParamTy::Unbound {
+                            minimum: NonZeroUsize::new(2).unwrap(),
+                            param: Box::new(CommandParamTy::String(CmdParamStrConstraints::None)),
+                        },
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("simulate", CmdSimulate).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "game",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    })
+                    .required(CommandParam {
+                        name: "options",
+                        ty: CommandParamTy::Unbound {
+                            minimum: NonZeroUsize::new(2).unwrap(),
+                            param: Box::new(CommandParamTy::String(CmdParamStrConstraints::None)),
+                        },
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("replay", CmdReplay).params(UsageBuilder::new().required(
+                CommandParam {
+                    name: "replay",
+                    ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                },
+            )),
+        )
+        .command(
+            CommandBuilder::new("log", CmdLog).params(UsageBuilder::new().required(CommandParam {
+                name: "replay",
+                ty: CommandParamTy::String(CmdParamStrConstraints::None),
+            })),
+        )
+        .command(
+            CommandBuilder::new("audit", CmdAudit).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "replay",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    })
+                    .optional(CommandParam {
+                        name: "function",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("lint", CmdLint).params(UsageBuilder::new().required(
+                CommandParam {
+                    name: "game",
+                    ty: CommandParamTy::String(CmdParamStrConstraints::None),
                 },
-                Function {
-                    params: &[],
-                    var_len: false,
-                    name: "playerCount",
-                    call: player_cnt,
+            )),
+        )
+        .command(
+            CommandBuilder::new("pause", CmdPause).params(UsageBuilder::new().required(
+                CommandParam {
+                    name: "session",
+                    ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
                 },
-                Function {
-                    params: &[],
-                    var_len: true,
-                    name: "playerName",
-                    call: player_name,
+            )),
+        )
+        .command(
+            CommandBuilder::new("resume", CmdResume).params(UsageBuilder::new().required(
+                CommandParam {
+                    name: "session",
+                    ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
                 },
-                Function {
-                    params: &[],
-                    var_len: true,
-                    name: "createInvGlobal",
-                    call: create_inv_global,
+            )),
+        )
+        .command(
+            CommandBuilder::new("save", CmdSave).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "session",
+                        ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
+                    })
+                    .required(CommandParam {
+                        name: "slot",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("undo", CmdUndo).params(UsageBuilder::new().required(
+                CommandParam {
+                    name: "session",
+                    ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
                 },
-                Function {
-                    params: &[],
-                    var_len: true,
-                    name: "createInvRestricted",
-                    call: create_inv_restricted,
+            )),
+        )
+        .command(
+            CommandBuilder::new("redo", CmdRedo).params(UsageBuilder::new().required(
+                CommandParam {
+                    name: "session",
+                    ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
                 },
-                Function {
-                    params: &[],
-                    var_len: true,
-                    name: "storeMeta",
-                    call: store_meta,
+            )),
+        )
+        .command(
+            CommandBuilder::new("load", CmdLoad).params(UsageBuilder::new().required(
+                CommandParam {
+                    name: "slot",
+                    ty: CommandParamTy::String(CmdParamStrConstraints::None),
                 },
-                Function {
-                    params: &[],
-                    var_len: true,
-                    name: "loadMeta",
-                    call: load_meta,
+            )),
+        )
+        .command(
+            CommandBuilder::new("host", CmdHost).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "game",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    })
+                    .required(CommandParam {
+                        name: "port",
+                        ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
+                    })
+                    .optional(CommandParam {
+                        name: "websocket port",
+                        ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("lobby", CmdLobby).params(UsageBuilder::new().required(
+                CommandParam {
+                    name: "lobby",
+                    ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
                 },
-                Function {
-                    params: &[],
-                    var_len: false,
-                    name: "playerPlay",
-                    call: player_play,
+            )),
+        )
+        .command(
+            CommandBuilder::new("pick", CmdPick).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "lobby",
+                        ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
+                    })
+                    .required(CommandParam {
+                        name: "game",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("start", CmdStart).params(UsageBuilder::new().required(
+                CommandParam {
+                    name: "lobby",
+                    ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
                 },
-            ],
-        )?;
-        Ok(())
-    }
-}
-
-const GAMES_DIR: &str = "./play_cards/games/";
-
-struct CmdCreate;
-
-impl CommandImpl for CmdCreate {
-    type CTX = ();
-
-    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
-        let game_name = input[0].to_string();
-        let code_path = input[1].to_string();
-        let min_players = input[2].parse::<usize>()?;
-        let max_players = input[3].parse::<usize>()?;
-        let cards = input
-            .iter()
-            .skip(4)
-            .map(|path| path.to_string())
-            .collect::<Vec<_>>();
-        let out = serde_json::to_string(&GameTemplate {
-            name: game_name,
-            max_players,
-            min_players,
-            cards: vec![],
-            card_paths: cards,
-            code_path,
-        })?;
-        fs::write(format!("{}{}.json", GAMES_DIR, input[0]), out)?;
-        CLI.get()
-            .unwrap()
-            .println(format!("Successfully created {}", input[0]).as_str());
-        Ok(())
-    }
-}
-
-struct CmdGames;
-
-impl CommandImpl for CmdGames {
-    type CTX = ();
-
-    fn execute(&self, _ctx: &Self::CTX, _input: &[&str]) -> anyhow::Result<()> {
-        let dir = fs::read_dir(GAMES_DIR)?;
-        let mut games = vec![];
-        for game in dir {
-            games.push(game?.path());
-        }
-        CLI.get()
-            .unwrap()
-            .println(format!("Games ({}):", games.len()).as_str());
-        for game_path in games {
-            let game_name = game_path.file_name().unwrap().to_str().unwrap().to_string();
-            let game: GameTemplate = serde_json::from_str(fs::read_to_string(game_path)?.as_str())?;
-            CLI.get()
-                .unwrap()
-                .println(format!("{}: {:?}", game_name, game).as_str());
-        }
-        Ok(())
-    }
-}
-
-const CARDS_DIR: &str = "./play_cards/cards/";
-
-struct CmdCreateCard;
-
-impl CommandImpl for CmdCreateCard {
-    type CTX = ();
-
-    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
-        let name = input[0].to_string();
-        let ord = input[1].parse::<usize>()?;
-        let image_path = input[2].to_string();
-        fs::write(
-            format!("{}{}.json", CARDS_DIR, name),
-            serde_json::to_string_pretty(&CardTemplate {
-                name,
-                ord,
-                image_path,
-                image: Arc::new(DynamicImage::default()),
-                metadata: HashMap::new(),
-            })?,
-        )?;
-        CLI.get()
-            .unwrap()
-            .println(format!("Created card {}", input[0]).as_str());
+            )),
+        )
+        .command(
+            CommandBuilder::new("join", CmdJoin).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "address",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    })
+                    .required(CommandParam {
+                        name: "name",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    })
+                    .optional(CommandParam {
+                        name: "token",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("ready", CmdReady).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "game",
+                        ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
+                    })
+                    .optional(CommandParam {
+                        name: "no",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("spectate", CmdSpectate).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "session",
+                        ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
+                    })
+                    .optional(CommandParam {
+                        name: "--hands",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("status", CmdStatus).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "session",
+                        ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
+                    })
+                    .optional(CommandParam {
+                        name: "player",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("tui", CmdTui).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "session",
+                        ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
+                    })
+                    .optional(CommandParam {
+                        name: "player",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("gui", CmdGui).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "session",
+                        ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
+                    })
+                    .optional(CommandParam {
+                        name: "player",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("answer", CmdAnswer).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "session",
+                        ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
+                    })
+                    .required(CommandParam {
+                        name: "answer",
+                        ty: CommandParamTy::Unbound {
+                            minimum: NonZeroUsize::new(1).unwrap(),
+                            param: Box::new(CommandParamTy::String(CmdParamStrConstraints::None)),
+                        },
+                    }),
+            ),
+        )
+        .command(CommandBuilder::new("sessions", CmdSessions))
+        .command(CommandBuilder::new("leaderboard", CmdLeaderboard).params(
+            UsageBuilder::new().required(CommandParam {
+                name: "game",
+                ty: CommandParamTy::String(CmdParamStrConstraints::None),
+            }),
+        ))
+        .command(
+            CommandBuilder::new("stats", CmdStats).params(UsageBuilder::new().optional(
+                CommandParam {
+                    name: "game",
+                    ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                },
+            )),
+        )
+        .command(CommandBuilder::new("games", CmdGames))
+        .command(
+            CommandBuilder::new("help", CmdHelp).params(UsageBuilder::new().required(
+                CommandParam {
+                    name: "topic",
+                    ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                },
+            )),
+        )
+        .command(
+            CommandBuilder::new("find", CmdFind).params(UsageBuilder::new().required(
+                CommandParam {
+                    name: "query",
+                    ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                },
+            )),
+        )
+        .command(
+            CommandBuilder::new("validate", CmdValidate).params(UsageBuilder::new().required(
+                CommandParam {
+                    name: "game",
+                    ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                },
+            )),
+        )
+        .command(
+            CommandBuilder::new("run", CmdRun).params(UsageBuilder::new().required(CommandParam {
+                name: "script",
+                ty: CommandParamTy::String(CmdParamStrConstraints::None),
+            })),
+        )
+        .command(
+            CommandBuilder::new("mkcard", CmdCreateCard).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "name",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    })
+                    .required(CommandParam {
+                        name: "ordinal",
+                        ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
+                    })
+                    .required(CommandParam {
+                        name: "image path",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    })
+                    .optional(CommandParam {
+                        name: "--normalize",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("editcard", CmdEditCard).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "name",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    })
+                    .required(CommandParam {
+                        name: "options",
+                        ty: CommandParamTy::Unbound {
+                            minimum: NonZeroUsize::new(2).unwrap(),
+                            param: Box::new(CommandParamTy::String(CmdParamStrConstraints::None)),
+                        },
+                    }),
+            ),
+        )
+        .command(CommandBuilder::new("mkcards", CmdCreateCards).params(
+            UsageBuilder::new().required(CommandParam {
+                name: "csv",
+                ty: CommandParamTy::String(CmdParamStrConstraints::None),
+            }),
+        ))
+        .command(
+            CommandBuilder::new("importsheet", CmdImportSheet).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "image",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    })
+                    .required(CommandParam {
+                        name: "cols",
+                        ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
+                    })
+                    .required(CommandParam {
+                        name: "rows",
+                        ty: CommandParamTy::UInt(CmdParamNumConstraints::None),
+                    })
+                    .required(CommandParam {
+                        name: "name prefix",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("showcard", CmdShowCard).params(
+                UsageBuilder::new()
+                    .required(CommandParam {
+                        name: "name",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    })
+                    .optional(CommandParam {
+                        name: "protocol",
+                        ty: CommandParamTy::String(CmdParamStrConstraints::None),
+                    }),
+            ),
+        )
+        .command(
+            CommandBuilder::new("mkstandarddeck", CmdCreateStandardDeck).params(
+                UsageBuilder::new().optional(CommandParam {
+                    name: "options",
+                    ty: CommandParamTy::Unbound {
+                        minimum: NonZeroUsize::new(1).unwrap(),
+                        param: Box::new(CommandParamTy::String(CmdParamStrConstraints::None)),
+                    },
+                }),
+            ),
+        )
+        .fallback(Box::new(PrintFallback::new(
+            "This command is not known".to_string(),
+        )))
+        .prompt("Enter a command: ".to_string())
+        .build();
+    CLI.get_or_init(|| CmdLineInterface::new(window));
+    let ctx = CTX.get_or_init(|| CliCtx {
+        sessions: Mutex::new(HashMap::new()),
+        next_session: AtomicUsize::new(1),
+        prompts: Arc::new(Mutex::new(HashMap::new())),
+        lobbies: Mutex::new(HashMap::new()),
+        remotes: Mutex::new(HashMap::new()),
+    });
+    let cli = CLI.get().unwrap();
+    loop {
+        // a failing command only fails itself, the cli stays usable
+        if let Err(err) = cli.await_input(ctx) {
+            cli.println(format!("{:#}", err).red().to_string().as_str());
+        }
+    }
+}
+
+struct CmdPlay;
+
+impl CommandImpl for CmdPlay {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let game = Game::load(input[0])?;
+        let mut players = vec![];
+        let mut bots = HashMap::new();
+        let mut seed = None;
+        let mut force = false;
+        let mut args = input.iter().skip(1);
+        while let Some(arg) = args.next() {
+            if *arg == "--force" {
+                force = true;
+                continue;
+            }
+            if let Some(option) = arg.strip_prefix("--") {
+                let val = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("Missing value for option `{arg}`"))?;
+                match option {
+                    "seed" => seed = Some(val.parse::<u64>()?),
+                    _ => return Err(anyhow::anyhow!("Unknown option `{arg}`")),
+                }
+                continue;
+            }
+            // `name@bot` lets a bot play the seat, e.g. `Bob@greedy`
+            match arg.split_once('@') {
+                Some((name, bot)) => {
+                    bots.insert(players.len(), Bot::load(bot)?);
+                    players.push(name.to_string());
+                }
+                None => players.push(arg.to_string()),
+            }
+        }
+        // problems of the script are better noticed before anybody is in the middle of a game
+        let lints = game.lints();
+        if !lints.is_empty() && !force {
+            let cli = CLI.get().unwrap();
+            for lint in lints {
+                cli.println(format!("Warning: {}", lint).as_str());
+            }
+            return Err(anyhow::anyhow!(
+                "The script of {} has warnings, pass `--force` to play it anyway",
+                input[0]
+            ));
+        }
+        let session = match seed {
+            Some(seed) => game.start_seeded(players, seed)?,
+            None => game.start(players)?,
+        };
+        // the seed lets the game be dealt the same way again, e.g. to reproduce a bug
+        let seed = session.seed();
+        let id = run_session(ctx, session, |session, id| {
+            play_locally(session, id, &ctx.prompts, bots)
+        })?;
+        CLI.get()
+            .unwrap()
+            .println(format!("Started {} as session {} with seed {}", input[0], id, seed).as_str());
+        Ok(())
+    }
+}
+
+/// Runs the session in the background in order to keep the cli usable while it's running,
+/// `players` lets the players interact with it. Returns the id of the session.
+fn run_session(
+    ctx: &CliCtx,
+    session: Session,
+    players: impl FnOnce(&Session, usize),
+) -> anyhow::Result<usize> {
+    let (id, run) = prepare_session(ctx, session, players)?;
+    thread::spawn(run);
+    Ok(id)
+}
+
+/// Registers the session with the cli and lets `players` interact with it. Returns the id
+/// of the session and a closure which runs it to its end, reports how it went and returns
+/// whether it finished.
+fn prepare_session(
+    ctx: &CliCtx,
+    mut session: Session,
+    players: impl FnOnce(&Session, usize),
+) -> anyhow::Result<(usize, impl FnOnce() -> bool + Send + 'static)> {
+    let started = SystemTime::now().duration_since(UNIX_EPOCH)?.as_secs();
+    let id = ctx.start_session(session.ctx().clone());
+    // the replay, the action log and the autosaves of the game share its name, the id
+    // tells apart sessions started within the same second
+    let name = format!("{}_{}_{}", session.ctx().game.name, started, id);
+    let open = |suffix: &str| {
+        OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(format!("{}{}{}.jsonl", LOGS_DIR, name, suffix))
+    };
+    // the host calls are kept apart from the events as they are only needed to investigate
+    // disputes, see the `audit` command
+    let (log, mut audit_log) = match open("").and_then(|log| Ok((log, open(".audit")?))) {
+        Ok((log, audit_log)) => (Mutex::new(log), audit_log),
+        Err(err) => {
+            ctx.sessions.lock().unwrap().remove(&id);
+            return Err(err.into());
+        }
+    };
+    session.on_event(move |_, event| {
+        let res = serde_json::to_string(event)
+            .map_err(anyhow::Error::from)
+            .and_then(|line| Ok(writeln!(log.lock().unwrap(), "{}", line)?));
+        if let Err(err) = res {
+            CLI.get()
+                .unwrap()
+                .println(format!("Session {}: logging failed: {}", id, err).as_str());
+        }
+    });
+    session.audit(move |call| {
+        let res = serde_json::to_string(&call)
+            .map_err(anyhow::Error::from)
+            .and_then(|line| Ok(writeln!(audit_log, "{}", line)?));
+        if let Err(err) = res {
+            CLI.get()
+                .unwrap()
+                .println(format!("Session {}: auditing failed: {}", id, err).as_str());
+        }
+    });
+    // crashed games can be continued from one of the last turns using the `load` command
+    let slots = (0..AUTOSAVES)
+        .map(|slot| format!("autosave_{}_{}", name, slot))
+        .collect::<Vec<_>>();
+    let autosave_slots = slots.clone();
+    session.autosave(move |save| {
+        let slot = &autosave_slots[save.state.turns % AUTOSAVES];
+        if let Err(err) = save.save(slot) {
+            CLI.get()
+                .unwrap()
+                .println(format!("Session {}: autosaving failed: {}", id, err).as_str());
+        }
+    });
+    players(&session, id);
+    session.on_event(move |game, event| {
+        if let GameEvent::Finished { winner } = event {
+            let result = match winner {
+                Some(winner) => format!("{} won", game.state().players[*winner].name),
+                None => "it's a draw".to_string(),
+            };
+            CLI.get().unwrap().println(
+                format!(
+                    "Session {}: the game ended after {} turns, {}",
+                    id,
+                    game.state().turns,
+                    result
+                )
+                .as_str(),
+            );
+        }
+    });
+    let run = move || {
+        let cli = CLI.get().unwrap();
+        let game = session.ctx().clone();
+        let res = session.run().and_then(|replay| {
+            // the autosaves are only kept around for games which didn't finish
+            for slot in &slots {
+                SaveGame::delete(slot)?;
+            }
+            replay.save(&name)?;
+            record_result(&game)?;
+            Ok(())
+        });
+        match &res {
+            Ok(()) => cli.println(format!("Session {}: recorded replay {}", id, name).as_str()),
+            Err(err) => cli.println(format!("Session {}: the game failed: {}", id, err).as_str()),
+        }
+        res.is_ok()
+    };
+    Ok((id, run))
+}
+
+/// lets the players play through the cli, the seats in `bots` are played by bots
+fn play_locally(session: &Session, id: usize, prompts: &Prompts, bots: HashMap<usize, Bot>) {
+    // listeners mustn't block the game, so the reveals are confirmed before the next decision
+    let reveals = Arc::new(Mutex::new(vec![]));
+    let revealed = reveals.clone();
+    let seats = bots.keys().copied().collect::<Vec<_>>();
+    session.on_event(move |game, event| {
+        if let GameEvent::CardRevealed { card, players } = event {
+            // bots don't have to be waited for
+            let players = players
+                .iter()
+                .copied()
+                .filter(|player| !seats.contains(player))
+                .collect::<Vec<_>>();
+            if players.is_empty() {
+                return;
+            }
+            let names = players
+                .iter()
+                .map(|player| game.state().players[*player].name.clone())
+                .collect::<Vec<_>>();
+            revealed.lock().unwrap().push(format!(
+                "Session {}: {}, {} is revealed to you, confirm with `answer {} ok`",
+                id,
+                names.join(", "),
+                game.game.cards[*card].name,
+                id
+            ));
+        }
+    });
+    let prompts = prompts.clone();
+    let ctx = session.ctx().clone();
+    // players sharing the keyboard mustn't see each other's hands
+    let hotseat = ctx.state().players.len() - bots.len() > 1;
+    let last_player = Mutex::new(None);
+    session.on_query(move |game, query| {
+        // the players get to see the revealed cards before anyone decides anything
+        let pending = mem::take(&mut *reveals.lock().unwrap());
+        for line in pending {
+            await_answer(
+                id,
+                &prompts,
+                vec![line],
+                None,
+                None,
+                |answer| match answer {
+                    "ok" => Ok(()),
+                    _ => Err("Confirm with `ok`".to_string()),
+                },
+            );
+        }
+        let Some(bot) = bots.get(&query.player()) else {
+            if !hotseat {
+                return ask_player(id, &prompts, game, query);
+            }
+            let mut last_player = last_player.lock().unwrap();
+            if *last_player != Some(query.player()) {
+                hand_off(id, &prompts, game, query.player())?;
+                *last_player = Some(query.player());
+            }
+            let answer = ask_player(id, &prompts, game, query);
+            clear_screen();
+            return answer;
+        };
+        Some(bot.answer(&ctx, query).unwrap_or_else(|err| {
+            CLI.get().unwrap().println(
+                format!(
+                    "Session {}: the strategy of {} failed, it decides at random: {}",
+                    id,
+                    game.state().players[query.player()].name,
+                    err
+                )
+                .as_str(),
+            );
+            query.random_answer(&game.game, &mut Rng::new(Rng::entropy_seed()))
+        }))
+    });
+}
+
+/// Prompts the player until they give a valid answer. Answers are given through the
+/// `answer` command, so the game doesn't compete with the cli for the input.
+fn ask_player(id: usize, prompts: &Prompts, game: &GameCtx, query: &Query) -> Option<String> {
+    let mut lines = vec![format!("Session {} is waiting for an answer:", id)];
+    lines.extend(prompt_lines(game, query));
+    let player = game.state().players[query.player()].name.clone();
+    let answer = await_answer(
+        id,
+        prompts,
+        lines,
+        Some(query.clone()),
+        game.deadline(),
+        |answer| query.parse(&game.game, answer).map(|_| ()),
+    );
+    if answer.is_none() {
+        CLI.get()
+            .unwrap()
+            .println(format!("Session {}: {} ran out of time", id, player).as_str());
+    }
+    answer
+}
+
+/// Hides the screen until `player` has the keyboard, returns `None` if they didn't confirm
+/// it before their time ran out.
+fn hand_off(id: usize, prompts: &Prompts, game: &GameCtx, player: usize) -> Option<()> {
+    clear_screen();
+    let name = game.state().players[player].name.clone();
+    let lines = vec![format!(
+        "Session {}: pass the keyboard to {}, who confirms with `answer {} ok`",
+        id, name, id
+    )];
+    let confirmed = await_answer(
+        id,
+        prompts,
+        lines,
+        None,
+        game.deadline(),
+        |answer| match answer {
+            "ok" => Ok(()),
+            _ => Err("Confirm with `ok`".to_string()),
+        },
+    );
+    if confirmed.is_none() {
+        CLI.get()
+            .unwrap()
+            .println(format!("Session {}: {} ran out of time", id, name).as_str());
+        return None;
+    }
+    clear_screen();
+    Some(())
+}
+
+fn clear_screen() {
+    // the hand stays visible if the terminal can't be cleared, but the game goes on
+    let _ = crossterm::execute!(io::stdout(), Clear(ClearType::All), MoveTo(0, 0));
+}
+
+/// the question of the query together with what the player sees of the game
+fn prompt_lines(game: &GameCtx, query: &Query) -> Vec<String> {
+    let mut lines = vec![];
+    if let Some(deadline) = game.deadline() {
+        lines.push(format!(
+            "{} seconds are left",
+            deadline.saturating_duration_since(Instant::now()).as_secs()
+        ));
+    }
+    let player = game.state().players[query.player()].name.clone();
+    // players only get to see what they are allowed to see
+    lines.extend(inventory_lines(
+        game,
+        game.state().inventories_for(Some(query.player())),
+    ));
+    match query {
+        Query::SelectCards {
+            cards,
+            cnt,
+            force_different,
+            allow_partial,
+            ..
+        } => {
+            lines.push(format!(
+                "{}, select {}{} cards{}:",
+                player,
+                if *allow_partial { "up to " } else { "" },
+                cnt,
+                if *force_different {
+                    " with different names"
+                } else {
+                    ""
+                }
+            ));
+            let state = game.state();
+            for (pos, card) in cards.iter().enumerate() {
+                let name = if state.card_visible_to(*card, query.player()) {
+                    game.game.cards[*card].name.as_str()
+                } else {
+                    "a face down card"
+                };
+                lines.push(format!("{}: {}", pos, name));
+            }
+        }
+        Query::SelectPlayers {
+            players,
+            cnt,
+            allow_partial,
+            ..
+        } => {
+            lines.push(format!(
+                "{}, select {}{} players:",
+                player,
+                if *allow_partial { "up to " } else { "" },
+                cnt
+            ));
+            let state = game.state();
+            for (pos, player) in players.iter().enumerate() {
+                lines.push(format!("{}: {}", pos, state.players[*player].name));
+            }
+        }
+        Query::Choice { options, .. } => {
+            lines.push(format!("{}, choose one of these options:", player));
+            for (pos, option) in options.iter().enumerate() {
+                lines.push(format!("{}: {}", pos, option));
+            }
+        }
+        Query::Confirm { question, .. } => {
+            lines.push(format!("{}, {} (yes/no)", player, question));
+        }
+    }
+    lines
+}
+
+/// Shows the prompt and blocks until an answer `check` accepts is given through the
+/// `answer` command, returns `None` if none was given before the deadline.
+fn await_answer(
+    id: usize,
+    prompts: &Prompts,
+    lines: Vec<String>,
+    query: Option<Query>,
+    deadline: Option<Instant>,
+    check: impl Fn(&str) -> Result<(), String>,
+) -> Option<String> {
+    let cli = CLI.get().unwrap();
+    for line in &lines {
+        cli.println(line.as_str());
+    }
+    let (sender, answers) = mpsc::channel();
+    prompts.lock().unwrap().insert(
+        id,
+        Prompt {
+            lines,
+            query,
+            answers: sender,
+        },
+    );
+    loop {
+        // the sender stays registered until a valid answer arrives
+        let answer = match deadline {
+            Some(deadline) => {
+                match answers.recv_timeout(deadline.saturating_duration_since(Instant::now())) {
+                    Ok(answer) => answer,
+                    Err(_) => {
+                        prompts.lock().unwrap().remove(&id);
+                        return None;
+                    }
+                }
+            }
+            None => answers.recv().unwrap(),
+        };
+        match check(&answer) {
+            Ok(()) => {
+                prompts.lock().unwrap().remove(&id);
+                return Some(answer);
+            }
+            Err(err) => cli.println(format!("{}, try again", err).as_str()),
+        }
+    }
+}
+
+struct CmdAnswer;
+
+impl CommandImpl for CmdAnswer {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let id = input[0].parse::<usize>()?;
+        let answer = input[1..].join(" ");
+        // the hosts of joined games validate the answers themselves
+        if let Some(stream) = ctx.remotes.lock().unwrap().get_mut(&id) {
+            // no answer starts with these, so they can be used to chat while being asked
+            let msg = match input[1] {
+                "say" | "emote" => ClientMsg::Chat {
+                    text: input[2..].join(" "),
+                    emote: input[1] == "emote",
+                },
+                _ => ClientMsg::Answer { answer },
+            };
+            return net::send(stream, &msg);
+        }
+        let prompts = ctx.prompts.lock().unwrap();
+        let prompt = prompts
+            .get(&id)
+            .ok_or_else(|| anyhow::anyhow!("Session {} isn't waiting for an answer", id))?;
+        prompt.answers.send(answer)?;
+        Ok(())
+    }
+}
+
+/// adds the result of the finished game to the persistent stats
+fn record_result(game: &GameCtx) -> anyhow::Result<()> {
+    // sessions finishing at the same time mustn't overwrite each other's results
+    static STATS: Mutex<()> = Mutex::new(());
+    let _guard = STATS.lock().unwrap();
+    let mut stats = Stats::load()?;
+    stats.record(&game.result());
+    stats.save()
+}
+
+struct CmdPause;
+
+impl CommandImpl for CmdPause {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let game = ctx.running_session(input[0])?;
+        let msg = if game.control.pause() {
+            "The game will pause at the next safe point"
+        } else {
+            "The game is already paused"
+        };
+        CLI.get().unwrap().println(msg);
+        Ok(())
+    }
+}
+
+struct CmdResume;
+
+impl CommandImpl for CmdResume {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let game = ctx.running_session(input[0])?;
+        let msg = if game.control.resume() {
+            "Resumed the game"
+        } else {
+            "The game isn't paused"
+        };
+        CLI.get().unwrap().println(msg);
+        Ok(())
+    }
+}
+
+struct CmdSave;
+
+impl CommandImpl for CmdSave {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let game = ctx.running_session(input[0])?;
+        let saves = game.control.request_save();
+        let id = input[0].to_string();
+        let slot = input[1].to_string();
+        // the game might be waiting for a player, so it only gets saved once it can be
+        thread::spawn(move || {
+            let cli = CLI.get().unwrap();
+            let Ok(save) = saves.recv() else {
+                // the game ended before it could be saved
+                return;
+            };
+            match save.save(&slot) {
+                Ok(()) => cli.println(format!("Session {}: saved to {}", id, slot).as_str()),
+                Err(err) => cli.println(
+                    format!("Session {}: saving to {} failed: {}", id, slot, err).as_str(),
+                ),
+            }
+        });
+        CLI.get()
+            .unwrap()
+            .println("The game will be saved at the next safe point");
+        Ok(())
+    }
+}
+
+struct CmdUndo;
+
+impl CommandImpl for CmdUndo {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let game = ctx.running_session(input[0])?;
+        report_restore(input[0], game.control.request_undo());
+        Ok(())
+    }
+}
+
+struct CmdRedo;
+
+impl CommandImpl for CmdRedo {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let game = ctx.running_session(input[0])?;
+        report_restore(input[0], game.control.request_redo());
+        Ok(())
+    }
+}
+
+/// reports the turn an undo or a redo went to once the session got to it
+fn report_restore(id: &str, turns: Receiver<Result<usize, String>>) {
+    let id = id.to_string();
+    thread::spawn(move || {
+        let cli = CLI.get().unwrap();
+        let msg = match turns.recv() {
+            Ok(Ok(turn)) => format!("Session {}: went to turn {}", id, turn),
+            Ok(Err(err)) => format!("Session {}: {}", id, err),
+            // the game ended before it got to it
+            Err(_) => return,
+        };
+        cli.println(msg.as_str());
+    });
+    CLI.get()
+        .unwrap()
+        .println("The turn will be changed at the next safe point");
+}
+
+struct CmdLoad;
+
+impl CommandImpl for CmdLoad {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let save = SaveGame::load(input[0])?;
+        let session = Game::load(&save.game)?.resume(&save)?;
+        let id = run_session(ctx, session, |session, id| {
+            play_locally(session, id, &ctx.prompts, HashMap::new())
+        })?;
+        CLI.get()
+            .unwrap()
+            .println(format!("Resumed {} from {} as session {}", save.game, input[0], id).as_str());
+        Ok(())
+    }
+}
+
+struct CmdHost;
+
+impl CommandImpl for CmdHost {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let template = GameTemplate::load(input[0])?;
+        let ws_port = match input.get(2) {
+            Some(port) => Some(port.parse::<u16>()?),
+            None => None,
+        };
+        let lobby = Lobby::host(&template, input[1].parse::<u16>()?, ws_port)?;
+        let id = ctx.next_session.fetch_add(1, Ordering::Relaxed);
+        ctx.lobbies.lock().unwrap().insert(id, lobby);
+        CLI.get().unwrap().println(
+            format!(
+                "Hosting {} on port {} as lobby {}, start it with `start {}` once all are ready",
+                input[0], input[1], id, id
+            )
+            .as_str(),
+        );
+        Ok(())
+    }
+}
+
+impl CliCtx {
+    fn lobby(&self, id: &str) -> anyhow::Result<Arc<Lobby>> {
+        let id = id.parse::<usize>()?;
+        self.lobbies
+            .lock()
+            .unwrap()
+            .get(&id)
+            .cloned()
+            .ok_or_else(|| anyhow::anyhow!("There is no lobby with id {}", id))
+    }
+}
+
+struct CmdLobby;
+
+impl CommandImpl for CmdLobby {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let lobby = ctx.lobby(input[0])?;
+        let cli = CLI.get().unwrap();
+        let players = lobby.players();
+        cli.println(format!("{} ({} players):", lobby.game(), players.len()).as_str());
+        for player in players {
+            let ready = if player.ready { "ready" } else { "not ready" };
+            cli.println(format!("{} ({})", player.name, ready).as_str());
+        }
+        Ok(())
+    }
+}
+
+struct CmdPick;
+
+impl CommandImpl for CmdPick {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let lobby = ctx.lobby(input[0])?;
+        lobby.pick(&GameTemplate::load(input[1])?)?;
+        CLI.get()
+            .unwrap()
+            .println(format!("Lobby {} will play {}", input[0], input[1]).as_str());
+        Ok(())
+    }
+}
+
+struct CmdStart;
+
+impl CommandImpl for CmdStart {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let lobby = ctx.lobby(input[0])?;
+        let game = Game::load(&lobby.game())?;
+        let session = game.start(lobby.start()?)?;
+        ctx.lobbies
+            .lock()
+            .unwrap()
+            .remove(&input[0].parse::<usize>()?);
+        let id = run_session(ctx, session, |session, _| lobby.play(session))?;
+        CLI.get()
+            .unwrap()
+            .println(format!("Started {} as session {}", lobby.game(), id).as_str());
+        Ok(())
+    }
+}
+
+struct CmdJoin;
+
+impl CommandImpl for CmdJoin {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let id = ctx.next_session.fetch_add(1, Ordering::Relaxed);
+        let token = input.get(2).map(|token| token.to_string());
+        let stream = net::join(id, input[0], input[1].to_string(), token)?;
+        ctx.remotes.lock().unwrap().insert(id, stream);
+        CLI.get().unwrap().println(
+            format!(
+                "Joined {} as game {}, chat using `answer {} say <text>` or `answer {} emote <text>`",
+                input[0], id, id, id
+            )
+            .as_str(),
+        );
+        Ok(())
+    }
+}
+
+struct CmdReady;
+
+impl CommandImpl for CmdReady {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let id = input[0].parse::<usize>()?;
+        let ready = match input.get(1) {
+            Some(&"no") => false,
+            Some(option) => return Err(anyhow::anyhow!("Unknown option {}", option)),
+            None => true,
+        };
+        let mut remotes = ctx.remotes.lock().unwrap();
+        let stream = remotes
+            .get_mut(&id)
+            .ok_or_else(|| anyhow::anyhow!("There is no joined game with id {}", id))?;
+        net::send(stream, &ClientMsg::Ready { ready })
+    }
+}
+
+struct CmdSpectate;
+
+impl CommandImpl for CmdSpectate {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let game = ctx.running_session(input[0])?;
+        let all_hands = match input.get(1) {
+            Some(&"--hands") => true,
+            Some(option) => return Err(anyhow::anyhow!("Unknown option {}", option)),
+            None => false,
+        };
+        let spectator = Spectator::attach(game, all_hands);
+        let id = input[0].to_string();
+        // the view is rendered in the background until the game finishes
+        thread::spawn(move || {
+            let cli = CLI.get().unwrap();
+            let render = |spectator: &Spectator| {
+                cli.println(format!("Session {}:", id).as_str());
+                print_table(spectator.game(), &spectator.game().state());
+                print_inventories(spectator.game(), spectator.inventories());
+            };
+            render(&spectator);
+            while let Some(event) = spectator.next_event() {
+                let game = spectator.game();
+                let players = game
+                    .state()
+                    .players
+                    .iter()
+                    .map(|player| player.name.clone())
+                    .collect::<Vec<_>>();
+                cli.println(
+                    format!(
+                        "Session {}: {}",
+                        id,
+                        describe_event(&game.game, &players, &event)
+                    )
+                    .as_str(),
+                );
+                if let GameEvent::TurnStarted { .. } | GameEvent::Restored { .. } = event {
+                    render(&spectator);
+                }
+            }
+        });
+        Ok(())
+    }
+}
+
+struct CmdStatus;
+
+impl CommandImpl for CmdStatus {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let game = ctx.session(input[0])?;
+        CLI.get()
+            .unwrap()
+            .println(format!("{} ({})", game.game.name, session_state(&game)).as_str());
+        let state = game.state();
+        print_table(&game, &state);
+        let viewer = viewer(&state, input.get(1).copied())?;
+        print_inventories(&game, state.inventories_for(viewer));
+        Ok(())
+    }
+}
+
+/// the player called `name`, without a player only what's public is revealed
+fn viewer(state: &GameState, name: Option<&str>) -> anyhow::Result<Option<usize>> {
+    match name {
+        Some(name) => Ok(Some(
+            state
+                .players
+                .iter()
+                .position(|player| player.name == name)
+                .ok_or_else(|| anyhow::anyhow!("There is no player called {}", name))?,
+        )),
+        None => Ok(None),
+    }
+}
+
+struct CmdTui;
+
+impl CommandImpl for CmdTui {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let game = ctx.session(input[0])?;
+        let viewer = viewer(&game.state(), input.get(1).copied())?;
+        tui::show(input[0].parse()?, &game, viewer, &ctx.prompts)
+    }
+}
+
+struct CmdGui;
+
+impl CommandImpl for CmdGui {
+    type CTX = CliCtx;
+
+    #[cfg(feature = "gui")]
+    fn execute(&self, ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let game = ctx.session(input[0])?;
+        let viewer = viewer(&game.state(), input.get(1).copied())?;
+        gui::show(input[0].parse()?, game, viewer, ctx.prompts.clone())
+    }
+
+    #[cfg(not(feature = "gui"))]
+    fn execute(&self, _ctx: &Self::CTX, _input: &[&str]) -> anyhow::Result<()> {
+        Err(anyhow::anyhow!(
+            "The gui feature wasn't enabled when play-cards was built"
+        ))
+    }
+}
+
+/// prints the current turn, the discard pile and the players
+fn print_table(game: &GameCtx, state: &GameState) {
+    let cli = CLI.get().unwrap();
+    cli.println(format!("turn: {}", state.turns).as_str());
+    if let Some(card) = state.discard_top() {
+        cli.println(
+            format!(
+                "discard pile: {} cards, {} on top",
+                state.discard_pile.len(),
+                game.game.cards[card].name
+            )
+            .as_str(),
+        );
+    }
+    for (idx, player) in state.players.iter().enumerate() {
+        cli.println(
+            format!(
+                "{}{}{}",
+                player.name,
+                if idx == state.curr_player {
+                    " (current)"
+                } else {
+                    ""
+                },
+                if player.active { "" } else { " (inactive)" }
+            )
+            .as_str(),
+        );
+    }
+}
+
+fn print_inventories(game: &GameCtx, inventories: Vec<InventoryView>) {
+    let cli = CLI.get().unwrap();
+    for line in inventory_lines(game, inventories) {
+        cli.println(line.as_str());
+    }
+}
+
+fn inventory_lines(game: &GameCtx, inventories: Vec<InventoryView>) -> Vec<String> {
+    inventories
+        .into_iter()
+        .enumerate()
+        .map(|(idx, inv)| {
+            let cards = match inv {
+                InventoryView::Visible(cards) => cards
+                    .iter()
+                    .map(|card| game.game.cards[*card].name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", "),
+                InventoryView::Hidden(cards) => format!("{} face down cards", cards),
+            };
+            format!("inventory {}: {}", idx, cards)
+        })
+        .collect()
+}
+
+fn session_state(game: &GameCtx) -> &'static str {
+    if game.finished.load(Ordering::Acquire) {
+        "finished"
+    } else if game.control.is_paused() {
+        "paused"
+    } else {
+        "running"
+    }
+}
+
+struct CmdStats;
+
+impl CommandImpl for CmdStats {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let cli = CLI.get().unwrap();
+        let stats = Stats::load()?;
+        let mut games = stats
+            .games
+            .iter()
+            .filter(|(game, _)| input.first().map_or(true, |name| *name == game.as_str()))
+            .collect::<Vec<_>>();
+        if games.is_empty() {
+            cli.println("No games were recorded yet");
+            return Ok(());
+        }
+        games.sort_unstable_by_key(|(game, _)| *game);
+        for (game, players) in games {
+            cli.println(format!("{}:", game).as_str());
+            let mut players = players.iter().collect::<Vec<_>>();
+            players.sort_unstable_by(|(_, a), (_, b)| b.wins.cmp(&a.wins));
+            for (name, player) in players {
+                cli.println(
+                    format!(
+                        "  {}: {} games, {} wins, {} losses, {} draws, {:.2} average score",
+                        name,
+                        player.games,
+                        player.wins,
+                        player.losses,
+                        player.draws(),
+                        player.total_score / player.games as f64
+                    )
+                    .as_str(),
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+struct CmdLeaderboard;
+
+impl CommandImpl for CmdLeaderboard {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let cli = CLI.get().unwrap();
+        let stats = Stats::load()?;
+        let leaderboard = stats.leaderboard(input[0]);
+        if leaderboard.is_empty() {
+            cli.println(format!("No games of {} were recorded yet", input[0]).as_str());
+            return Ok(());
+        }
+        for (rank, (name, player)) in leaderboard.into_iter().enumerate() {
+            cli.println(
+                format!(
+                    "{}. {}: {:.0} ({:.1}% of {} games won)",
+                    rank + 1,
+                    name,
+                    player.rating,
+                    player.wins as f64 * 100.0 / player.games as f64,
+                    player.games
+                )
+                .as_str(),
+            );
+        }
+        Ok(())
+    }
+}
+
+struct CmdSessions;
+
+impl CommandImpl for CmdSessions {
+    type CTX = CliCtx;
+
+    fn execute(&self, ctx: &Self::CTX, _input: &[&str]) -> anyhow::Result<()> {
+        let cli = CLI.get().unwrap();
+        let sessions = ctx.sessions.lock().unwrap();
+        if sessions.is_empty() {
+            cli.println("There are no sessions");
+            return Ok(());
+        }
+        let mut ids = sessions.keys().copied().collect::<Vec<_>>();
+        ids.sort_unstable();
+        for id in ids {
+            let game = &sessions[&id];
+            cli.println(
+                format!(
+                    "{}: {} with {} players ({})",
+                    id,
+                    game.game.name,
+                    game.state().players.len(),
+                    session_state(game)
+                )
+                .as_str(),
+            );
+        }
+        Ok(())
+    }
+}
+
+struct CmdReplay;
+
+impl CommandImpl for CmdReplay {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let replay = Replay::load(input[0])?;
+        let mut session = Game::load(&replay.game)?.replay(&replay)?;
+        let cli = CLI.get().unwrap();
+        // the turn which started during the last step
+        let started = Arc::new(Mutex::new(None));
+        let turns = started.clone();
+        session.on_event(move |game, event| {
+            if let GameEvent::TurnStarted { turn, player } = event {
+                *turns.lock().unwrap() = Some(format!(
+                    "Turn {} ({}), press enter to continue",
+                    turn,
+                    game.state().players[*player].name
+                ));
+            }
+        });
+        // wait for confirmation before each turn in order to let the user follow the game
+        while session.step()? {
+            let turn = started.lock().unwrap().take();
+            if let Some(turn) = turn {
+                cli.println(turn.as_str());
+                io::stdin().read_line(&mut String::new())?;
+            }
+        }
+        cli.println(format!("Finished replay {}", input[0]).as_str());
+        Ok(())
+    }
+}
+
+struct CmdLog;
+
+impl CommandImpl for CmdLog {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let replay = Replay::load(input[0])?;
+        let game = GameTemplate::load(&replay.game)?;
+        let log = fs::read_to_string(format!("{}{}.jsonl", LOGS_DIR, input[0]))?;
+        let cli = CLI.get().unwrap();
+        for line in log.lines() {
+            let event = serde_json::from_str::<GameEvent>(line)?;
+            // wait for confirmation before each turn in order to let the user follow the game
+            if let GameEvent::TurnStarted { .. } = event {
+                cli.println("Press enter to continue");
+                io::stdin().read_line(&mut String::new())?;
+            }
+            cli.println(describe_event(&game, &replay.players, &event).as_str());
+        }
+        cli.println(format!("Finished log {}", input[0]).as_str());
+        Ok(())
+    }
+}
+
+struct CmdAudit;
+
+impl CommandImpl for CmdAudit {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let path = format!("{}{}.audit.jsonl", LOGS_DIR, input[0]);
+        let log = fs::read_to_string(&path)
+            .map_err(|err| anyhow::anyhow!("{} couldn't be read: {}", path, err))?;
+        let cli = CLI.get().unwrap();
+        let mut calls = 0;
+        for line in log.lines() {
+            let call = serde_json::from_str::<HostCall>(line)?;
+            if input.get(1).is_some_and(|name| *name != call.name) {
+                continue;
+            }
+            calls += 1;
+            let args = call
+                .args
+                .iter()
+                .map(describe_value)
+                .collect::<Vec<_>>()
+                .join(", ");
+            let result = match &call.result {
+                Ok(val) => describe_value(val),
+                Err(err) => format!("failed: {}", err),
+            };
+            cli.println(
+                format!(
+                    "{:>8}: {}({}) -> {}",
+                    call.executed, call.name, args, result
+                )
+                .as_str(),
+            );
+        }
+        cli.println(format!("Found {} host calls in {}", calls, input[0]).as_str());
+        Ok(())
+    }
+}
+
+/// Checks the script of a game for code which most likely doesn't do what its author meant.
+struct CmdLint;
+
+impl CommandImpl for CmdLint {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let game = Game::load(input[0])?;
+        let cli = CLI.get().unwrap();
+        for lint in game.lints() {
+            cli.println(format!("Warning: {}", lint).as_str());
+        }
+        cli.println(format!("Found {} warnings in {}", game.lints().len(), input[0]).as_str());
+        Ok(())
+    }
+}
+
+/// renders chat messages in a way that sets them apart from the game's output
+fn chat_line(from: &str, text: &str, emote: bool) -> String {
+    if emote {
+        format!("* {} {}", from, text)
+            .magenta()
+            .italic()
+            .to_string()
+    } else {
+        format!("<{}> {}", from, text).cyan().to_string()
+    }
+}
+
+fn describe_event(game: &GameTemplate, players: &[String], event: &GameEvent) -> String {
+    let zone = |zone: &Zone| match zone {
+        Zone::DrawStack => "the draw stack".to_string(),
+        Zone::DiscardPile => "the discard pile".to_string(),
+        Zone::Inventory(inv) => format!("inventory {}", inv),
+    };
+    match event {
+        GameEvent::TurnStarted { turn, player } => {
+            format!("Turn {} ({})", turn, players[*player])
+        }
+        GameEvent::RoundStarted { round } => format!("Round {} started", round),
+        GameEvent::CardRevealed { card, players: to } => format!(
+            "{} was revealed to {}",
+            game.cards[*card].name,
+            to.iter()
+                .map(|player| players[*player].as_str())
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        GameEvent::CardMoved { card, from, to } => match from {
+            Some(from) => format!(
+                "{} moved from {} to {}",
+                game.cards[*card].name,
+                zone(from),
+                zone(to)
+            ),
+            None => format!("{} was put into {}", game.cards[*card].name, zone(to)),
+        },
+        GameEvent::Answered { player, answer } => {
+            format!("{} answered `{}`", players[*player], answer)
+        }
+        GameEvent::Restored { turn } => format!("The game was set back to turn {}", turn),
+        GameEvent::Disconnected { player } => format!("{} lost the connection", players[*player]),
+        GameEvent::Reconnected { player } => format!("{} is back", players[*player]),
+        GameEvent::Finished {
+            winner: Some(winner),
+        } => format!("{} won", players[*winner]),
+        GameEvent::Finished { winner: None } => "The game ended in a draw".to_string(),
+    }
+}
+
+struct CmdSimulate;
+
+impl CommandImpl for CmdSimulate {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let mut players = None;
+        let mut runs = 100;
+        let mut options = input.iter().skip(1);
+        while let Some(option) = options.next() {
+            let val = options
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("Missing value for option `{option}`"))?;
+            match *option {
+                "--players" => players = Some(val.parse::<usize>()?),
+                "--runs" => runs = val.parse::<usize>()?,
+                _ => return Err(anyhow::anyhow!("Unknown option `{option}`")),
+            }
+        }
+        let players =
+            players.ok_or_else(|| anyhow::anyhow!("The `--players` option is required"))?;
+        if players == 0 || runs == 0 {
+            return Err(anyhow::anyhow!(
+                "Simulations need at least 1 player and 1 run"
+            ));
+        }
+
+        let cli = CLI.get().unwrap();
+        // the script only has to be compiled once for all runs
+        let game = Game::load_with(input[0], vec![silent_println()])?;
+        let mut total_turns = 0;
+        let mut wins = vec![0; players];
+        let mut undecided = 0;
+        let mut aborted = 0;
+        for _ in 0..runs {
+            let session = game.start((1..=players).map(|idx| format!("Bot {idx}")).collect())?;
+            let game = session.ctx().clone();
+            // a panicking script shouldn't take down the whole simulation
+            let result = panic::catch_unwind(AssertUnwindSafe(|| session.run()));
+            if !matches!(result, Ok(Ok(_))) {
+                aborted += 1;
+            } else {
+                let state = game.state();
+                total_turns += state.turns;
+                match state.winner {
+                    Some(winner) => wins[winner] += 1,
+                    None => undecided += 1,
+                }
+            }
+        }
+
+        let finished = runs - aborted;
+        cli.println(format!("Simulated {} runs of {}:", runs, input[0]).as_str());
+        // aborted runs have no outcome, so they don't count towards the averages
+        if finished != 0 {
+            cli.println(
+                format!(
+                    "average length: {:.2} turns",
+                    total_turns as f64 / finished as f64
+                )
+                .as_str(),
+            );
+            for (idx, wins) in wins.iter().enumerate() {
+                cli.println(
+                    format!(
+                        "Bot {}: {} wins ({:.1}%)",
+                        idx + 1,
+                        wins,
+                        *wins as f64 * 100.0 / finished as f64
+                    )
+                    .as_str(),
+                );
+            }
+        }
+        cli.println(format!("undecided: {}", undecided).as_str());
+        cli.println(format!("aborted: {} of {} runs", aborted, runs).as_str());
+        Ok(())
+    }
+}
+
+struct CmdTournament;
+
+impl CommandImpl for CmdTournament {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let game = Game::load(input[0])?;
+        let mut entrants = vec![];
+        let mut format = Format::RoundRobin;
+        let mut args = input.iter().skip(1);
+        while let Some(arg) = args.next() {
+            if let Some(option) = arg.strip_prefix("--") {
+                let val = args
+                    .next()
+                    .ok_or_else(|| anyhow::anyhow!("Missing value for option `{arg}`"))?;
+                match option {
+                    "format" => format = Format::parse(val)?,
+                    _ => return Err(anyhow::anyhow!("Unknown option `{arg}`")),
+                }
+                continue;
+            }
+            entrants.push(Entrant::parse(arg));
+        }
+        let tournament = Tournament::new(game, entrants, format)?;
+        CLI.get()
+            .unwrap()
+            .println(format!("Started a tournament of {}", input[0]).as_str());
+        // the matches are played one after another while the cli stays usable
+        thread::spawn(move || tournament.run(CTX.get().unwrap()));
+        Ok(())
+    }
+}
+
+struct CmdCreate;
+
+impl CommandImpl for CmdCreate {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let game_name = input[0].to_string();
+        let code_path = input[1].to_string();
+        let min_players = input[2].parse::<usize>()?;
+        let max_players = input[3].parse::<usize>()?;
+        let cards = input
+            .iter()
+            .skip(4)
+            .map(|card| card_entry(card))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let game = GameTemplate {
+            name: game_name,
+            max_players,
+            min_players,
+            cards: vec![],
+            card_paths: cards,
+            code_path,
+            undo: UndoPolicy::default(),
+            turn_time: None,
+            card_back_path: None,
+            card_back: Arc::new(DynamicImage::default()),
+            engine_version: engine::LANGUAGE_VERSION,
+            capabilities: None,
+        };
+        templates::write(templates::find(GAMES_DIR, input[0]), &game)?;
+        CLI.get()
+            .unwrap()
+            .println(format!("Successfully created {}", input[0]).as_str());
+        Ok(())
+    }
+}
+
+/// `<path>*<count>` puts multiple copies of a card into the deck
+fn card_entry(card: &str) -> anyhow::Result<CardEntry> {
+    Ok(match card.rsplit_once('*') {
+        Some((path, count)) => CardEntry::Counted {
+            path: path.to_string(),
+            count: count.parse::<usize>()?,
+        },
+        None => CardEntry::Path(card.to_string()),
+    })
+}
+
+/// Changes an existing game. Options: `--min <players>`, `--max <players>`,
+/// `--code <code path>`, `--add <card path>` and `--remove <card path>`. The game is loaded
+/// afterwards and left unchanged if that fails.
+struct CmdEditGame;
+
+impl CommandImpl for CmdEditGame {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let path = templates::existing(GAMES_DIR, "game", input[0])?;
+        let old = fs::read_to_string(&path)?;
+        let mut game: GameTemplate = templates::read(&path)?;
+        let mut options = input[1..].iter();
+        while let Some(option) = options.next() {
+            let val = options
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("Missing value for option `{option}`"))?;
+            match *option {
+                "--min" => game.min_players = val.parse()?,
+                "--max" => game.max_players = val.parse()?,
+                "--code" => game.code_path = val.to_string(),
+                "--add" => game.card_paths.push(card_entry(val)?),
+                "--remove" => {
+                    let idx = game
+                        .card_paths
+                        .iter()
+                        .position(|entry| entry.path() == *val)
+                        .ok_or_else(|| {
+                            anyhow::anyhow!("{} doesn't use the card {val}", game.name)
+                        })?;
+                    game.card_paths.remove(idx);
+                }
+                _ => return Err(anyhow::anyhow!("Unknown option `{option}`")),
+            }
+        }
+        if game.min_players == 0 || game.min_players > game.max_players {
+            return Err(anyhow::anyhow!(
+                "{} to {} players isn't a valid player limit",
+                game.min_players,
+                game.max_players
+            ));
+        }
+        templates::write(&path, &game)?;
+        // the cards have to exist and the script has to compile
+        if let Err(err) = Game::load(input[0]) {
+            fs::write(&path, old)?;
+            return Err(anyhow::anyhow!("{} was left unchanged: {}", input[0], err));
+        }
+        CLI.get()
+            .unwrap()
+            .println(format!("Updated {}", input[0]).as_str());
+        Ok(())
+    }
+}
+
+/// Checks everything a game needs to be played and reports all problems at once: the
+/// templates, the cards' images, the ordinals, the player limits and the script.
+struct CmdValidate;
+
+impl CommandImpl for CmdValidate {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let path = templates::existing(GAMES_DIR, "game", input[0])?;
+        let mut game: GameTemplate = templates::read(&path)?;
+        let mut problems = vec![];
+        if game.min_players == 0 || game.min_players > game.max_players {
+            problems.push(format!(
+                "{} to {} players isn't a valid player limit",
+                game.min_players, game.max_players
+            ));
+        }
+        if game.card_paths.is_empty() {
+            problems.push("the deck has no cards".to_string());
+        }
+        let mut cards = vec![];
+        for entry in game.card_paths.iter() {
+            if entry.count() == 0 {
+                problems.push(format!("{} is in the deck 0 times", entry.path()));
+            }
+            let mut card: CardTemplate = match templates::read(entry.path()) {
+                Ok(card) => card,
+                Err(err) => {
+                    problems.push(format!("{}: {}", entry.path(), err));
+                    continue;
+                }
+            };
+            card.migrate();
+            if let Err(err) = card.load_image() {
+                problems.push(err.to_string());
+            }
+            if let Some(other) = cards.iter().find(|other| ordinals_clash(other, &card)) {
+                problems.push(format!(
+                    "{} and {} have the same ordinal {}",
+                    other.name, card.name, card.ord
+                ));
+            }
+            cards.push(card);
+        }
+        if let Err(err) = game.load_card_back() {
+            problems.push(err.to_string());
+        }
+        match fs::read_to_string(&game.code_path) {
+            Ok(src) => {
+                if let Err(err) = Game::new(game, &src, vec![]) {
+                    problems.push(format!("the script doesn't compile: {}", err));
+                }
+            }
+            Err(err) => problems.push(format!("{}: {}", game.code_path, err)),
+        }
+
+        let cli = CLI.get().unwrap();
+        if problems.is_empty() {
+            cli.println(format!("{} is ready to be played", input[0]).as_str());
+            return Ok(());
+        }
+        cli.println(format!("{} has {} problems:", input[0], problems.len()).as_str());
+        for problem in problems {
+            cli.println(format!("  {}", problem).as_str());
+        }
+        Ok(())
+    }
+}
+
+struct CmdRun;
+
+impl CommandImpl for CmdRun {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let src = fs::read_to_string(input[0])
+            .map_err(|err| anyhow::anyhow!("{} couldn't be read: {}", input[0], err))?;
+        // only the engine's builtins are available, there is no game to act on
+        let program = engine::compile(&src, vec![])?;
+        let mut vm = program.instantiate();
+        if let engine::Status::Suspended(_) = vm.run()? {
+            return Err(anyhow::anyhow!(
+                "The script was suspended, which scripts without a game can't be"
+            ));
+        }
+        // scripts can't return values from their main code, so a `main` function
+        // provides the result
+        let cli = CLI.get().unwrap();
+        if program.has_fn("main") {
+            let val = engine::RtValue::from_ref(vm.call("main", &[])?);
+            cli.println(format!("main returned {}", describe_value(&val)).as_str());
+        } else {
+            cli.println("The script finished");
+        }
+        Ok(())
+    }
+}
+
+/// `val` the way scripts write it
+fn describe_value(val: &engine::RtValue) -> String {
+    match val {
+        engine::RtValue::None => "Null".to_string(),
+        engine::RtValue::Decimal(val) => val.to_string(),
+        engine::RtValue::Bool(val) => val.to_string(),
+        engine::RtValue::String(val) => format!("{:?}", val),
+        engine::RtValue::Function(idx) => format!("function {}", idx),
+        engine::RtValue::List(vals) => format!(
+            "[{}]",
+            vals.iter()
+                .map(describe_value)
+                .collect::<Vec<_>>()
+                .join(", ")
+        ),
+        engine::RtValue::Player(player) => format!("player {}", player.idx()),
+        engine::RtValue::Inventory(inv) => format!("inventory {}", inv),
+        engine::RtValue::Card(card) => format!("card {}", card.idx()),
+    }
+}
+
+/// Searches the names of games and cards and the values of the cards' suits, ranks, tags
+/// and metadata for something like the query.
+struct CmdFind;
+
+impl CommandImpl for CmdFind {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let query = input[0];
+        // every hit with its score and where it was found
+        let mut hits = vec![];
+        for name in templates::names(GAMES_DIR) {
+            if let Some(score) = templates::fuzzy_score(query, &name) {
+                hits.push((score, format!("game {}", name)));
+            }
+        }
+        for name in templates::names(CARDS_DIR) {
+            // cards which can't be read are reported by `validate`
+            let Ok(mut card) = templates::read::<CardTemplate>(templates::find(CARDS_DIR, &name))
+            else {
+                continue;
+            };
+            card.migrate();
+            let mut fields = vec![("name".to_string(), card.name.clone())];
+            fields.extend(card.suit.map(|suit| ("suit".to_string(), suit)));
+            fields.extend(card.rank.map(|rank| ("rank".to_string(), rank)));
+            fields.extend(card.tags.into_iter().map(|tag| ("tag".to_string(), tag)));
+            fields.extend(card.metadata);
+            // only the best field of each card is shown
+            let best = fields
+                .into_iter()
+                .filter_map(|(field, val)| {
+                    templates::fuzzy_score(query, &val).map(|score| (score, field, val))
+                })
+                .min();
+            if let Some((score, field, val)) = best {
+                hits.push((score, format!("card {} ({}: {})", name, field, val)));
+            }
+        }
+        hits.sort();
+        let cli = CLI.get().unwrap();
+        if hits.is_empty() {
+            cli.println(format!("Nothing matches {}", query).as_str());
+        }
+        for (_, hit) in hits {
+            cli.println(hit.as_str());
+        }
+        Ok(())
+    }
+}
+
+struct CmdGames;
+
+impl CommandImpl for CmdGames {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, _input: &[&str]) -> anyhow::Result<()> {
+        let dir = fs::read_dir(GAMES_DIR)?;
+        let mut games = vec![];
+        for game in dir {
+            games.push(game?.path());
+        }
+        CLI.get()
+            .unwrap()
+            .println(format!("Games ({}):", games.len()).as_str());
+        for game_path in games {
+            let game_name = game_path.file_name().unwrap().to_str().unwrap().to_string();
+            let game: GameTemplate = templates::read(game_path)?;
+            CLI.get()
+                .unwrap()
+                .println(format!("{}: {:?}", game_name, game).as_str());
+        }
+        Ok(())
+    }
+}
+
+struct CmdHelp;
+
+impl CommandImpl for CmdHelp {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        match input[0] {
+            "functions" => {
+                let funcs = engine::registry(host_functions());
+                let cli = CLI.get().unwrap();
+                cli.println(format!("Functions scripts can call ({}):", funcs.len()).as_str());
+                for func in funcs {
+                    let mut params = func.params;
+                    if func.var_len {
+                        params.push("..".to_string());
+                    }
+                    cli.println(
+                        format!("  {}({}) - {}", func.name, params.join(", "), func.desc).as_str(),
+                    );
+                }
+                Ok(())
+            }
+            topic => Err(anyhow::anyhow!(
+                "There is no help about `{}`, try functions",
+                topic
+            )),
+        }
+    }
+}
+
+struct CmdCreateCard;
+
+impl CommandImpl for CmdCreateCard {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let name = input[0].to_string();
+        let ord = input[1].parse::<usize>()?;
+        let image_path = match input.get(3) {
+            None => input[2].to_string(),
+            Some(&"--normalize") => normalize_image(input[2], &name)?,
+            Some(option) => return Err(anyhow::anyhow!("Unknown option `{option}`")),
+        };
+        let mut card = CardTemplate {
+            name,
+            ord,
+            image_path,
+            image: Arc::new(DynamicImage::default()),
+            suit: None,
+            rank: None,
+            tags: vec![],
+            metadata: HashMap::new(),
+        };
+        // games can't be played with cards whose image is broken
+        card.load_image()?;
+        templates::write(templates::find(CARDS_DIR, &card.name), &card)?;
+        CLI.get()
+            .unwrap()
+            .println(format!("Created card {}", input[0]).as_str());
+        Ok(())
+    }
+}
+
+/// Changes an existing card. Options: `--ordinal <ordinal>`, `--image <image path>`,
+/// `--set <key>=<value>` to set metadata and `--unset <key>` to remove it.
+struct CmdEditCard;
+
+impl CommandImpl for CmdEditCard {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let path = templates::existing(CARDS_DIR, "card", input[0])?;
+        let mut card: CardTemplate = templates::read(&path)?;
+        card.migrate();
+        let mut options = input[1..].iter();
+        while let Some(option) = options.next() {
+            let val = options
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("Missing value for option `{option}`"))?;
+            match *option {
+                "--ordinal" => {
+                    card.ord = val.parse()?;
+                    check_ordinal(&path, &card)?;
+                }
+                "--image" => {
+                    card.image_path = val.to_string();
+                    card.load_image()?;
+                }
+                "--set" => {
+                    let (key, val) = val
+                        .split_once('=')
+                        .ok_or_else(|| anyhow::anyhow!("`{val}` isn't of the form key=value"))?;
+                    card.metadata.insert(key.to_string(), val.to_string());
+                }
+                "--unset" => {
+                    if card.metadata.remove(*val).is_none() {
+                        return Err(anyhow::anyhow!(
+                            "Card {} has no metadata `{val}`",
+                            card.name
+                        ));
+                    }
+                }
+                _ => return Err(anyhow::anyhow!("Unknown option `{option}`")),
+            }
+        }
+        // nothing is written unless all options are valid
+        templates::write(&path, &card)?;
+        CLI.get()
+            .unwrap()
+            .println(format!("Updated card {}", card.name).as_str());
+        Ok(())
+    }
+}
+
+/// Fails if another card of a game using the card at `path` has the card's ordinal.
+fn check_ordinal(path: &Path, card: &CardTemplate) -> anyhow::Result<()> {
+    for (_, game) in games_using(path)? {
+        for entry in game.card_paths.iter() {
+            if same_file(path, entry.path()) {
+                continue;
+            }
+            let mut other: CardTemplate = templates::read(entry.path())?;
+            other.migrate();
+            if ordinals_clash(&other, card) {
+                return Err(anyhow::anyhow!(
+                    "{} of {} already has the ordinal {}",
+                    other.name,
+                    game.name,
+                    card.ord
+                ));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Cards of different suits may share ordinals, like the ranks of standard decks do, and so
+/// may cards of the same rank, like jokers.
+fn ordinals_clash(card: &CardTemplate, other: &CardTemplate) -> bool {
+    card.ord == other.ord
+        && card.suit == other.suit
+        && !(card.rank.is_some() && card.rank == other.rank)
+}
+
+/// whether both paths lead to the same file, games refer to their cards by any path
+fn same_file(path: &Path, other: &str) -> bool {
+    match (fs::canonicalize(path), fs::canonicalize(other)) {
+        (Ok(path), Ok(other)) => path == other,
+        _ => path == Path::new(other),
+    }
+}
+
+/// the games whose deck contains the card at `path` together with the paths of their
+/// templates
+fn games_using(path: &Path) -> anyhow::Result<Vec<(PathBuf, GameTemplate)>> {
+    let mut games = vec![];
+    for entry in fs::read_dir(GAMES_DIR)? {
+        let game_path = entry?.path();
+        let game: GameTemplate = templates::read(&game_path)?;
+        if game
+            .card_paths
+            .iter()
+            .any(|entry| same_file(path, entry.path()))
+        {
+            games.push((game_path, game));
+        }
+    }
+    Ok(games)
+}
+
+/// Deletes a game, its script and cards are kept.
+struct CmdRemoveGame;
+
+impl CommandImpl for CmdRemoveGame {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        fs::remove_file(templates::existing(GAMES_DIR, "game", input[0])?)?;
+        CLI.get()
+            .unwrap()
+            .println(format!("Deleted {}", input[0]).as_str());
+        Ok(())
+    }
+}
+
+/// Deletes a card, cards which are part of a game's deck are only deleted with `--force`,
+/// which removes them from the decks as well.
+struct CmdRemoveCard;
+
+impl CommandImpl for CmdRemoveCard {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let force = match input.get(1) {
+            None => false,
+            Some(&"--force") => true,
+            Some(option) => return Err(anyhow::anyhow!("Unknown option `{option}`")),
+        };
+        let path = templates::existing(CARDS_DIR, "card", input[0])?;
+        let games = games_using(&path)?;
+        if !games.is_empty() && !force {
+            return Err(anyhow::anyhow!(
+                "{} is still used by {}, pass `--force` to remove it from them as well",
+                input[0],
+                games
+                    .iter()
+                    .map(|(_, game)| game.name.as_str())
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            ));
+        }
+        for (game_path, mut game) in games {
+            game.card_paths
+                .retain(|entry| !same_file(&path, entry.path()));
+            templates::write(game_path, &game)?;
+        }
+        fs::remove_file(&path)?;
+        CLI.get()
+            .unwrap()
+            .println(format!("Deleted card {}", input[0]).as_str());
+        Ok(())
+    }
+}
+
+/// Renames a game, saves and replays of the game keep referring to the old name.
+struct CmdRenameGame;
+
+impl CommandImpl for CmdRenameGame {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        if templates::find(GAMES_DIR, input[1]).exists() {
+            return Err(anyhow::anyhow!(
+                "There already is a game called {}",
+                input[1]
+            ));
+        }
+        let path = templates::existing(GAMES_DIR, "game", input[0])?;
+        let mut game: GameTemplate = templates::read(&path)?;
+        game.name = input[1].to_string();
+        templates::write(templates::renamed(&path, input[1]), &game)?;
+        fs::remove_file(path)?;
+        CLI.get()
+            .unwrap()
+            .println(format!("Renamed {} to {}", input[0], input[1]).as_str());
+        Ok(())
+    }
+}
+
+/// Copies a game under a new name, with `--script` the copy gets its own copy of the script
+/// next to the original, otherwise both games share the script.
+struct CmdCloneGame;
+
+impl CommandImpl for CmdCloneGame {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let copy_script = match input.get(2) {
+            None => false,
+            Some(&"--script") => true,
+            Some(option) => return Err(anyhow::anyhow!("Unknown option `{option}`")),
+        };
+        if templates::find(GAMES_DIR, input[1]).exists() {
+            return Err(anyhow::anyhow!(
+                "There already is a game called {}",
+                input[1]
+            ));
+        }
+        let path = templates::existing(GAMES_DIR, "game", input[0])?;
+        let mut game: GameTemplate = templates::read(&path)?;
+        game.name = input[1].to_string();
+        if copy_script {
+            let code_path = Path::new(&game.code_path);
+            let mut new_code_path = code_path.with_file_name(input[1]);
+            if let Some(ext) = code_path.extension() {
+                new_code_path.set_extension(ext);
+            }
+            if new_code_path.exists() {
+                return Err(anyhow::anyhow!(
+                    "There already is a script at {}",
+                    new_code_path.display()
+                ));
+            }
+            fs::copy(code_path, &new_code_path)?;
+            game.code_path = new_code_path.to_string_lossy().into_owned();
+        }
+        templates::write(templates::renamed(&path, input[1]), &game)?;
+        CLI.get()
+            .unwrap()
+            .println(format!("Copied {} to {}", input[0], input[1]).as_str());
+        Ok(())
+    }
+}
+
+/// Renames a card and updates the decks of the games using it.
+struct CmdRenameCard;
+
+impl CommandImpl for CmdRenameCard {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        if templates::find(CARDS_DIR, input[1]).exists() {
+            return Err(anyhow::anyhow!(
+                "There already is a card called {}",
+                input[1]
+            ));
+        }
+        let path = templates::existing(CARDS_DIR, "card", input[0])?;
+        let new_path = templates::renamed(&path, input[1]);
+        let mut card: CardTemplate = templates::read(&path)?;
+        card.name = input[1].to_string();
+        // the games have to be found while the card is still at its old path
+        let games = games_using(&path)?;
+        templates::write(&new_path, &card)?;
+        let new_path = new_path.to_string_lossy().into_owned();
+        for (game_path, mut game) in games {
+            for entry in game.card_paths.iter_mut() {
+                if same_file(&path, entry.path()) {
+                    *entry = match entry {
+                        CardEntry::Path(_) => CardEntry::Path(new_path.clone()),
+                        CardEntry::Counted { count, .. } => CardEntry::Counted {
+                            path: new_path.clone(),
+                            count: *count,
+                        },
+                    };
+                }
+            }
+            templates::write(game_path, &game)?;
+        }
+        fs::remove_file(path)?;
+        CLI.get()
+            .unwrap()
+            .println(format!("Renamed card {} to {}", input[0], input[1]).as_str());
+        Ok(())
+    }
+}
+
+/// Creates the cards listed in a csv file. Its header names the columns, `name`, `ordinal`
+/// and `image` are required, `suit` and `rank` are optional, `tags` holds the tags separated
+/// by spaces and all other columns are metadata. Empty values are left out.
+struct CmdCreateCards;
+
+impl CommandImpl for CmdCreateCards {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let mut reader = csv::Reader::from_path(input[0])?;
+        let headers = reader.headers()?.clone();
+        for required in ["name", "ordinal", "image"] {
+            if !headers.iter().any(|header| header == required) {
+                return Err(anyhow::anyhow!("The csv has no `{}` column", required));
+            }
+        }
+        // all cards are checked before any of them gets written
+        let mut cards = vec![];
+        for (line, record) in reader.records().enumerate() {
+            let record = record?;
+            let mut card = CardTemplate {
+                name: String::new(),
+                ord: 0,
+                image_path: String::new(),
+                image: Arc::new(DynamicImage::default()),
+                suit: None,
+                rank: None,
+                tags: vec![],
+                metadata: HashMap::new(),
+            };
+            for (header, val) in headers.iter().zip(record.iter()) {
+                if val.is_empty() {
+                    continue;
+                }
+                match header {
+                    "name" => card.name = val.to_string(),
+                    "ordinal" => {
+                        card.ord = val.parse().map_err(|_| {
+                            // the header is the first line
+                            anyhow::anyhow!("Line {}: `{}` isn't an ordinal", line + 2, val)
+                        })?
+                    }
+                    "image" => card.image_path = val.to_string(),
+                    "suit" => card.suit = Some(val.to_string()),
+                    "rank" => card.rank = Some(val.to_string()),
+                    "tags" => card.tags = val.split_whitespace().map(str::to_string).collect(),
+                    _ => {
+                        card.metadata.insert(header.to_string(), val.to_string());
+                    }
+                }
+            }
+            if card.name.is_empty() {
+                return Err(anyhow::anyhow!("Line {}: the card has no name", line + 2));
+            }
+            card.load_image()?;
+            cards.push(card);
+        }
+
+        let mut card_paths = vec![];
+        for card in cards.iter() {
+            let path = templates::find(CARDS_DIR, &card.name.replace(' ', "_"));
+            templates::write(&path, card)?;
+            card_paths.push(path.to_string_lossy().into_owned());
+        }
+        let cli = CLI.get().unwrap();
+        cli.println(format!("Created {} cards", cards.len()).as_str());
+        cli.println(format!("card paths: {}", card_paths.join(" ")).as_str());
+        Ok(())
+    }
+}
+
+/// Slices a sprite sheet into a grid of `cols` times `rows` card images and creates a card
+/// for each of them, named and ranked by their position from left to right and top to
+/// bottom. Cells which are fully transparent are left out.
+struct CmdImportSheet;
+
+impl CommandImpl for CmdImportSheet {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let sheet = image::open(input[0]).map_err(|err| {
+            anyhow::anyhow!("The sheet `{}` couldn't be loaded: {}", input[0], err)
+        })?;
+        let cols = input[1].parse::<u32>()?;
+        let rows = input[2].parse::<u32>()?;
+        let prefix = input[3];
+        if cols == 0 || rows == 0 || cols > sheet.width() || rows > sheet.height() {
+            return Err(anyhow::anyhow!(
+                "A sheet of {}x{} pixels can't be sliced into {} columns and {} rows",
+                sheet.width(),
+                sheet.height(),
+                cols,
+                rows
+            ));
+        }
+        // pixels which don't fill a whole cell at the right and bottom edge are cut off
+        let (width, height) = (sheet.width() / cols, sheet.height() / rows);
+        let mut card_paths = vec![];
+        for row in 0..rows {
+            for col in 0..cols {
+                let cell = sheet.crop_imm(col * width, row * height, width, height);
+                if cell.to_rgba8().pixels().all(|pixel| pixel.0[3] == 0) {
+                    continue;
+                }
+                let ord = (row * cols + col + 1) as usize;
+                let name = format!("{}{}", prefix, ord);
+                let image_path = format!("{}{}.png", IMAGES_DIR, name);
+                cell.save_with_format(&image_path, ImageFormat::Png)?;
+                let card = CardTemplate {
+                    name,
+                    ord,
+                    image_path,
+                    image: Arc::new(DynamicImage::default()),
+                    suit: None,
+                    rank: None,
+                    tags: vec![],
+                    metadata: HashMap::new(),
+                };
+                let path = templates::find(CARDS_DIR, &card.name);
+                templates::write(&path, &card)?;
+                card_paths.push(path.to_string_lossy().into_owned());
+            }
+        }
+        let cli = CLI.get().unwrap();
+        cli.println(format!("Created {} cards", card_paths.len()).as_str());
+        cli.println(format!("card paths: {}", card_paths.join(" ")).as_str());
+        Ok(())
+    }
+}
+
+/// Shows the image of a card in the terminal, the protocol (`kitty`, `sixel` or `ascii`) is
+/// guessed from the terminal unless it's passed.
+struct CmdShowCard;
+
+impl CommandImpl for CmdShowCard {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let mut card: CardTemplate =
+            templates::read(templates::existing(CARDS_DIR, "card", input[0])?)?;
+        if card.image_path.is_empty() {
+            return Err(anyhow::anyhow!("Card {} has no image", card.name));
+        }
+        card.load_image()?;
+        let protocol = match input.get(1) {
+            Some(protocol) => Protocol::parse(protocol)?,
+            None => Protocol::detect(),
+        };
+        preview::show(&card.image, protocol)
+    }
+}
+
+const SUITS: [(&str, &str); 4] = [
+    ("Hearts", "red"),
+    ("Diamonds", "red"),
+    ("Clubs", "black"),
+    ("Spades", "black"),
+];
+const RANKS: [&str; 13] = [
+    "2", "3", "4", "5", "6", "7", "8", "9", "10", "Jack", "Queen", "King", "Ace",
+];
+
+/// Creates the 52 cards of a standard deck ranked from 2 to Ace, plus any number of jokers
+/// ranked above them. Options: `--jokers <count>`, `--<suit> <image path>` to use an image
+/// for all cards of a suit, `--game <name>` to make the deck the cards of a game,
+/// `--back <image path>` to show the game's face down cards with an image and `--normalize`
+/// to store the images cropped to the size of cards in the images directory.
+struct CmdCreateStandardDeck;
+
+impl CommandImpl for CmdCreateStandardDeck {
+    type CTX = CliCtx;
+
+    fn execute(&self, _ctx: &Self::CTX, input: &[&str]) -> anyhow::Result<()> {
+        let mut jokers = 0;
+        let mut images = HashMap::new();
+        let mut game = None;
+        let mut back = None;
+        let mut normalize = false;
+        let mut options = input.iter();
+        while let Some(option) = options.next() {
+            if *option == "--normalize" {
+                normalize = true;
+                continue;
+            }
+            let val = options
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("Missing value for option `{option}`"))?;
+            match *option {
+                "--jokers" => jokers = val.parse::<usize>()?,
+                "--game" => game = Some(val.to_string()),
+                "--back" => back = Some(val.to_string()),
+                _ => match SUITS
+                    .iter()
+                    .find(|(suit, _)| option.strip_prefix("--") == Some(&suit.to_lowercase()))
+                {
+                    Some((suit, _)) => {
+                        images.insert(*suit, val.to_string());
+                    }
+                    None => return Err(anyhow::anyhow!("Unknown option `{option}`")),
+                },
+            }
+        }
+
+        if back.is_some() && game.is_none() {
+            return Err(anyhow::anyhow!("`--back` requires `--game`"));
+        }
+        if normalize {
+            for (suit, path) in images.iter_mut() {
+                *path = normalize_image(path, suit)?;
+            }
+            if let Some(path) = &mut back {
+                *path = normalize_image(path, &format!("{} back", game.as_ref().unwrap()))?;
+            }
+        }
+
+        let mut cards = vec![];
+        for (suit, color) in SUITS {
+            for (ord, rank) in RANKS.iter().enumerate() {
+                cards.push(CardTemplate {
+                    name: format!("{} of {}", rank, suit),
+                    ord: ord + 2,
+                    image_path: images.get(suit).cloned().unwrap_or_default(),
+                    image: Arc::new(DynamicImage::default()),
+                    suit: Some(suit.to_string()),
+                    rank: Some(rank.to_string()),
+                    tags: vec![],
+                    metadata: HashMap::from([("color".to_string(), color.to_string())]),
+                });
+            }
+        }
+        for joker in 1..=jokers {
+            cards.push(CardTemplate {
+                name: format!("Joker {}", joker),
+                ord: RANKS.len() + 2,
+                image_path: String::new(),
+                image: Arc::new(DynamicImage::default()),
+                suit: None,
+                rank: Some("Joker".to_string()),
+                tags: vec!["wild".to_string()],
+                metadata: HashMap::new(),
+            });
+        }
+
+        for card in cards.iter_mut() {
+            card.load_image()?;
+        }
+
+        let mut card_paths = vec![];
+        for card in cards.iter() {
+            // the paths are separated by spaces when passed to `create`
+            let path = templates::find(CARDS_DIR, &card.name.replace(' ', "_"));
+            templates::write(&path, card)?;
+            card_paths.push(path.to_string_lossy().into_owned());
+        }
+        let cli = CLI.get().unwrap();
+        cli.println(format!("Created {} cards", cards.len()).as_str());
+        match game {
+            Some(game) => {
+                let path = templates::existing(GAMES_DIR, "game", &game)?;
+                let mut template: GameTemplate = templates::read(&path)?;
+                template.card_paths = card_paths.into_iter().map(CardEntry::Path).collect();
+                if back.is_some() {
+                    template.card_back_path = back;
+                    template.load_card_back()?;
+                }
+                templates::write(path, &template)?;
+                cli.println(format!("{} now uses the deck", game).as_str());
+            }
+            None => cli.println(format!("card paths: {}", card_paths.join(" ")).as_str()),
+        }
         Ok(())
     }
 }
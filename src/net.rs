@@ -0,0 +1,723 @@
+//! Multiplayer over tcp. Every message is json prefixed with its length as a big endian u32,
+//! browsers can use websockets instead where every text message is a json message.
+//! The host runs the game and only sends the players what they are allowed to see,
+//! clients merely display it and relay the answers of their player.
+
+use std::{
+    collections::hash_map::RandomState,
+    hash::{BuildHasher, Hasher},
+    io::{self, Read, Write},
+    net::{TcpListener, TcpStream},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, Receiver, Sender},
+        Arc, Mutex, OnceLock,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use play_cards::{
+    game_ctx::{GameState, GameTemplate},
+    GameCtx, GameEvent, Query, Session,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tungstenite::{Message, WebSocket};
+
+use crate::{chat_line, describe_event, inventory_lines, prompt_lines, CLI};
+
+/// messages which are larger than this are rejected in order to not allocate arbitrary amounts
+const MAX_MSG_LEN: u32 = 1 << 20;
+
+/// how long the reader of a websocket waits for a message before it lets the writer send
+const WS_POLL: Duration = Duration::from_millis(50);
+
+#[derive(Deserialize, Serialize, Debug)]
+pub enum ClientMsg {
+    /// the first message of every client, players who rejoin a running game have to pass
+    /// the token they got when they joined
+    Join {
+        name: String,
+        #[serde(default)]
+        token: Option<String>,
+    },
+    /// the player is (or isn't) ready for the game to start
+    Ready {
+        ready: bool,
+    },
+    Answer {
+        answer: String,
+    },
+    /// something the player says to the others, emotes describe what they do instead
+    Chat {
+        text: String,
+        emote: bool,
+    },
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub enum ServerMsg {
+    /// who is in the lobby and which game the host picked, sent whenever this changes
+    Lobby {
+        game: String,
+        min_players: usize,
+        max_players: usize,
+        players: Vec<LobbyPlayer>,
+    },
+    /// the client can't join, the connection is closed afterwards
+    Rejected { reason: String },
+    /// the client joined the lobby, only it knows the token it has to rejoin with
+    Joined { token: String },
+    /// something the player is allowed to see happened
+    Event { text: String },
+    /// the table as the player sees it, sent at the start of every turn
+    Table { lines: Vec<String> },
+    /// the player has to make a decision, answers are sent as `ClientMsg::Answer`
+    Prompt { lines: Vec<String> },
+    /// the answer wasn't valid, the player has to answer again
+    Invalid { reason: String },
+    /// a chat message of the player called `from`
+    Chat {
+        from: String,
+        text: String,
+        emote: bool,
+    },
+    /// the game is over, the connection is closed afterwards
+    Finished { result: String },
+}
+
+#[derive(Deserialize, Serialize, Debug)]
+pub struct LobbyPlayer {
+    pub name: String,
+    pub ready: bool,
+}
+
+pub fn send<T: Serialize>(stream: &mut impl Write, msg: &T) -> anyhow::Result<()> {
+    let msg = serde_json::to_vec(msg)?;
+    stream.write_all(&(msg.len() as u32).to_be_bytes())?;
+    stream.write_all(&msg)?;
+    Ok(stream.flush()?)
+}
+
+pub fn recv<T: DeserializeOwned>(stream: &mut impl Read) -> anyhow::Result<T> {
+    let mut len = [0; 4];
+    stream.read_exact(&mut len)?;
+    let len = u32::from_be_bytes(len);
+    if len > MAX_MSG_LEN {
+        return Err(anyhow::anyhow!("The message is too large ({} bytes)", len));
+    }
+    let mut msg = vec![0; len as usize];
+    stream.read_exact(&mut msg)?;
+    Ok(serde_json::from_slice(&msg)?)
+}
+
+/// One half of the connection to a player, the host reads from one half and writes to the
+/// other one on different threads.
+enum Connection {
+    Tcp(TcpStream),
+    /// a websocket keeps the state of the connection, so both halves share it
+    WebSocket(Arc<Mutex<WebSocket<TcpStream>>>),
+}
+
+impl Connection {
+    fn tcp(stream: TcpStream) -> anyhow::Result<(Self, Self)> {
+        Ok((Self::Tcp(stream.try_clone()?), Self::Tcp(stream)))
+    }
+
+    /// performs the websocket handshake
+    fn web_socket(stream: TcpStream) -> anyhow::Result<(Self, Self)> {
+        let socket = tungstenite::accept(stream)
+            .map_err(|err| anyhow::anyhow!("The websocket handshake failed: {}", err))?;
+        // the reader would keep the socket locked while it waits otherwise
+        socket.get_ref().set_read_timeout(Some(WS_POLL))?;
+        let socket = Arc::new(Mutex::new(socket));
+        Ok((Self::WebSocket(socket.clone()), Self::WebSocket(socket)))
+    }
+
+    fn send<T: Serialize>(&mut self, msg: &T) -> anyhow::Result<()> {
+        match self {
+            Connection::Tcp(stream) => send(stream, msg),
+            Connection::WebSocket(socket) => Ok(socket
+                .lock()
+                .unwrap()
+                .send(Message::Text(serde_json::to_string(msg)?))?),
+        }
+    }
+
+    fn recv<T: DeserializeOwned>(&mut self) -> anyhow::Result<T> {
+        match self {
+            Connection::Tcp(stream) => recv(stream),
+            Connection::WebSocket(socket) => loop {
+                // the lock is released between polls, so messages can be sent meanwhile
+                let msg = socket.lock().unwrap().read();
+                match msg {
+                    Ok(Message::Text(msg)) => return Ok(serde_json::from_str(&msg)?),
+                    Ok(Message::Binary(msg)) => return Ok(serde_json::from_slice(&msg)?),
+                    Ok(Message::Close(_)) => {
+                        return Err(anyhow::anyhow!("The connection was closed"))
+                    }
+                    Ok(Message::Ping(_) | Message::Pong(_) | Message::Frame(_)) => {}
+                    // the socket keeps partially read messages until the next poll
+                    Err(tungstenite::Error::Io(err))
+                        if matches!(
+                            err.kind(),
+                            io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut
+                        ) => {}
+                    Err(err) => return Err(err.into()),
+                }
+            },
+        }
+    }
+}
+
+/// A player connected to the host, players who lost their connection can rejoin
+/// under the same name once the game started.
+struct Client {
+    name: String,
+    /// proves that a player who rejoins is the one who lost the connection
+    token: String,
+    /// replaced by the new connection when the player rejoins
+    connection: Mutex<Connection>,
+    /// `sender` stays alive while the player is gone, so they can answer once they are back
+    answers: Mutex<Receiver<String>>,
+    sender: Sender<String>,
+    ready: AtomicBool,
+    connected: Mutex<bool>,
+    /// the prompt the player didn't answer yet, it's sent again when they rejoin
+    prompt: Mutex<Option<Vec<String>>>,
+}
+
+impl Client {
+    /// players who disconnected simply don't receive anything until they rejoin
+    fn send(&self, msg: &ServerMsg) {
+        let _ = self.connection.lock().unwrap().send(msg);
+    }
+}
+
+/// The game the host picked for the lobby.
+struct LobbyGame {
+    name: String,
+    min_players: usize,
+    max_players: usize,
+}
+
+impl LobbyGame {
+    fn new(game: &GameTemplate) -> Self {
+        Self {
+            name: game.name.clone(),
+            min_players: game.min_players,
+            max_players: game.max_players,
+        }
+    }
+}
+
+/// The players waiting for the host to start the game.
+pub struct Lobby {
+    game: Mutex<LobbyGame>,
+    clients: Mutex<Vec<Arc<Client>>>,
+    started: AtomicBool,
+    /// the game the players play once it started
+    session: OnceLock<Arc<GameCtx>>,
+}
+
+impl Lobby {
+    /// Accepts players on `port` in the background until the game is over, once it started
+    /// only players who lost their connection can join. Browsers can join through
+    /// websockets on `ws_port`.
+    pub fn host(game: &GameTemplate, port: u16, ws_port: Option<u16>) -> anyhow::Result<Arc<Self>> {
+        let listener = TcpListener::bind(("0.0.0.0", port))?;
+        let ws_listener = match ws_port {
+            Some(port) => Some(TcpListener::bind(("0.0.0.0", port))?),
+            None => None,
+        };
+        let lobby = Arc::new(Self {
+            game: Mutex::new(LobbyGame::new(game)),
+            clients: Mutex::new(vec![]),
+            started: AtomicBool::new(false),
+            session: OnceLock::new(),
+        });
+        let accepting = lobby.clone();
+        thread::spawn(move || accepting.accept(listener, Connection::tcp));
+        if let Some(listener) = ws_listener {
+            let accepting = lobby.clone();
+            thread::spawn(move || accepting.accept(listener, Connection::web_socket));
+        }
+        Ok(lobby)
+    }
+
+    fn accept(
+        self: Arc<Self>,
+        listener: TcpListener,
+        connect: fn(TcpStream) -> anyhow::Result<(Connection, Connection)>,
+    ) {
+        for stream in listener.incoming() {
+            let Ok(stream) = stream else {
+                continue;
+            };
+            let lobby = self.clone();
+            // a slow client mustn't keep others from joining
+            thread::spawn(move || {
+                if let Err(err) =
+                    connect(stream).and_then(|(reader, writer)| lobby.join(reader, writer))
+                {
+                    CLI.get()
+                        .unwrap()
+                        .println(format!("A player failed to join: {}", err).as_str());
+                }
+            });
+            // the player who tried to join after the game was over was rejected
+            if self
+                .session
+                .get()
+                .is_some_and(|game| game.finished.load(Ordering::Acquire))
+            {
+                break;
+            }
+        }
+    }
+
+    fn join(self: Arc<Self>, mut reader: Connection, mut writer: Connection) -> anyhow::Result<()> {
+        let ClientMsg::Join { name, token } = reader.recv()? else {
+            return Err(anyhow::anyhow!("The client didn't introduce itself"));
+        };
+        let mut clients = self.clients.lock().unwrap();
+        let existing = clients.iter().position(|client| client.name == name);
+        if self.started.load(Ordering::Acquire) {
+            let Some(idx) = existing else {
+                writer.send(&ServerMsg::Rejected {
+                    reason: "the game already started".to_string(),
+                })?;
+                return Ok(());
+            };
+            let client = clients[idx].clone();
+            drop(clients);
+            // everyone knows the names of the players, but only the player knows their token
+            if token.as_ref() != Some(&client.token) {
+                writer.send(&ServerMsg::Rejected {
+                    reason: format!("the token of {} is wrong", name),
+                })?;
+                return Ok(());
+            }
+            return self.rejoin(idx, &client, reader, writer);
+        }
+        let rejection = if clients.len() >= self.game.lock().unwrap().max_players {
+            Some("the game is full".to_string())
+        } else if existing.is_some() {
+            Some(format!("there already is a player called {}", name))
+        } else {
+            None
+        };
+        if let Some(reason) = rejection {
+            writer.send(&ServerMsg::Rejected { reason })?;
+            return Ok(());
+        }
+        let token = rejoin_token();
+        writer.send(&ServerMsg::Joined {
+            token: token.clone(),
+        })?;
+        let (sender, answers) = mpsc::channel();
+        let client = Arc::new(Client {
+            name: name.clone(),
+            token,
+            connection: Mutex::new(writer),
+            answers: Mutex::new(answers),
+            sender,
+            ready: AtomicBool::new(false),
+            connected: Mutex::new(true),
+            prompt: Mutex::new(None),
+        });
+        clients.push(client.clone());
+        drop(clients);
+        let lobby = self.clone();
+        thread::spawn(move || lobby.relay(&client, &mut reader));
+        self.broadcast();
+        CLI.get()
+            .unwrap()
+            .println(format!("{} joined the lobby", name).as_str());
+        Ok(())
+    }
+
+    /// gives the seat of player `idx` back to them, they get to see the table and the
+    /// prompt they didn't answer yet
+    fn rejoin(
+        self: Arc<Self>,
+        idx: usize,
+        client: &Arc<Client>,
+        mut reader: Connection,
+        mut writer: Connection,
+    ) -> anyhow::Result<()> {
+        let mut connected = client.connected.lock().unwrap();
+        // the host might not have noticed that the old connection broke yet
+        if *connected {
+            writer.send(&ServerMsg::Rejected {
+                reason: format!("{} is still connected", client.name),
+            })?;
+            return Ok(());
+        }
+        *connected = true;
+        *client.connection.lock().unwrap() = writer;
+        if let Some(game) = self.session.get() {
+            game.reconnect(idx);
+            client.send(&ServerMsg::Table {
+                lines: table_lines(game, idx),
+            });
+        }
+        drop(connected);
+        if let Some(lines) = client.prompt.lock().unwrap().clone() {
+            client.send(&ServerMsg::Prompt { lines });
+        }
+        let lobby = self.clone();
+        let relayed = client.clone();
+        thread::spawn(move || lobby.relay(&relayed, &mut reader));
+        CLI.get()
+            .unwrap()
+            .println(format!("{} rejoined the game", client.name).as_str());
+        Ok(())
+    }
+
+    /// handles the messages of the player until they disconnect
+    fn relay(&self, client: &Arc<Client>, connection: &mut Connection) {
+        while let Ok(msg) = connection.recv::<ClientMsg>() {
+            match msg {
+                // the receiver lives as long as the client
+                ClientMsg::Answer { answer } => client.sender.send(answer).unwrap(),
+                ClientMsg::Ready { ready } => {
+                    client.ready.store(ready, Ordering::Release);
+                    self.broadcast();
+                }
+                ClientMsg::Chat { text, emote } => self.chat(&client.name, text, emote),
+                ClientMsg::Join { .. } => {}
+            }
+        }
+        let mut connected = client.connected.lock().unwrap();
+        *connected = false;
+        let mut clients = self.clients.lock().unwrap();
+        if self.started.load(Ordering::Acquire) {
+            // players who left a running game keep their seat
+            let idx = clients
+                .iter()
+                .position(|other| Arc::ptr_eq(other, client))
+                .unwrap();
+            drop(clients);
+            if let Some(game) = self.session.get() {
+                game.disconnect(idx);
+            }
+            CLI.get()
+                .unwrap()
+                .println(format!("{} lost the connection", client.name).as_str());
+            return;
+        }
+        // players who leave the lobby free their seat
+        clients.retain(|other| !Arc::ptr_eq(other, client));
+        drop(clients);
+        drop(connected);
+        self.broadcast();
+        CLI.get()
+            .unwrap()
+            .println(format!("{} left the lobby", client.name).as_str());
+    }
+
+    /// passes the chat message on to everyone, including its author
+    fn chat(&self, from: &str, text: String, emote: bool) {
+        CLI.get()
+            .unwrap()
+            .println(chat_line(from, &text, emote).as_str());
+        let msg = ServerMsg::Chat {
+            from: from.to_string(),
+            text,
+            emote,
+        };
+        for client in self.clients.lock().unwrap().iter() {
+            client.send(&msg);
+        }
+    }
+
+    /// tells everyone in the lobby who's in it
+    fn broadcast(&self) {
+        let clients = self.clients.lock().unwrap();
+        let game = self.game.lock().unwrap();
+        let msg = ServerMsg::Lobby {
+            game: game.name.clone(),
+            min_players: game.min_players,
+            max_players: game.max_players,
+            players: clients
+                .iter()
+                .map(|client| LobbyPlayer {
+                    name: client.name.clone(),
+                    ready: client.ready.load(Ordering::Acquire),
+                })
+                .collect(),
+        };
+        for client in clients.iter() {
+            client.send(&msg);
+        }
+    }
+
+    pub fn game(&self) -> String {
+        self.game.lock().unwrap().name.clone()
+    }
+
+    /// the players in the lobby and whether they are ready
+    pub fn players(&self) -> Vec<LobbyPlayer> {
+        self.clients
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|client| LobbyPlayer {
+                name: client.name.clone(),
+                ready: client.ready.load(Ordering::Acquire),
+            })
+            .collect()
+    }
+
+    /// Lets the lobby play `game` instead, which fails if there are too many players for it.
+    /// The players have to ready up again.
+    pub fn pick(&self, game: &GameTemplate) -> anyhow::Result<()> {
+        let clients = self.clients.lock().unwrap();
+        if clients.len() > game.max_players {
+            return Err(anyhow::anyhow!(
+                "{} allows at most {} players, but {} are in the lobby",
+                game.name,
+                game.max_players,
+                clients.len()
+            ));
+        }
+        for client in clients.iter() {
+            client.ready.store(false, Ordering::Release);
+        }
+        drop(clients);
+        *self.game.lock().unwrap() = LobbyGame::new(game);
+        self.broadcast();
+        Ok(())
+    }
+
+    /// Closes the lobby and returns the names of the players in the order they joined,
+    /// which fails unless enough players are there and all of them are ready.
+    pub fn start(&self) -> anyhow::Result<Vec<String>> {
+        let clients = self.clients.lock().unwrap();
+        let game = self.game.lock().unwrap();
+        if clients.len() < game.min_players {
+            return Err(anyhow::anyhow!(
+                "{} needs at least {} players, but {} are in the lobby",
+                game.name,
+                game.min_players,
+                clients.len()
+            ));
+        }
+        if let Some(client) = clients
+            .iter()
+            .find(|client| !client.ready.load(Ordering::Acquire))
+        {
+            return Err(anyhow::anyhow!("{} isn't ready yet", client.name));
+        }
+        self.started.store(true, Ordering::Release);
+        Ok(clients.iter().map(|client| client.name.clone()).collect())
+    }
+
+    /// Lets the players of the lobby play the session, they are its players in the order
+    /// they joined.
+    pub fn play(&self, session: &Session) {
+        let _ = self.session.set(session.ctx().clone());
+        let clients = self.clients.lock().unwrap().clone();
+        let listeners = clients.clone();
+        session.on_event(move |game, event| notify(&listeners, game, event));
+        session.on_query(move |game, query| ask_client(&clients[query.player()], game, query));
+    }
+}
+
+/// a secret only the player who joined gets to know
+fn rejoin_token() -> String {
+    // the keys of `RandomState` are taken from the os' rng
+    let part = || RandomState::new().build_hasher().finish();
+    format!("{:016x}{:016x}", part(), part())
+}
+
+/// sends the event to everyone who may see it, the hidden information never leaves the host.
+/// The messages are sent once the state isn't locked anymore, so a slow client doesn't hold
+/// up the game.
+fn notify(clients: &[Arc<Client>], game: &GameCtx, event: &GameEvent) {
+    let msgs = {
+        let state = game.state();
+        let players = state
+            .players
+            .iter()
+            .map(|player| player.name.clone())
+            .collect::<Vec<_>>();
+        (0..clients.len())
+            .map(|idx| {
+                let mut msgs = vec![];
+                if let GameEvent::Finished { .. } = event {
+                    msgs.push(ServerMsg::Finished {
+                        result: describe_event(&game.game, &players, event),
+                    });
+                    return msgs;
+                }
+                if state.event_visible_to(event, Some(idx)) {
+                    msgs.push(ServerMsg::Event {
+                        text: describe_event(&game.game, &players, event),
+                    });
+                }
+                if let GameEvent::TurnStarted { .. } | GameEvent::Restored { .. } = event {
+                    msgs.push(ServerMsg::Table {
+                        lines: table_lines_of(game, &state, idx),
+                    });
+                }
+                msgs
+            })
+            .collect::<Vec<_>>()
+    };
+    for (client, msgs) in clients.iter().zip(msgs) {
+        for msg in msgs {
+            client.send(&msg);
+        }
+    }
+}
+
+/// the table as player `idx` sees it
+fn table_lines(game: &GameCtx, idx: usize) -> Vec<String> {
+    table_lines_of(game, &game.state(), idx)
+}
+
+fn table_lines_of(game: &GameCtx, state: &GameState, idx: usize) -> Vec<String> {
+    let mut lines = vec![format!("turn: {}", state.turns)];
+    if let Some(card) = state.discard_top() {
+        lines.push(format!(
+            "discard pile: {} on top",
+            game.game.cards[card].name
+        ));
+    }
+    lines.extend(inventory_lines(game, state.inventories_for(Some(idx))));
+    lines
+}
+
+/// prompts the player until they give a valid answer, players who didn't answer in time
+/// skip the decision. Players who lost their connection get the prompt once they rejoin.
+fn ask_client(client: &Client, game: &GameCtx, query: &Query) -> Option<String> {
+    let lines = prompt_lines(game, query);
+    *client.prompt.lock().unwrap() = Some(lines.clone());
+    client.send(&ServerMsg::Prompt { lines });
+    let answers = client.answers.lock().unwrap();
+    // answers which were sent before the player was asked don't count
+    while answers.try_recv().is_ok() {}
+    let answer = loop {
+        let answer = match game.deadline() {
+            Some(deadline) => answers
+                .recv_timeout(deadline.saturating_duration_since(Instant::now()))
+                .ok(),
+            // the client holds on to the sender
+            None => answers.recv().ok(),
+        };
+        let Some(answer) = answer else {
+            break None;
+        };
+        match query.parse(&game.game, &answer) {
+            Ok(_) => break Some(answer),
+            Err(reason) => client.send(&ServerMsg::Invalid { reason }),
+        }
+    };
+    *client.prompt.lock().unwrap() = None;
+    answer
+}
+
+/// Joins the game hosted at `addr` as `name`, the messages of the host are printed in the
+/// background. Returns the connection to send answers through.
+/// Joins the lobby at `addr`, players who lost the connection to a running game rejoin with
+/// the `token` they got when they joined.
+pub fn join(
+    id: usize,
+    addr: &str,
+    name: String,
+    token: Option<String>,
+) -> anyhow::Result<TcpStream> {
+    let mut stream = TcpStream::connect(addr)?;
+    send(
+        &mut stream,
+        &ClientMsg::Join {
+            name: name.clone(),
+            token,
+        },
+    )?;
+    let mut reader = stream.try_clone()?;
+    let addr = addr.to_string();
+    thread::spawn(move || {
+        let cli = CLI.get().unwrap();
+        loop {
+            let msg = match recv::<ServerMsg>(&mut reader) {
+                Ok(msg) => msg,
+                Err(err) => {
+                    cli.println(format!("Game {}: lost the connection: {}", id, err).as_str());
+                    break;
+                }
+            };
+            match msg {
+                ServerMsg::Lobby {
+                    game,
+                    min_players,
+                    max_players,
+                    players,
+                } => {
+                    let players = players
+                        .iter()
+                        .map(|player| {
+                            if player.ready {
+                                format!("{} (ready)", player.name)
+                            } else {
+                                player.name.clone()
+                            }
+                        })
+                        .collect::<Vec<_>>();
+                    cli.println(
+                        format!(
+                            "Game {}: {} for {} to {} players, {} are in the lobby: {}",
+                            id,
+                            game,
+                            min_players,
+                            max_players,
+                            players.len(),
+                            players.join(", ")
+                        )
+                        .as_str(),
+                    );
+                }
+                ServerMsg::Rejected { reason } => {
+                    cli.println(format!("Game {}: couldn't join, {}", id, reason).as_str());
+                    break;
+                }
+                ServerMsg::Joined { token } => cli.println(
+                    format!(
+                        "Game {}: rejoin with `join {} {} {}` if you lose the connection",
+                        id, addr, name, token
+                    )
+                    .as_str(),
+                ),
+                ServerMsg::Event { text } => cli.println(format!("Game {}: {}", id, text).as_str()),
+                ServerMsg::Table { lines } => {
+                    cli.println(format!("Game {}:", id).as_str());
+                    for line in lines {
+                        cli.println(line.as_str());
+                    }
+                }
+                ServerMsg::Prompt { lines } => {
+                    cli.println(
+                        format!("Game {} is waiting for your answer (`answer {}`):", id, id)
+                            .as_str(),
+                    );
+                    for line in lines {
+                        cli.println(line.as_str());
+                    }
+                }
+                ServerMsg::Chat { from, text, emote } => {
+                    cli.println(format!("Game {}: {}", id, chat_line(&from, &text, emote)).as_str())
+                }
+                ServerMsg::Invalid { reason } => {
+                    cli.println(format!("Game {}: {}, try again", id, reason).as_str())
+                }
+                ServerMsg::Finished { result } => {
+                    cli.println(format!("Game {}: {}", id, result).as_str());
+                    break;
+                }
+            }
+        }
+    });
+    Ok(stream)
+}
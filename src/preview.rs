@@ -0,0 +1,206 @@
+//! Renders card images inside the terminal, either with one of the graphics protocols
+//! (kitty, sixel) or as dithered ascii art on terminals which support neither.
+
+use std::{
+    collections::BTreeMap,
+    env,
+    io::{self, Cursor, Write},
+};
+
+use base64::{engine::general_purpose::STANDARD, Engine};
+use image::{imageops::FilterType, DynamicImage, ImageFormat};
+
+use crate::CLI;
+
+/// the size in pixels images are scaled to fit into before they are sent to the terminal
+const PIXELS: u32 = 360;
+/// the width of ascii art in columns
+const COLUMNS: u32 = 48;
+/// from dark to bright, meant for terminals with a dark background
+const RAMP: &[u8] = b" .:-=+*#%@";
+/// the terminal doesn't accept more base64 than this per kitty escape sequence
+const KITTY_CHUNK: usize = 4096;
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Protocol {
+    Kitty,
+    Sixel,
+    Ascii,
+}
+
+impl Protocol {
+    pub fn parse(name: &str) -> anyhow::Result<Self> {
+        match name {
+            "kitty" => Ok(Protocol::Kitty),
+            "sixel" => Ok(Protocol::Sixel),
+            "ascii" => Ok(Protocol::Ascii),
+            _ => Err(anyhow::anyhow!(
+                "Unknown protocol `{}`, use kitty, sixel or ascii",
+                name
+            )),
+        }
+    }
+
+    /// Guesses the protocol the terminal supports from its environment, asking the
+    /// terminal itself would mix its reply into the cli's input.
+    pub fn detect() -> Self {
+        let var = |name: &str| env::var(name).unwrap_or_default();
+        let term = var("TERM");
+        let program = var("TERM_PROGRAM");
+        if env::var_os("KITTY_WINDOW_ID").is_some()
+            || term == "xterm-kitty"
+            || matches!(program.as_str(), "WezTerm" | "ghostty")
+        {
+            Protocol::Kitty
+        } else if term.contains("sixel")
+            || term.starts_with("foot")
+            || matches!(program.as_str(), "mlterm" | "iTerm.app")
+        {
+            Protocol::Sixel
+        } else {
+            Protocol::Ascii
+        }
+    }
+}
+
+/// Prints `image` to the terminal.
+pub fn show(image: &DynamicImage, protocol: Protocol) -> anyhow::Result<()> {
+    let out = match protocol {
+        Protocol::Kitty => kitty(&image.resize(PIXELS, PIXELS, FilterType::Triangle))?,
+        Protocol::Sixel => sixel(&image.resize(PIXELS, PIXELS, FilterType::Triangle)),
+        Protocol::Ascii => {
+            let cli = CLI.get().unwrap();
+            for line in ascii(image) {
+                cli.println(line.as_str());
+            }
+            return Ok(());
+        }
+    };
+    // escape sequences have to reach the terminal as they are
+    let mut stdout = io::stdout().lock();
+    stdout.write_all(out.as_bytes())?;
+    // the image ends where the cli's next line starts
+    stdout.write_all(b"\n")?;
+    stdout.flush()?;
+    Ok(())
+}
+
+/// the image as a png split into chunks, see
+/// https://sw.kovidgoyal.net/kitty/graphics-protocol/
+fn kitty(image: &DynamicImage) -> anyhow::Result<String> {
+    let mut png = vec![];
+    image.write_to(&mut Cursor::new(&mut png), ImageFormat::Png)?;
+    let data = STANDARD.encode(png);
+    let chunks = data.as_bytes().chunks(KITTY_CHUNK).collect::<Vec<_>>();
+    let mut out = String::new();
+    for (idx, chunk) in chunks.iter().enumerate() {
+        let more = (idx + 1 < chunks.len()) as u8;
+        let control = if idx == 0 {
+            format!("a=T,f=100,m={}", more)
+        } else {
+            format!("m={}", more)
+        };
+        out += &format!(
+            "\x1b_G{};{}\x1b\\",
+            control,
+            // base64 is ascii
+            std::str::from_utf8(chunk).unwrap()
+        );
+    }
+    Ok(out)
+}
+
+/// the image reduced to a 6x6x6 color cube, which is enough to tell whether the art looks
+/// right, in bands of six rows
+fn sixel(image: &DynamicImage) -> String {
+    let rgb = image.to_rgb8();
+    let (width, height) = (rgb.width(), rgb.height());
+    let color = |x: u32, y: u32| {
+        let [r, g, b] = rgb.get_pixel(x, y).0.map(|c| (c as usize * 5 + 127) / 255);
+        r * 36 + g * 6 + b
+    };
+    let mut out = format!("\x1bPq\"1;1;{};{}", width, height);
+    for idx in 0..216 {
+        // sixel colors are percentages
+        out += &format!(
+            "#{};2;{};{};{}",
+            idx,
+            idx / 36 * 20,
+            idx / 6 % 6 * 20,
+            idx % 6 * 20
+        );
+    }
+    for band in (0..height).step_by(6) {
+        // the rows of the band each color is drawn in, per column
+        let mut colors = BTreeMap::<usize, Vec<u8>>::new();
+        for y in band..(band + 6).min(height) {
+            for x in 0..width {
+                colors
+                    .entry(color(x, y))
+                    .or_insert_with(|| vec![0; width as usize])[x as usize] |= 1 << (y - band);
+            }
+        }
+        for (color, rows) in colors {
+            out += &format!("#{}", color);
+            let mut rows = rows.into_iter().peekable();
+            while let Some(bits) = rows.next() {
+                let mut cnt = 1;
+                while rows.next_if_eq(&bits).is_some() {
+                    cnt += 1;
+                }
+                let chr = (b'?' + bits) as char;
+                if cnt > 3 {
+                    out += &format!("!{}{}", cnt, chr);
+                } else {
+                    out.extend(std::iter::repeat(chr).take(cnt));
+                }
+            }
+            // the next color is drawn over the same band
+            out.push('$');
+        }
+        out.push('-');
+    }
+    out += "\x1b\\";
+    out
+}
+
+/// the brightness of the image mapped onto `RAMP` with floyd-steinberg dithering
+fn ascii(image: &DynamicImage) -> Vec<String> {
+    let width = COLUMNS.min(image.width().max(1));
+    // terminal cells are about twice as high as they are wide
+    let height = ((image.height() * width) as f32 / image.width().max(1) as f32 / 2.0)
+        .round()
+        .max(1.0) as u32;
+    let gray = image
+        .resize_exact(width, height, FilterType::Triangle)
+        .to_luma8();
+    let (width, height) = (width as usize, height as usize);
+    let mut values = gray
+        .as_raw()
+        .iter()
+        .map(|val| *val as f32)
+        .collect::<Vec<_>>();
+    let levels = (RAMP.len() - 1) as f32;
+    let mut lines = vec![];
+    for y in 0..height {
+        let mut line = String::new();
+        for x in 0..width {
+            let val = values[y * width + x].clamp(0.0, 255.0);
+            let level = (val / 255.0 * levels).round();
+            line.push(RAMP[level as usize] as char);
+            let err = val - level * 255.0 / levels;
+            let mut spread = |dx: isize, dy: usize, weight: f32| {
+                let x = x as isize + dx;
+                if x >= 0 && (x as usize) < width && y + dy < height {
+                    values[(y + dy) * width + x as usize] += err * weight;
+                }
+            };
+            spread(1, 0, 7.0 / 16.0);
+            spread(-1, 1, 3.0 / 16.0);
+            spread(0, 1, 5.0 / 16.0);
+            spread(1, 1, 1.0 / 16.0);
+        }
+        lines.push(line);
+    }
+    lines
+}
@@ -0,0 +1,183 @@
+//! Python bindings, mainly meant for running large numbers of simulated games
+//! in order to balance them.
+
+use std::{
+    panic::{self, AssertUnwindSafe},
+    sync::{atomic::Ordering, Arc},
+};
+
+use engine::{Program, Vm};
+use pyo3::prelude::*;
+
+use crate::{
+    funcs::silent_println,
+    game_ctx::{GameCtx, GameState},
+    Game, Session,
+};
+
+/// Compiles a script against the engine's builtin functions only.
+#[pyfunction]
+fn compile(src: &str) -> PyResult<PyProgram> {
+    Ok(PyProgram {
+        program: engine::compile(src, vec![])?,
+    })
+}
+
+#[pyclass(name = "Program", unsendable)]
+struct PyProgram {
+    program: Program,
+}
+
+#[pymethods]
+impl PyProgram {
+    fn run(&self) -> PyResult<()> {
+        Ok(self.program.execute()?)
+    }
+
+    fn instantiate(&self) -> PyVm {
+        PyVm {
+            vm: self.program.instantiate(),
+        }
+    }
+}
+
+#[pyclass(name = "Vm", unsendable)]
+struct PyVm {
+    vm: Vm,
+}
+
+#[pymethods]
+impl PyVm {
+    /// executes a single instruction, returns whether there is more to execute
+    fn step(&mut self) -> PyResult<bool> {
+        Ok(self.vm.step()?)
+    }
+}
+
+#[pyclass(name = "Game", unsendable)]
+struct PyGame {
+    game: Game,
+}
+
+#[pymethods]
+impl PyGame {
+    /// Loads a game from the games directory, silent games don't print anything.
+    #[staticmethod]
+    #[pyo3(signature = (name, silent = false))]
+    fn load(name: &str, silent: bool) -> PyResult<Self> {
+        let funcs = if silent {
+            vec![silent_println()]
+        } else {
+            vec![]
+        };
+        Ok(Self {
+            game: Game::load_with(name, funcs)?,
+        })
+    }
+
+    #[pyo3(signature = (players, seed = None))]
+    fn start(&self, players: Vec<String>, seed: Option<u64>) -> PyResult<PySession> {
+        let session = match seed {
+            Some(seed) => self.game.start_seeded(players, seed)?,
+            None => self.game.start(players)?,
+        };
+        Ok(PySession {
+            ctx: session.ctx().clone(),
+            session: Some(session),
+        })
+    }
+
+    /// Plays the game `runs` times with `players` bots and returns the final state of
+    /// each run, runs which failed are `None`.
+    fn simulate(&self, players: usize, runs: usize) -> PyResult<Vec<Option<PyGameState>>> {
+        (0..runs)
+            .map(|_| {
+                let session = self
+                    .game
+                    .start((1..=players).map(|idx| format!("Bot {idx}")).collect())?;
+                let ctx = session.ctx().clone();
+                // a panicking script shouldn't take down the whole simulation
+                Ok(
+                    match panic::catch_unwind(AssertUnwindSafe(|| session.run())) {
+                        Ok(Ok(_)) => Some(PyGameState::new(&ctx.state())),
+                        _ => None,
+                    },
+                )
+            })
+            .collect()
+    }
+}
+
+#[pyclass(name = "Session", unsendable)]
+struct PySession {
+    /// the session is gone once it was run to completion
+    session: Option<Session>,
+    ctx: Arc<GameCtx>,
+}
+
+#[pymethods]
+impl PySession {
+    /// executes a single instruction, returns whether there is more to execute
+    fn step(&mut self) -> PyResult<bool> {
+        match &mut self.session {
+            Some(session) => Ok(session.step()?),
+            None => Ok(false),
+        }
+    }
+
+    /// plays the rest of the game
+    fn run(&mut self) -> PyResult<()> {
+        if let Some(session) = self.session.take() {
+            session.run()?;
+        }
+        Ok(())
+    }
+
+    fn state(&self) -> PyGameState {
+        PyGameState::new(&self.ctx.state())
+    }
+
+    fn finished(&self) -> bool {
+        self.ctx.finished.load(Ordering::Acquire)
+    }
+}
+
+/// A copy of the state of a game at the time it was requested.
+#[pyclass(name = "GameState", get_all)]
+struct PyGameState {
+    turns: usize,
+    curr_player: usize,
+    winner: Option<usize>,
+    players: Vec<String>,
+    active: Vec<bool>,
+    /// the game's meta values as json
+    meta: String,
+}
+
+impl PyGameState {
+    fn new(state: &GameState) -> Self {
+        Self {
+            turns: state.turns,
+            curr_player: state.curr_player,
+            winner: state.winner,
+            players: state
+                .players
+                .iter()
+                .map(|player| player.name.clone())
+                .collect(),
+            active: state.players.iter().map(|player| player.active).collect(),
+            meta: serde_json::to_string(&state.meta).unwrap(),
+        }
+    }
+}
+
+#[pymodule]
+fn play_cards(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_function(wrap_pyfunction!(compile, m)?)?;
+    m.add_class::<PyProgram>()?;
+    m.add_class::<PyVm>()?;
+    m.add_class::<PyGame>()?;
+    m.add_class::<PySession>()?;
+    m.add_class::<PyGameState>()?;
+    Ok(())
+}
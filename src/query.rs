@@ -0,0 +1,166 @@
+use engine::Rng;
+
+use crate::game_ctx::GameTemplate;
+
+/// A decision a player has to make, hosts answer them through the handler registered with
+/// `Session::on_query`. Answers list the positions of the selected options separated by spaces.
+#[derive(Clone, Debug)]
+pub enum Query {
+    /// `player` has to select `cnt` of `cards` (indices of the game's cards), fewer if
+    /// `allow_partial` is set. If `force_different` is set, no two of the selected cards
+    /// may have the same name.
+    SelectCards {
+        player: usize,
+        cards: Vec<usize>,
+        cnt: usize,
+        force_different: bool,
+        allow_partial: bool,
+    },
+    /// `player` has to select `cnt` of the other `players`, fewer if `allow_partial` is set
+    SelectPlayers {
+        player: usize,
+        players: Vec<usize>,
+        cnt: usize,
+        allow_partial: bool,
+    },
+    /// `player` has to pick exactly one of the `options`
+    Choice { player: usize, options: Vec<String> },
+    /// `player` has to answer the yes/no `question` with `yes` or `no`
+    Confirm { player: usize, question: String },
+}
+
+impl Query {
+    /// the player who has to answer the query
+    pub fn player(&self) -> usize {
+        match self {
+            Query::SelectCards { player, .. }
+            | Query::SelectPlayers { player, .. }
+            | Query::Choice { player, .. }
+            | Query::Confirm { player, .. } => *player,
+        }
+    }
+
+    /// the number of options the player can select from
+    pub fn options(&self) -> usize {
+        match self {
+            Query::SelectCards { cards, .. } => cards.len(),
+            Query::SelectPlayers { players, .. } => players.len(),
+            Query::Choice { options, .. } => options.len(),
+            // no is 0, yes is 1
+            Query::Confirm { .. } => 2,
+        }
+    }
+
+    fn cnt(&self) -> (usize, bool) {
+        match self {
+            Query::SelectCards {
+                cnt, allow_partial, ..
+            }
+            | Query::SelectPlayers {
+                cnt, allow_partial, ..
+            } => (*cnt, *allow_partial),
+            Query::Choice { .. } | Query::Confirm { .. } => (1, false),
+        }
+    }
+
+    /// whether `pos` may be selected in addition to the already `selected` options
+    fn compatible(&self, game: &GameTemplate, selected: &[usize], pos: usize) -> bool {
+        match self {
+            Query::SelectCards {
+                cards,
+                force_different,
+                ..
+            } => {
+                !force_different
+                    || selected
+                        .iter()
+                        .all(|other| game.cards[cards[*other]].name != game.cards[cards[pos]].name)
+            }
+            Query::SelectPlayers { .. } | Query::Choice { .. } | Query::Confirm { .. } => true,
+        }
+    }
+
+    /// Validates `answer`, returns the selected positions or why the answer isn't valid.
+    pub fn parse(&self, game: &GameTemplate, answer: &str) -> Result<Vec<usize>, String> {
+        if let Query::Confirm { .. } = self {
+            return match answer.trim() {
+                "yes" | "y" => Ok(vec![1]),
+                "no" | "n" => Ok(vec![0]),
+                _ => Err("the answer has to be either yes or no".to_string()),
+            };
+        }
+        let mut selected = vec![];
+        for pos in answer.split_whitespace() {
+            let pos = pos
+                .parse::<usize>()
+                .ok()
+                .filter(|pos| *pos < self.options())
+                .ok_or_else(|| format!("`{pos}` isn't one of the options"))?;
+            if selected.contains(&pos) {
+                return Err(format!("`{pos}` was selected more than once"));
+            }
+            if !self.compatible(game, &selected, pos) {
+                return Err(format!(
+                    "`{pos}` can't be selected together with the others"
+                ));
+            }
+            selected.push(pos);
+        }
+        let (cnt, allow_partial) = self.cnt();
+        if selected.len() > cnt || (!allow_partial && selected.len() < cnt) {
+            return Err(format!(
+                "{} options have to be selected, but {} were",
+                cnt,
+                selected.len()
+            ));
+        }
+        Ok(selected)
+    }
+
+    /// Selects as little as possible, this is how players who ran out of time answer.
+    /// Queries which require a selection get a random one.
+    pub(crate) fn skip_answer(&self, game: &GameTemplate, rng: &mut Rng) -> String {
+        match self {
+            Query::Confirm { .. } => "no".to_string(),
+            _ if self.cnt().1 => String::new(),
+            _ => self.random_answer(game, rng),
+        }
+    }
+
+    /// makes a random selection, this is how queries nobody answers get answered
+    pub fn random_answer(&self, game: &GameTemplate, rng: &mut Rng) -> String {
+        if let Query::Confirm { .. } = self {
+            return if rng.gen_range(2) == 1 { "yes" } else { "no" }.to_string();
+        }
+        let mut options = (0..self.options()).collect::<Vec<_>>();
+        rng.shuffle(&mut options);
+        self.preferred_answer(game, options)
+    }
+
+    /// selects as many of the options as possible, preferring the ones which come first in
+    /// `options`, confirmations are answered with yes if it prefers `1`
+    pub(crate) fn preferred_answer(&self, game: &GameTemplate, options: Vec<usize>) -> String {
+        if let Query::Confirm { .. } = self {
+            return if options.first() == Some(&1) {
+                "yes"
+            } else {
+                "no"
+            }
+            .to_string();
+        }
+        let mut selected = vec![];
+        for pos in options {
+            if selected.len() == self.cnt().0 {
+                break;
+            }
+            if self.compatible(game, &selected, pos) {
+                selected.push(pos);
+            }
+        }
+        selected
+            .iter()
+            .map(|pos| pos.to_string())
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
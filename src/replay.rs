@@ -0,0 +1,52 @@
+use std::collections::VecDeque;
+
+use serde::{Deserialize, Serialize};
+
+#[cfg(feature = "fs")]
+pub const REPLAYS_DIR: &str = "./play_cards/replays/";
+/// the action logs of the games, a `GameEvent` per line in the order they happened.
+/// A game's log has the same name as its replay.
+#[cfg(feature = "fs")]
+pub const LOGS_DIR: &str = "./play_cards/logs/";
+
+/// Everything that's needed in order to re-execute a game exactly like it was played.
+#[derive(Deserialize, Serialize, Debug)]
+pub struct Replay {
+    pub game: String,
+    pub players: Vec<String>,
+    pub seed: u64,
+    pub decisions: Vec<Decision>,
+}
+
+#[cfg(feature = "fs")]
+impl Replay {
+    pub fn load(name: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(
+            std::fs::read_to_string(format!("{}{}.json", REPLAYS_DIR, name))?.as_str(),
+        )?)
+    }
+
+    pub fn save(&self, name: &str) -> anyhow::Result<()> {
+        std::fs::write(
+            format!("{}{}.json", REPLAYS_DIR, name),
+            serde_json::to_string_pretty(self)?,
+        )?;
+        Ok(())
+    }
+}
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct Decision {
+    pub player: usize,
+    pub answer: String,
+    /// the player ran out of time, so the decision was skipped
+    #[serde(default)]
+    pub timed_out: bool,
+}
+
+pub enum Decisions {
+    /// decisions are made by the players and recorded
+    Record(Vec<Decision>),
+    /// decisions are taken from a recorded game
+    Replay(VecDeque<Decision>),
+}
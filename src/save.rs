@@ -0,0 +1,43 @@
+use engine::VmSnapshot;
+use serde::{Deserialize, Serialize};
+
+use crate::{game_ctx::GameState, replay::Decision};
+
+#[cfg(feature = "fs")]
+pub const SAVES_DIR: &str = "./play_cards/saves/";
+
+/// Everything that's needed in order to continue a game where it was left off.
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct SaveGame {
+    pub game: String,
+    pub seed: u64,
+    pub state: GameState,
+    pub vm: VmSnapshot,
+    /// the decisions made so far, they become part of the replay of the resumed game
+    pub decisions: Vec<Decision>,
+}
+
+#[cfg(feature = "fs")]
+impl SaveGame {
+    pub fn load(slot: &str) -> anyhow::Result<Self> {
+        Ok(serde_json::from_str(
+            std::fs::read_to_string(format!("{}{}.json", SAVES_DIR, slot))?.as_str(),
+        )?)
+    }
+
+    pub fn save(&self, slot: &str) -> anyhow::Result<()> {
+        std::fs::write(
+            format!("{}{}.json", SAVES_DIR, slot),
+            serde_json::to_string(self)?,
+        )?;
+        Ok(())
+    }
+
+    /// removes the save game in `slot` if there is one
+    pub fn delete(slot: &str) -> anyhow::Result<()> {
+        match std::fs::remove_file(format!("{}{}.json", SAVES_DIR, slot)) {
+            Err(err) if err.kind() != std::io::ErrorKind::NotFound => Err(err.into()),
+            _ => Ok(()),
+        }
+    }
+}
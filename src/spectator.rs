@@ -0,0 +1,74 @@
+use std::{
+    sync::{
+        atomic::Ordering,
+        mpsc::{self, Receiver, RecvTimeoutError},
+        Arc,
+    },
+    time::Duration,
+};
+
+use crate::game_ctx::{GameCtx, GameEvent, InventoryView};
+
+/// A read-only view of a running game for people who aren't playing it. Spectators only
+/// see what's visible to everyone, unless they are allowed to see all hands (e.g. streams).
+pub struct Spectator {
+    game: Arc<GameCtx>,
+    all_hands: bool,
+    events: Receiver<GameEvent>,
+}
+
+impl Spectator {
+    pub fn attach(game: Arc<GameCtx>, all_hands: bool) -> Self {
+        let (sender, events) = mpsc::channel();
+        game.subscribe(move |game, event| {
+            // the visibility depends on the state right after the event happened
+            if all_hands || game.state().event_visible_to(event, None) {
+                // the spectator might have left already
+                let _ = sender.send(event.clone());
+            }
+        });
+        Self {
+            game,
+            all_hands,
+            events,
+        }
+    }
+
+    pub fn game(&self) -> &GameCtx {
+        &self.game
+    }
+
+    /// the inventories of the game as the spectator sees them
+    pub fn inventories(&self) -> Vec<InventoryView> {
+        let state = self.game.state();
+        if self.all_hands {
+            return state
+                .inventories
+                .iter()
+                .map(|inv| {
+                    InventoryView::Visible(
+                        inv.cards.iter().map(|card| card.idx() as usize).collect(),
+                    )
+                })
+                .collect();
+        }
+        state.inventories_for(None)
+    }
+
+    /// Blocks until the next event the spectator may see happens,
+    /// returns `None` once the game finished.
+    pub fn next_event(&self) -> Option<GameEvent> {
+        loop {
+            match self.events.recv_timeout(Duration::from_secs(1)) {
+                Ok(event) => return Some(event),
+                // spectators who attached just as the game finished never get to see `Finished`
+                Err(RecvTimeoutError::Timeout) => {
+                    if self.game.finished.load(Ordering::Acquire) {
+                        return None;
+                    }
+                }
+                Err(RecvTimeoutError::Disconnected) => return None,
+            }
+        }
+    }
+}
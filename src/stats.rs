@@ -0,0 +1,138 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::game_ctx::GameResult;
+
+#[cfg(feature = "fs")]
+pub const STATS_PATH: &str = "./play_cards/stats.json";
+
+/// The results of all games played so far, per game and player.
+#[derive(Deserialize, Serialize, Default, Debug)]
+pub struct Stats {
+    pub games: HashMap<String, HashMap<String, PlayerStats>>,
+}
+
+/// the elo rating of players who didn't play yet
+pub const DEFAULT_RATING: f64 = 1000.0;
+/// how much a single game can change a rating
+const RATING_FACTOR: f64 = 32.0;
+
+#[derive(Deserialize, Serialize, Clone, Debug)]
+pub struct PlayerStats {
+    /// the elo rating of the player, games are rated as if the winner beat every other
+    /// player and draws as if everyone drew against everyone
+    #[serde(default = "default_rating")]
+    pub rating: f64,
+    pub games: usize,
+    pub wins: usize,
+    /// games somebody else won
+    pub losses: usize,
+    /// the sum of the scores of all games which kept scores
+    pub total_score: f64,
+}
+
+fn default_rating() -> f64 {
+    DEFAULT_RATING
+}
+
+impl Default for PlayerStats {
+    fn default() -> Self {
+        Self {
+            rating: DEFAULT_RATING,
+            games: 0,
+            wins: 0,
+            losses: 0,
+            total_score: 0.0,
+        }
+    }
+}
+
+impl PlayerStats {
+    /// games which nobody won
+    pub fn draws(&self) -> usize {
+        self.games - self.wins - self.losses
+    }
+}
+
+impl Stats {
+    /// The rating of `player` in `game`.
+    pub fn rating(&self, game: &str, player: &str) -> f64 {
+        self.games
+            .get(game)
+            .and_then(|players| players.get(player))
+            .map_or(DEFAULT_RATING, |stats| stats.rating)
+    }
+
+    /// The players of `game` ranked by their rating, the best first.
+    pub fn leaderboard(&self, game: &str) -> Vec<(&str, &PlayerStats)> {
+        let mut players = self
+            .games
+            .get(game)
+            .into_iter()
+            .flatten()
+            .map(|(name, stats)| (name.as_str(), stats))
+            .collect::<Vec<_>>();
+        players.sort_unstable_by(|(_, a), (_, b)| b.rating.total_cmp(&a.rating));
+        players
+    }
+
+    /// Adds the result of a finished game to the stats of its players.
+    pub fn record(&mut self, result: &GameResult) {
+        let decided = result.players.iter().any(|player| player.won);
+        let game = self.games.entry(result.game.clone()).or_default();
+        // all rating changes are based on the ratings before the game
+        let ratings = result
+            .players
+            .iter()
+            .map(|player| {
+                game.get(&player.name)
+                    .map_or(DEFAULT_RATING, |stats| stats.rating)
+            })
+            .collect::<Vec<_>>();
+        for (idx, player) in result.players.iter().enumerate() {
+            let mut change = 0.0;
+            for (other_idx, other) in result.players.iter().enumerate() {
+                let outcome = match (player.won, other.won) {
+                    _ if idx == other_idx => continue,
+                    (true, _) => 1.0,
+                    (false, true) => 0.0,
+                    // losers didn't play against each other
+                    (false, false) if decided => continue,
+                    (false, false) => 0.5,
+                };
+                let expected =
+                    1.0 / (1.0 + 10f64.powf((ratings[other_idx] - ratings[idx]) / 400.0));
+                change += RATING_FACTOR * (outcome - expected);
+            }
+            game.entry(player.name.clone()).or_default().rating += change;
+        }
+        for player in result.players.iter() {
+            let stats = game.entry(player.name.clone()).or_default();
+            stats.games += 1;
+            if player.won {
+                stats.wins += 1;
+            } else if decided {
+                stats.losses += 1;
+            }
+            stats.total_score += player.score.unwrap_or(0.0);
+        }
+    }
+}
+
+#[cfg(feature = "fs")]
+impl Stats {
+    /// Loads the stats, there are none until the first game was recorded.
+    pub fn load() -> anyhow::Result<Self> {
+        match std::fs::read_to_string(STATS_PATH) {
+            Ok(stats) => Ok(serde_json::from_str(&stats)?),
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(Self::default()),
+            Err(err) => Err(err.into()),
+        }
+    }
+
+    pub fn save(&self) -> anyhow::Result<()> {
+        std::fs::write(STATS_PATH, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+//! Game and card templates can be written in json, toml or yaml. The format of a template
+//! is picked by the extension of its file and kept when the template gets changed.
+
+use std::path::{Path, PathBuf};
+
+use serde::{de::DeserializeOwned, Serialize};
+
+/// the extensions templates are looked up with, in order
+const EXTENSIONS: [&str; 4] = ["json", "toml", "yaml", "yml"];
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    Json,
+    Toml,
+    Yaml,
+}
+
+impl Format {
+    /// The format of the file at `path`, files without an extension are json.
+    pub fn of(path: &Path) -> anyhow::Result<Self> {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            None | Some("json") => Ok(Format::Json),
+            Some("toml") => Ok(Format::Toml),
+            Some("yaml" | "yml") => Ok(Format::Yaml),
+            Some(ext) => Err(anyhow::anyhow!(
+                "`{}` isn't a template format, use json, toml or yaml",
+                ext
+            )),
+        }
+    }
+
+    pub fn parse<T: DeserializeOwned>(self, src: &str) -> anyhow::Result<T> {
+        Ok(match self {
+            Format::Json => serde_json::from_str(src)?,
+            Format::Toml => toml::from_str(src)?,
+            Format::Yaml => serde_yaml::from_str(src)?,
+        })
+    }
+
+    pub fn serialize<T: Serialize>(self, val: &T) -> anyhow::Result<String> {
+        Ok(match self {
+            Format::Json => serde_json::to_string_pretty(val)?,
+            Format::Toml => toml::to_string_pretty(val)?,
+            Format::Yaml => serde_yaml::to_string(val)?,
+        })
+    }
+}
+
+/// Reads the template at `path` in the format of its extension.
+pub fn read<T: DeserializeOwned>(path: impl AsRef<Path>) -> anyhow::Result<T> {
+    let path = path.as_ref();
+    let src = std::fs::read_to_string(path)
+        .map_err(|err| anyhow::anyhow!("{} couldn't be read: {}", path.display(), err))?;
+    Format::of(path)?
+        .parse(&src)
+        .map_err(|err| anyhow::anyhow!("{} isn't a valid template: {}", path.display(), err))
+}
+
+/// Writes `val` to `path` in the format of its extension.
+pub fn write<T: Serialize>(path: impl AsRef<Path>, val: &T) -> anyhow::Result<()> {
+    let path = path.as_ref();
+    std::fs::write(path, Format::of(path)?.serialize(val)?)?;
+    Ok(())
+}
+
+/// The path of the template called `name` in `dir`, whichever format it's written in.
+/// Templates which don't exist yet are json.
+pub fn find(dir: &str, name: &str) -> PathBuf {
+    EXTENSIONS
+        .iter()
+        .map(|ext| Path::new(dir).join(format!("{}.{}", name, ext)))
+        .find(|path| path.exists())
+        .unwrap_or_else(|| Path::new(dir).join(format!("{}.json", name)))
+}
+
+/// The path `name` gets when it's renamed or copied from the template at `path`, the copy
+/// keeps the format.
+pub fn renamed(path: &Path, name: &str) -> PathBuf {
+    let ext = path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .unwrap_or("json");
+    path.with_file_name(format!("{}.{}", name, ext))
+}
+
+/// The names of all templates in `dir`, sorted.
+pub fn names(dir: &str) -> Vec<String> {
+    let mut names = std::fs::read_dir(dir)
+        .into_iter()
+        .flatten()
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if !EXTENSIONS.contains(&path.extension()?.to_str()?) {
+                return None;
+            }
+            path.file_stem()?.to_str().map(str::to_string)
+        })
+        .collect::<Vec<_>>();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Like `find`, but fails if there is no such template.
+pub fn existing(dir: &str, kind: &str, name: &str) -> anyhow::Result<PathBuf> {
+    let path = find(dir, name);
+    if !path.exists() {
+        return Err(anyhow::anyhow!("There is no {} called {}", kind, name));
+    }
+    Ok(path)
+}
+
+/// the number of characters which have to be inserted, removed or replaced to turn `a`
+/// into `b`
+fn distance(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, a) in a.chars().enumerate() {
+        let mut prev = row[0];
+        row[0] = i + 1;
+        for (j, b) in b.iter().enumerate() {
+            let replaced = prev + (a != *b) as usize;
+            prev = row[j + 1];
+            row[j + 1] = replaced.min(row[j] + 1).min(prev + 1);
+        }
+    }
+    row[b.len()]
+}
+
+/// How well `text` matches the search `query`, lower is better and `None` is no match.
+/// Texts containing the query come first, then texts containing its characters in order
+/// and last texts which are a typo away from it, case is ignored.
+pub fn fuzzy_score(query: &str, text: &str) -> Option<usize> {
+    let (query, text) = (query.to_lowercase(), text.to_lowercase());
+    if let Some(pos) = text.find(&query) {
+        // matches at the start of the text are the best ones
+        return Some(pos.min(1));
+    }
+    let mut chars = text.chars();
+    let mut skipped = 0;
+    let subsequence = query.chars().all(|chr| {
+        for other in chars.by_ref() {
+            if other == chr {
+                return true;
+            }
+            skipped += 1;
+        }
+        false
+    });
+    if subsequence {
+        return Some(2 + skipped);
+    }
+    let dist = distance(&query, &text);
+    (dist <= 2).then_some(100 + dist)
+}
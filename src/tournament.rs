@@ -0,0 +1,327 @@
+//! Tournaments play a game in many matches between their entrants and rank them by the
+//! results. Either everyone plays everyone (round robin) or the winners of a round meet
+//! in the next one until a single entrant is left (single elimination).
+
+use std::{cmp::Reverse, collections::HashMap};
+
+use play_cards::{bot::Bot, game_ctx::GameResult, stats::Stats, Game};
+
+use crate::{play_locally, prepare_session, CliCtx, CLI};
+
+#[derive(Clone, Copy, PartialEq, Eq, Debug)]
+pub enum Format {
+    RoundRobin,
+    Elimination,
+}
+
+impl Format {
+    pub fn parse(name: &str) -> anyhow::Result<Self> {
+        match name {
+            "round-robin" => Ok(Format::RoundRobin),
+            "elimination" => Ok(Format::Elimination),
+            _ => Err(anyhow::anyhow!(
+                "Unknown format `{}`, use round-robin or elimination",
+                name
+            )),
+        }
+    }
+}
+
+pub struct Entrant {
+    pub name: String,
+    /// the bot playing for the entrant, `None` if a person plays
+    pub bot: Option<String>,
+}
+
+impl Entrant {
+    /// `name@bot` lets a bot play for the entrant, e.g. `Bob@greedy`
+    pub fn parse(arg: &str) -> Self {
+        match arg.split_once('@') {
+            Some((name, bot)) => Entrant {
+                name: name.to_string(),
+                bot: Some(bot.to_string()),
+            },
+            None => Entrant {
+                name: arg.to_string(),
+                bot: None,
+            },
+        }
+    }
+}
+
+#[derive(Default)]
+struct Standing {
+    wins: usize,
+    draws: usize,
+    losses: usize,
+    /// the sum of the entrant's scores, if the game keeps scores
+    score: f64,
+    /// the round the entrant lost in, `None` while the entrant is still in the tournament
+    eliminated: Option<usize>,
+}
+
+impl Standing {
+    /// a win is worth a point, a draw half of one
+    fn points(&self) -> f64 {
+        self.wins as f64 + self.draws as f64 / 2.0
+    }
+}
+
+pub struct Tournament {
+    game: Game,
+    entrants: Vec<Entrant>,
+    format: Format,
+    /// the number of entrants playing each match
+    size: usize,
+    standings: Vec<Standing>,
+}
+
+impl Tournament {
+    pub fn new(game: Game, entrants: Vec<Entrant>, format: Format) -> anyhow::Result<Self> {
+        let template = game.template();
+        let (min, max) = (template.min_players, template.max_players);
+        // matches between two entrants are the fairest, other games are played by as few
+        // entrants as possible
+        let size = if (min..=max).contains(&2) { 2 } else { min };
+        if size < 2 {
+            return Err(anyhow::anyhow!(
+                "{} is played by a single player, so there is nobody to play against",
+                template.name
+            ));
+        }
+        if format == Format::Elimination && size != 2 {
+            return Err(anyhow::anyhow!(
+                "Single elimination needs a game for 2 players, but {} is played by {} to {}",
+                template.name,
+                min,
+                max
+            ));
+        }
+        if entrants.len() < size {
+            return Err(anyhow::anyhow!(
+                "A match of {} needs {} entrants, but there are only {}",
+                template.name,
+                size,
+                entrants.len()
+            ));
+        }
+        for (idx, entrant) in entrants.iter().enumerate() {
+            if entrants[..idx]
+                .iter()
+                .any(|other| other.name == entrant.name)
+            {
+                return Err(anyhow::anyhow!(
+                    "{} entered the tournament twice",
+                    entrant.name
+                ));
+            }
+            if let Some(bot) = &entrant.bot {
+                Bot::load(bot)?;
+            }
+        }
+        let standings = entrants.iter().map(|_| Standing::default()).collect();
+        Ok(Self {
+            game,
+            entrants,
+            format,
+            size,
+            standings,
+        })
+    }
+
+    /// Plays all matches one after another and prints the standings, a match which fails
+    /// counts as a draw.
+    pub fn run(mut self, ctx: &CliCtx) {
+        let res = match self.format {
+            Format::RoundRobin => self.round_robin(ctx),
+            Format::Elimination => self.elimination(ctx),
+        };
+        let cli = CLI.get().unwrap();
+        if let Err(err) = res {
+            cli.println(format!("Tournament: {:#}", err).as_str());
+            return;
+        }
+        self.print_standings();
+    }
+
+    fn round_robin(&mut self, ctx: &CliCtx) -> anyhow::Result<()> {
+        let matches = combinations(self.entrants.len(), self.size);
+        for (idx, seats) in matches.iter().enumerate() {
+            let label = format!("match {} of {}", idx + 1, matches.len());
+            let result = self.play(ctx, seats, &label)?;
+            self.record(seats, result.as_ref());
+        }
+        Ok(())
+    }
+
+    fn elimination(&mut self, ctx: &CliCtx) -> anyhow::Result<()> {
+        let stats = Stats::load()?;
+        let game = &self.game.template().name;
+        // the best rated entrants are seeded first, they get the byes and meet last
+        let mut alive = (0..self.entrants.len()).collect::<Vec<_>>();
+        alive.sort_by(|a, b| {
+            let rating = |idx: &usize| stats.rating(game, &self.entrants[*idx].name);
+            rating(b).total_cmp(&rating(a))
+        });
+        let mut round = 1;
+        while alive.len() > 1 {
+            let byes = alive.len() % 2;
+            let playing = alive[byes..].to_vec();
+            let mut next = alive[..byes].to_vec();
+            for bye in &next {
+                CLI.get().unwrap().println(
+                    format!(
+                        "Tournament: {} advances to round {} without playing",
+                        self.entrants[*bye].name,
+                        round + 1
+                    )
+                    .as_str(),
+                );
+            }
+            let matches = playing.len() / 2;
+            for idx in 0..matches {
+                // the best seed plays the worst one
+                let seats = [playing[idx], playing[playing.len() - 1 - idx]];
+                let label = format!("round {}, match {} of {}", round, idx + 1, matches);
+                let result = self.play(ctx, &seats, &label)?;
+                self.record(&seats, result.as_ref());
+                let winner = advancing(result.as_ref());
+                let loser = seats[1 - winner];
+                self.standings[loser].eliminated = Some(round);
+                if !result.iter().any(|result| result.players[winner].won) {
+                    CLI.get().unwrap().println(
+                        format!(
+                            "Tournament: nobody won, {} advances",
+                            self.entrants[seats[winner]].name
+                        )
+                        .as_str(),
+                    );
+                }
+                next.push(seats[winner]);
+            }
+            // the order of the seeds is kept
+            next.sort_by_key(|entrant| alive.iter().position(|other| other == entrant));
+            alive = next;
+            round += 1;
+        }
+        Ok(())
+    }
+
+    /// plays a match between the entrants at `seats` to its end, the result is `None` if
+    /// the match failed
+    fn play(
+        &self,
+        ctx: &CliCtx,
+        seats: &[usize],
+        label: &str,
+    ) -> anyhow::Result<Option<GameResult>> {
+        let players = seats
+            .iter()
+            .map(|idx| self.entrants[*idx].name.clone())
+            .collect::<Vec<_>>();
+        let mut bots = HashMap::new();
+        for (seat, idx) in seats.iter().enumerate() {
+            if let Some(bot) = &self.entrants[*idx].bot {
+                bots.insert(seat, Bot::load(bot)?);
+            }
+        }
+        let session = self.game.start(players.clone())?;
+        let game = session.ctx().clone();
+        let (id, run) = prepare_session(ctx, session, |session, id| {
+            play_locally(session, id, &ctx.prompts, bots)
+        })?;
+        CLI.get().unwrap().println(
+            format!(
+                "Tournament: {}, {} as session {}",
+                label,
+                players.join(" vs "),
+                id
+            )
+            .as_str(),
+        );
+        Ok(run().then(|| game.result()))
+    }
+
+    fn record(&mut self, seats: &[usize], result: Option<&GameResult>) {
+        let decided = result.is_some_and(|result| result.players.iter().any(|player| player.won));
+        for (seat, idx) in seats.iter().enumerate() {
+            let standing = &mut self.standings[*idx];
+            let Some(result) = result else {
+                standing.draws += 1;
+                continue;
+            };
+            let player = &result.players[seat];
+            if player.won {
+                standing.wins += 1;
+            } else if decided {
+                standing.losses += 1;
+            } else {
+                standing.draws += 1;
+            }
+            standing.score += player.score.unwrap_or(0.0);
+        }
+    }
+
+    fn print_standings(&self) {
+        let mut ranking = (0..self.entrants.len()).collect::<Vec<_>>();
+        // the entrants who lasted longest come first, then those with the most points
+        ranking.sort_by(|a, b| {
+            let (a, b) = (&self.standings[*a], &self.standings[*b]);
+            let round = |standing: &Standing| Reverse(standing.eliminated.unwrap_or(usize::MAX));
+            round(a)
+                .cmp(&round(b))
+                .then(b.points().total_cmp(&a.points()))
+                .then(b.score.total_cmp(&a.score))
+        });
+        let cli = CLI.get().unwrap();
+        cli.println(format!("Standings of the {} tournament:", self.game.template().name).as_str());
+        for (rank, idx) in ranking.iter().enumerate() {
+            let standing = &self.standings[*idx];
+            cli.println(
+                format!(
+                    "{}. {}: {} points ({} wins, {} draws, {} losses), score {}",
+                    rank + 1,
+                    self.entrants[*idx].name,
+                    standing.points(),
+                    standing.wins,
+                    standing.draws,
+                    standing.losses,
+                    standing.score
+                )
+                .as_str(),
+            );
+        }
+    }
+}
+
+/// the seat of the match's winner, matches nobody won are decided by the scores and then
+/// by the seeds
+fn advancing(result: Option<&GameResult>) -> usize {
+    let Some(result) = result else {
+        return 0;
+    };
+    if let Some(winner) = result.players.iter().position(|player| player.won) {
+        return winner;
+    }
+    match (result.players[0].score, result.players[1].score) {
+        (Some(first), Some(second)) if second > first => 1,
+        _ => 0,
+    }
+}
+
+/// all ways to pick `size` of `cnt` entrants, in lexicographic order
+fn combinations(cnt: usize, size: usize) -> Vec<Vec<usize>> {
+    let mut combinations = vec![];
+    let mut picked = (0..size).collect::<Vec<_>>();
+    loop {
+        combinations.push(picked.clone());
+        // advance the last position which can still move
+        let Some(pos) = (0..size).rev().find(|pos| picked[*pos] < cnt - size + pos) else {
+            return combinations;
+        };
+        picked[pos] += 1;
+        for next in pos + 1..size {
+            picked[next] = picked[next - 1] + 1;
+        }
+    }
+}
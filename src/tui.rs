@@ -0,0 +1,298 @@
+//! A full screen view of a running session which is redrawn as the game changes,
+//! answers are typed into it directly instead of going through the `answer` command.
+
+use std::{
+    collections::VecDeque,
+    io::{self, Stdout},
+    mem,
+    sync::{
+        mpsc::{self, Receiver},
+        Arc,
+    },
+    time::Duration,
+};
+
+use play_cards::{
+    game_ctx::{GameState, InventoryView},
+    GameCtx, GameEvent,
+};
+use ratatui::{
+    backend::CrosstermBackend,
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEventKind},
+        execute,
+        terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    layout::{Constraint, Layout, Rect},
+    style::{Color, Modifier, Style},
+    text::Line,
+    widgets::{Block, Paragraph, Wrap},
+    Frame, Terminal,
+};
+
+use crate::{describe_event, session_state, Prompts};
+
+/// the number of events the log shows
+const EVENTS: usize = 12;
+/// the glyph face down cards are shown as, terminals can't show the game's card back
+const CARD_BACK: &str = "\u{1F0A0}";
+/// how often the view is redrawn if nothing is typed, host functions change the game
+/// without notifying anyone
+const REFRESH: Duration = Duration::from_millis(100);
+
+/// Shows session `id` as `viewer` sees it until the view is closed with escape,
+/// spectators (`None`) only see what's public.
+pub fn show(
+    id: usize,
+    game: &Arc<GameCtx>,
+    viewer: Option<usize>,
+    prompts: &Prompts,
+) -> anyhow::Result<()> {
+    let (sender, events) = mpsc::channel();
+    game.subscribe(move |_, event| {
+        // the view might have been closed already
+        let _ = sender.send(event.clone());
+    });
+    enable_raw_mode()?;
+    execute!(io::stdout(), EnterAlternateScreen)?;
+    let res = Terminal::new(CrosstermBackend::new(io::stdout()))
+        .map_err(anyhow::Error::from)
+        .and_then(|mut terminal| {
+            View {
+                id,
+                game,
+                viewer,
+                log: VecDeque::new(),
+                input: String::new(),
+                status: String::new(),
+            }
+            .run(&mut terminal, prompts, &events)
+        });
+    // the cli has to be usable again even if drawing failed
+    disable_raw_mode()?;
+    execute!(io::stdout(), LeaveAlternateScreen)?;
+    res
+}
+
+struct View<'a> {
+    id: usize,
+    game: &'a GameCtx,
+    viewer: Option<usize>,
+    /// the latest events the viewer may see
+    log: VecDeque<String>,
+    /// the answer that's being typed
+    input: String,
+    status: String,
+}
+
+impl View<'_> {
+    fn run(
+        &mut self,
+        terminal: &mut Terminal<CrosstermBackend<Stdout>>,
+        prompts: &Prompts,
+        events: &Receiver<GameEvent>,
+    ) -> anyhow::Result<()> {
+        loop {
+            while let Ok(event) = events.try_recv() {
+                self.record(&event);
+            }
+            let prompt = prompts
+                .lock()
+                .unwrap()
+                .get(&self.id)
+                .map(|prompt| prompt.lines.clone());
+            terminal.draw(|frame| self.draw(frame, prompt.as_deref()))?;
+            if !event::poll(REFRESH)? {
+                continue;
+            }
+            let Event::Key(key) = event::read()? else {
+                continue;
+            };
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            match key.code {
+                KeyCode::Esc => return Ok(()),
+                KeyCode::Enter => {
+                    let answer = mem::take(&mut self.input);
+                    self.status = match prompts.lock().unwrap().get(&self.id) {
+                        Some(prompt) => {
+                            // the prompt only disappears once the answer was accepted
+                            let _ = prompt.answers.send(answer);
+                            String::new()
+                        }
+                        None => "The game isn't waiting for an answer".to_string(),
+                    };
+                }
+                KeyCode::Backspace => {
+                    self.input.pop();
+                }
+                KeyCode::Char(chr) => self.input.push(chr),
+                _ => {}
+            }
+        }
+    }
+
+    fn record(&mut self, event: &GameEvent) {
+        let state = self.game.state();
+        if !state.event_visible_to(event, self.viewer) {
+            return;
+        }
+        let players = state
+            .players
+            .iter()
+            .map(|player| player.name.clone())
+            .collect::<Vec<_>>();
+        self.log
+            .push_back(describe_event(&self.game.game, &players, event));
+        if self.log.len() > EVENTS {
+            self.log.pop_front();
+        }
+    }
+
+    fn draw(&self, frame: &mut Frame, prompt: Option<&[String]>) {
+        let state = self.game.state();
+        let prompt = prompt.unwrap_or_default();
+        let [header, body, prompt_area, input] = Layout::vertical([
+            Constraint::Length(3),
+            Constraint::Min(8),
+            Constraint::Length(prompt.len() as u16 + 2),
+            Constraint::Length(3),
+        ])
+        .areas(frame.area());
+        let [table, players] =
+            Layout::horizontal([Constraint::Percentage(35), Constraint::Percentage(65)])
+                .areas(body);
+        let [table, log] = Layout::vertical([
+            Constraint::Length(state.team_scores.len() as u16 + 5),
+            Constraint::Min(0),
+        ])
+        .areas(table);
+
+        let title = format!(
+            "{} (session {}, {}) - turn {}, round {}",
+            self.game.game.name,
+            self.id,
+            session_state(self.game),
+            state.turns,
+            state.round
+        );
+        frame.render_widget(
+            Paragraph::new(Line::from(title)).block(Block::bordered().title("play-cards")),
+            header,
+        );
+        render_lines(frame, "Table", self.table_lines(&state), table);
+        render_lines(
+            frame,
+            "Events",
+            self.log.iter().cloned().map(Line::from).collect(),
+            log,
+        );
+        render_lines(frame, "Players", self.player_lines(&state), players);
+        render_lines(
+            frame,
+            "Prompt",
+            prompt.iter().cloned().map(Line::from).collect(),
+            prompt_area,
+        );
+        let input_line = if self.status.is_empty() {
+            Line::from(format!("> {}", self.input))
+        } else {
+            Line::from(self.status.clone()).style(Style::new().fg(Color::Red))
+        };
+        frame.render_widget(
+            Paragraph::new(input_line).block(Block::bordered().title("Answer (esc closes)")),
+            input,
+        );
+    }
+
+    fn table_lines(&self, state: &GameState) -> Vec<Line<'static>> {
+        let mut lines = vec![Line::from(format!(
+            "draw stack: {} cards",
+            state.draw_stack.len()
+        ))];
+        lines.push(Line::from(match state.discard_top() {
+            Some(card) => format!(
+                "discard pile: {} cards, {} on top",
+                state.discard_pile.len(),
+                self.game.game.cards[card].name
+            ),
+            None => "discard pile: empty".to_string(),
+        }));
+        let mut teams = state.team_scores.iter().collect::<Vec<_>>();
+        teams.sort_by_key(|(team, _)| **team);
+        for (team, score) in teams {
+            lines.push(Line::from(format!("team {}: {} points", team, score)));
+        }
+        lines
+    }
+
+    /// every player with their score and their hand, masked unless the viewer may see it
+    fn player_lines(&self, state: &GameState) -> Vec<Line<'static>> {
+        let inventories = state.inventories_for(self.viewer);
+        let mut lines = vec![];
+        for (idx, player) in state.players.iter().enumerate() {
+            let mut name = player.name.clone();
+            if let Some(engine::RtValue::Decimal(score)) = player.meta.get("score") {
+                name += &format!(", {} points", score);
+            }
+            if !player.active {
+                name += ", out";
+            }
+            if player.disconnected {
+                name += ", disconnected";
+            }
+            let style = if idx == state.curr_player {
+                Style::new().add_modifier(Modifier::BOLD).fg(Color::Yellow)
+            } else {
+                Style::new()
+            };
+            lines.push(Line::from(name).style(style));
+            if let Some(hand) = player.hand {
+                lines.push(Line::from(format!(
+                    "  {}",
+                    self.inventory(&inventories[hand as usize])
+                )));
+            }
+        }
+        // the inventories which don't belong to anyone
+        let hands = state
+            .players
+            .iter()
+            .filter_map(|player| player.hand)
+            .collect::<Vec<_>>();
+        for (idx, inv) in inventories.iter().enumerate() {
+            if !hands.contains(&(idx as u64)) {
+                lines.push(Line::from(format!(
+                    "inventory {}: {}",
+                    idx,
+                    self.inventory(inv)
+                )));
+            }
+        }
+        lines
+    }
+
+    fn inventory(&self, inv: &InventoryView) -> String {
+        match inv {
+            InventoryView::Visible(cards) if cards.is_empty() => "no cards".to_string(),
+            InventoryView::Visible(cards) => cards
+                .iter()
+                .map(|card| self.game.game.cards[*card].name.as_str())
+                .collect::<Vec<_>>()
+                .join(", "),
+            InventoryView::Hidden(cards) => {
+                format!("{} ({} face down cards)", CARD_BACK.repeat(*cards), cards)
+            }
+        }
+    }
+}
+
+fn render_lines(frame: &mut Frame, title: &str, lines: Vec<Line<'static>>, area: Rect) {
+    frame.render_widget(
+        Paragraph::new(lines)
+            .block(Block::bordered().title(title.to_string()))
+            .wrap(Wrap { trim: false }),
+        area,
+    );
+}